@@ -0,0 +1,410 @@
+//! `chex` - headless command-line front end over the ChexExplorer engine.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use clap::{Parser, Subcommand, ValueEnum};
+use tokio::sync::mpsc;
+
+use chexexplorer::core::file_item::{FileItem, FileType, SortBy, SortOrder};
+use chexexplorer::core::search::{FileSearcher, SearchQuery};
+use chexexplorer::operations::attributes::PreserveOptions;
+use chexexplorer::operations::checksum::HashAlgorithm;
+use chexexplorer::operations::conflict::ConflictPolicy;
+use chexexplorer::operations::copy::{self, CopyErrorMode, CopyErrors, CopyOperation, CopyProgress};
+use chexexplorer::operations::rename::MoveOperation;
+use chexexplorer::operations::{compress, delete, extract};
+use chexexplorer::utils::format::{format_file_size, format_timestamp};
+
+#[derive(Parser)]
+#[command(name = "chex", version, about = "ChexExplorer file operations from the command line")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the contents of a directory
+    Ls {
+        /// Directory to list (defaults to the current directory)
+        path: Option<PathBuf>,
+        /// Include hidden files
+        #[arg(short, long)]
+        all: bool,
+        /// Sort key
+        #[arg(short, long, value_enum, default_value_t = SortKey::Name)]
+        sort: SortKey,
+        /// Sort in descending order
+        #[arg(short, long)]
+        reverse: bool,
+        /// Print items as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Copy files or folders into a directory
+    Cp {
+        #[arg(required = true, num_args = 1..)]
+        sources: Vec<PathBuf>,
+        destination: PathBuf,
+        /// Overwrite existing files
+        #[arg(short, long)]
+        force: bool,
+        /// What to do with files that already exist (instead of failing)
+        #[arg(long, value_enum, conflicts_with = "force")]
+        on_conflict: Option<ConflictChoice>,
+        /// Copy what symbolic links point to instead of the links
+        #[arg(short = 'L', long)]
+        dereference: bool,
+        /// Also keep ownership and extended attributes
+        #[arg(short, long)]
+        archive: bool,
+        /// Number of files to copy at the same time
+        #[arg(short, long, default_value_t = copy::DEFAULT_WORKERS)]
+        jobs: usize,
+        /// Copy everything that can be copied and list the failures at the end
+        #[arg(long)]
+        keep_going: bool,
+        /// Compare checksums of every file and its copy (BLAKE3 unless named)
+        #[arg(long, value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "blake3")]
+        verify: Option<HashChoice>,
+    },
+    /// Move files or folders into a directory, or rename a single source
+    Mv {
+        #[arg(required = true, num_args = 1..)]
+        sources: Vec<PathBuf>,
+        destination: PathBuf,
+        /// When moving means copying, compare checksums before deleting the source
+        #[arg(long, value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "blake3")]
+        verify: Option<HashChoice>,
+    },
+    /// Delete files or folders
+    Rm {
+        #[arg(required = true, num_args = 1..)]
+        paths: Vec<PathBuf>,
+        /// Move to the trash instead of deleting permanently
+        #[arg(long)]
+        trash: bool,
+    },
+    /// Create an archive (.zip, or .tar when the output ends in .tar)
+    Zip {
+        output: PathBuf,
+        #[arg(required = true, num_args = 1..)]
+        sources: Vec<PathBuf>,
+    },
+    /// Extract a ZIP or TAR archive
+    Unzip {
+        archive: PathBuf,
+        /// Destination directory (defaults to the current directory)
+        destination: Option<PathBuf>,
+    },
+    /// Search for files by name or content
+    Find {
+        root: PathBuf,
+        pattern: String,
+        /// Treat the pattern as a regular expression
+        #[arg(long)]
+        regex: bool,
+        /// Match case-sensitively
+        #[arg(short = 'c', long)]
+        case_sensitive: bool,
+        /// Also search inside text files
+        #[arg(long)]
+        content: bool,
+        /// Restrict to these extensions (comma separated)
+        #[arg(short, long, value_delimiter = ',')]
+        ext: Vec<String>,
+        /// Print matches as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SortKey {
+    Name,
+    Size,
+    Modified,
+    Type,
+    Created,
+}
+
+impl From<SortKey> for SortBy {
+    fn from(key: SortKey) -> Self {
+        match key {
+            SortKey::Name => SortBy::Name,
+            SortKey::Size => SortBy::Size,
+            SortKey::Modified => SortBy::Modified,
+            SortKey::Type => SortBy::Type,
+            SortKey::Created => SortBy::Created,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ConflictChoice {
+    Skip,
+    Overwrite,
+    Newer,
+    DifferentSize,
+    KeepBoth,
+}
+
+impl From<ConflictChoice> for ConflictPolicy {
+    fn from(choice: ConflictChoice) -> Self {
+        match choice {
+            ConflictChoice::Skip => ConflictPolicy::Skip,
+            ConflictChoice::Overwrite => ConflictPolicy::Overwrite,
+            ConflictChoice::Newer => ConflictPolicy::OverwriteIfNewer,
+            ConflictChoice::DifferentSize => ConflictPolicy::OverwriteIfDifferentSize,
+            ConflictChoice::KeepBoth => ConflictPolicy::KeepBoth,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum HashChoice {
+    Blake3,
+    Sha256,
+    Xxh3,
+}
+
+impl From<HashChoice> for HashAlgorithm {
+    fn from(choice: HashChoice) -> Self {
+        match choice {
+            HashChoice::Blake3 => HashAlgorithm::Blake3,
+            HashChoice::Sha256 => HashAlgorithm::Sha256,
+            HashChoice::Xxh3 => HashAlgorithm::Xxh3,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+    let cli = Cli::parse();
+    if let Err(e) = run(cli.command).await {
+        eprintln!("chex: {:#}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Ls { path, all, sort, reverse, json } => {
+            let path = path.unwrap_or_else(|| PathBuf::from("."));
+            list(&path, all, sort.into(), reverse, json)
+        }
+        Command::Cp { sources, destination, force, on_conflict, dereference, archive, jobs, keep_going, verify } => {
+            let policy = match on_conflict {
+                Some(choice) => choice.into(),
+                None if force => ConflictPolicy::Overwrite,
+                None => ConflictPolicy::Ask,
+            };
+            let preserve = PreserveOptions {
+                ownership: archive,
+                xattrs: archive,
+                symlinks: !dereference,
+                ..PreserveOptions::default()
+            };
+            let error_mode = if keep_going { CopyErrorMode::CollectAll } else { CopyErrorMode::StopOnFirst };
+            let mut operation = CopyOperation::new(sources, destination)
+                .with_conflict_policy(policy)
+                .with_preserve(preserve)
+                .with_workers(jobs)
+                .with_error_mode(error_mode);
+            if let Some(choice) = verify {
+                operation = operation.with_verify(choice.into());
+            }
+            copy(operation).await
+        }
+        Command::Mv { sources, destination, verify } => {
+            move_items(sources, &destination, verify.map(HashAlgorithm::from)).await
+        }
+        Command::Rm { paths, trash } => delete::delete_files(paths, trash).await,
+        Command::Zip { output, sources } => {
+            let is_tar = output.extension().and_then(|e| e.to_str())
+                .map(|e| e.eq_ignore_ascii_case("tar"))
+                .unwrap_or(false);
+            if is_tar {
+                compress::create_tar_archive(sources, &output).await
+            } else {
+                compress::create_zip_archive(sources, &output).await
+            }
+        }
+        Command::Unzip { archive, destination } => {
+            let destination = destination.unwrap_or_else(|| PathBuf::from("."));
+            extract::extract_archive(&archive, &destination).await
+        }
+        Command::Find { root, pattern, regex, case_sensitive, content, ext, json } => {
+            let query = SearchQuery {
+                pattern,
+                is_regex: regex,
+                case_sensitive,
+                search_in_content: content,
+                file_types: ext.into_iter().map(|e| e.to_lowercase()).collect(),
+                ..Default::default()
+            };
+            find(&root, query, json).await
+        }
+    }
+}
+
+fn list(path: &Path, show_hidden: bool, sort_by: SortBy, reverse: bool, json: bool) -> Result<()> {
+    let mut items = Vec::new();
+
+    for entry in std::fs::read_dir(path)? {
+        let entry_path = entry?.path();
+        match FileItem::from_path(&entry_path) {
+            Ok(item) => {
+                if show_hidden || !item.is_hidden {
+                    items.push(item);
+                }
+            }
+            Err(e) => log::warn!("Failed to read file item {}: {}", entry_path.display(), e),
+        }
+    }
+
+    let sort_order = if reverse { SortOrder::Descending } else { SortOrder::Ascending };
+    FileItem::sort_items(&mut items, sort_by, sort_order);
+
+    if json {
+        return print_json(&items);
+    }
+
+    let mut out = std::io::stdout().lock();
+    for item in &items {
+        let (kind, size) = match item.file_type {
+            FileType::Directory => ("d", "-".to_string()),
+            FileType::SymbolicLink => ("l", format_file_size(item.size)),
+            FileType::RegularFile => ("-", format_file_size(item.size)),
+            FileType::Other => ("?", format_file_size(item.size)),
+        };
+        let line = format!("{} {:>10}  {}  {}", kind, size, format_timestamp(item.modified), item.name);
+        if !write_line(&mut out, &line)? {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints copy progress on stderr until every sender is dropped.
+fn report_progress() -> (mpsc::UnboundedSender<CopyProgress>, tokio::task::JoinHandle<()>) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<CopyProgress>();
+    let reporter = tokio::spawn(async move {
+        let mut reported = false;
+        while let Some(progress) = rx.recv().await {
+            eprint!(
+                "\r[{}/{}] {} / {}  {}{}\x1b[K",
+                progress.completed_files,
+                progress.total_files,
+                format_file_size(progress.bytes_copied),
+                format_file_size(progress.total_bytes),
+                progress.rate_text().map(|rate| format!("({})  ", rate)).unwrap_or_default(),
+                progress.current_file.display(),
+            );
+            reported = true;
+        }
+        if reported {
+            eprintln!();
+        }
+    });
+    (tx, reporter)
+}
+
+async fn copy(operation: CopyOperation) -> Result<()> {
+    let (tx, reporter) = report_progress();
+
+    let result = operation.with_progress_callback(tx).execute().await;
+
+    // The operation owned the sender, so the reporter finishes once it is dropped
+    let _ = reporter.await;
+
+    if let Some(errors) = result.as_ref().err().and_then(|e| e.downcast_ref::<CopyErrors>()) {
+        for (path, error) in &errors.failures {
+            eprintln!("{}: {}", path.display(), error);
+        }
+        for mismatch in &errors.mismatches {
+            eprintln!("{}", mismatch);
+        }
+    }
+    result
+}
+
+async fn move_items(sources: Vec<PathBuf>, destination: &Path, verify: Option<HashAlgorithm>) -> Result<()> {
+    let into_directory = destination.is_dir();
+    if sources.len() > 1 && !into_directory {
+        return Err(anyhow::anyhow!("Target is not a directory: {}", destination.display()));
+    }
+
+    let total = sources.len();
+    for (index, source) in sources.iter().enumerate() {
+        let target = if into_directory {
+            let name = source.file_name()
+                .ok_or_else(|| anyhow::anyhow!("Invalid file name: {}", source.display()))?;
+            destination.join(name)
+        } else {
+            destination.to_path_buf()
+        };
+
+        // Moves across filesystems fall back to copying, which reports its progress
+        let (tx, reporter) = report_progress();
+        let mut operation = MoveOperation::new(source.clone(), target.clone())
+            .with_progress_callback(tx);
+        if let Some(algorithm) = verify {
+            operation = operation.with_verify(algorithm);
+        }
+        let result = operation.execute().await;
+        // The operation holds the sender; the reporter finishes once it is dropped
+        drop(operation);
+        let _ = reporter.await;
+        result?;
+        eprintln!("[{}/{}] {} -> {}", index + 1, total, source.display(), target.display());
+    }
+
+    Ok(())
+}
+
+async fn find(root: &Path, query: SearchQuery, json: bool) -> Result<()> {
+    let mut searcher = FileSearcher::new(query);
+    let results = searcher.search(root).await?;
+
+    if json {
+        let items: Vec<FileItem> = results.iter()
+            .filter_map(|result| FileItem::from_path(&result.path).ok())
+            .collect();
+        return print_json(&items);
+    }
+
+    let mut out = std::io::stdout().lock();
+    for result in &results {
+        let line = match result.match_context {
+            Some(ref context) => format!("{}: {}", result.path.display(), context.replace('\n', " ")),
+            None => result.path.display().to_string(),
+        };
+        if !write_line(&mut out, &line)? {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+fn print_json(items: &[FileItem]) -> Result<()> {
+    let json = serde_json::to_string_pretty(items)?;
+    write_line(&mut std::io::stdout().lock(), &json)?;
+    Ok(())
+}
+
+/// Writes one line to stdout, returning `false` once the reader has gone away
+/// (e.g. when piped into `head`).
+fn write_line(out: &mut impl Write, line: &str) -> Result<bool> {
+    match writeln!(out, "{}", line) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,30 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use chexexplorer::core::settings::Settings;
+use chexexplorer::ui::app::ChexExplorerApp;
+use eframe::egui;
+
+fn main() -> Result<(), eframe::Error> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let settings = Settings::load();
+
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("ChexExplorer")
+            .with_app_id("chex-explorer")
+            .with_inner_size([settings.window_width, settings.window_height])
+            .with_min_inner_size([800.0, 600.0])
+            .with_icon(
+                eframe::icon_data::from_png_bytes(include_bytes!("../../assets/icon.png"))
+                    .unwrap_or_default(),
+            ),
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        "ChexExplorer",
+        options,
+        Box::new(|cc| Ok(Box::new(ChexExplorerApp::new(cc)))),
+    )
+}
@@ -0,0 +1,165 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use url::Url;
+
+use crate::operations::jobs::JobKind;
+
+/// Files copied or cut, waiting to be pasted.
+#[derive(Debug, Clone, Default)]
+pub struct Clipboard {
+    paths: Vec<PathBuf>,
+    /// The same paths, to look items up while drawing them.
+    index: HashSet<PathBuf>,
+    /// Pasting moves the files instead of copying them.
+    cut: bool,
+}
+
+impl Clipboard {
+    /// Holds `paths` to be copied, or moved when `cut`, replacing what was there.
+    pub fn set(&mut self, paths: Vec<PathBuf>, cut: bool) {
+        self.index = paths.iter().cloned().collect();
+        self.paths = paths;
+        self.cut = cut;
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    pub fn is_cut(&self) -> bool {
+        self.cut
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.index.contains(path)
+    }
+
+    /// Whether `path` was cut and will move away on the next paste.
+    pub fn is_cut_path(&self, path: &Path) -> bool {
+        self.cut && self.contains(path)
+    }
+
+    pub fn clear(&mut self) {
+        self.paths.clear();
+        self.index.clear();
+        self.cut = false;
+    }
+
+    /// The job that pastes into `destination`. Cut files can only be moved
+    /// once, so pasting them empties the clipboard; files already in
+    /// `destination` stay where they are.
+    pub fn paste(&mut self, destination: &Path) -> Option<JobKind> {
+        if self.paths.is_empty() {
+            return None;
+        }
+        let destination = destination.to_path_buf();
+        if !self.cut {
+            return Some(JobKind::Copy { sources: self.paths.clone(), destination });
+        }
+
+        let sources: Vec<PathBuf> = std::mem::take(&mut self.paths).into_iter()
+            .filter(|path| path.parent() != Some(destination.as_path()))
+            .collect();
+        self.clear();
+        (!sources.is_empty()).then_some(JobKind::Move { sources, destination })
+    }
+}
+
+/// A `text/uri-list` naming no files, put on the system clipboard once cut
+/// files have been pasted so they aren't offered again.
+pub const EMPTY_URI_LIST: &str = "# Nothing to paste\r\n";
+
+/// `paths` as `text/uri-list`: one `file://` URI per line, ending in CRLF.
+pub fn to_uri_list(paths: &[PathBuf]) -> String {
+    paths.iter()
+        .filter_map(|path| Url::from_file_path(path).ok())
+        .map(|url| format!("{}\r\n", url))
+        .collect()
+}
+
+/// The local files named by `text/uri-list` text. Empty unless every line
+/// is a `file://` URI, so pasted text such as a bare `/` copies nothing.
+pub fn parse_uri_list(text: &str) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let path = Url::parse(line)
+            .ok()
+            .filter(|url| url.scheme() == "file")
+            .and_then(|url| url.to_file_path().ok());
+        match path {
+            Some(path) => paths.push(path),
+            None => return Vec::new(),
+        }
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn pasting_copies_and_keeps_the_files() {
+        let mut clipboard = Clipboard::default();
+        clipboard.set(paths(&["/src/a", "/src/b"]), false);
+
+        let job = clipboard.paste(Path::new("/dst"));
+        assert!(matches!(job, Some(JobKind::Copy { sources, destination })
+            if sources == paths(&["/src/a", "/src/b"]) && destination == Path::new("/dst")));
+        assert!(clipboard.contains(Path::new("/src/a")));
+        assert!(clipboard.paste(Path::new("/other")).is_some());
+    }
+
+    #[test]
+    fn pasting_cut_files_moves_them_once() {
+        let mut clipboard = Clipboard::default();
+        clipboard.set(paths(&["/src/a", "/dst/b"]), true);
+        assert!(clipboard.is_cut_path(Path::new("/src/a")));
+
+        // Files already in the destination stay put
+        let job = clipboard.paste(Path::new("/dst"));
+        assert!(matches!(job, Some(JobKind::Move { sources, .. }) if sources == paths(&["/src/a"])));
+        assert!(clipboard.is_empty());
+        assert!(clipboard.paste(Path::new("/dst")).is_none());
+    }
+
+    #[test]
+    fn pasting_cut_files_into_their_own_folder_does_nothing() {
+        let mut clipboard = Clipboard::default();
+        clipboard.set(paths(&["/dst/a"]), true);
+        assert!(clipboard.paste(Path::new("/dst")).is_none());
+        assert!(clipboard.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn uri_lists_round_trip() {
+        let files = paths(&["/home/user/a b.txt", "/tmp/ü"]);
+        let text = to_uri_list(&files);
+        assert_eq!(text, "file:///home/user/a%20b.txt\r\nfile:///tmp/%C3%BC\r\n");
+        assert_eq!(parse_uri_list(&format!("# comment\n{}", text)), files);
+    }
+
+    #[test]
+    fn only_file_uris_are_pasted() {
+        assert!(parse_uri_list("/").is_empty());
+        assert!(parse_uri_list("/home/user/a.txt").is_empty());
+        assert!(parse_uri_list("https://example.com/a.txt").is_empty());
+        assert!(parse_uri_list("file:///tmp/a\n/tmp/b").is_empty());
+        assert!(parse_uri_list("").is_empty());
+        assert!(parse_uri_list(EMPTY_URI_LIST).is_empty());
+        assert!(parse_uri_list("# sftp://host/a.txt\r\n").is_empty());
+    }
+}
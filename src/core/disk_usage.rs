@@ -0,0 +1,422 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use anyhow::Result;
+use tokio::sync::mpsc;
+
+use crate::operations::control::{Cancelled, JobControl};
+
+/// Entries read between progress reports.
+const REPORT_EVERY: u64 = 2000;
+
+/// Identifies a file shared by several hard links: device and inode.
+type FileId = (u64, u64);
+
+/// A file or folder with the space it takes up.
+#[derive(Debug, Clone)]
+pub struct UsageNode {
+    pub name: String,
+    pub path: PathBuf,
+    pub is_dir: bool,
+    /// Bytes used on disk, including everything below a folder.
+    pub size: u64,
+    /// Files below a folder, or 1 for a file.
+    pub files: u64,
+    /// Entries of a folder, largest first.
+    pub children: Vec<UsageNode>,
+    /// Entries of a folder that couldn't be read.
+    pub errors: usize,
+    /// When a folder last changed, as of reading it.
+    modified: Option<SystemTime>,
+    /// Hard-linked files counted in this folder (not below it).
+    links: Vec<FileId>,
+    /// Hard-linked files left out of this folder because they were counted elsewhere.
+    skipped: Vec<FileId>,
+}
+
+impl UsageNode {
+    /// Finds the node for `path` at or below this one.
+    pub fn find(&self, path: &Path) -> Option<&UsageNode> {
+        if self.path == path {
+            return Some(self);
+        }
+        self.children.iter()
+            .filter(|child| child.is_dir && path.starts_with(&child.path))
+            .find_map(|child| child.find(path))
+    }
+
+    fn sum_children(&mut self) {
+        self.size = self.children.iter().map(|child| child.size).sum();
+        self.files = self.children.iter().map(|child| child.files).sum();
+        self.children.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    }
+}
+
+/// The result of scanning a folder: its tree of sizes, plus which
+/// hard-linked files were already counted so refreshes keep counting them once.
+#[derive(Debug, Clone)]
+pub struct DiskUsage {
+    pub root: UsageNode,
+    counted: HashSet<FileId>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UsageProgress {
+    pub entries: u64,
+    pub bytes: u64,
+    pub current_dir: PathBuf,
+}
+
+/// Measures how much space folders take up on the local disk.
+///
+/// Symbolic links are not followed and each hard-linked file is counted once,
+/// where it is first found.
+#[derive(Default)]
+pub struct UsageScanner {
+    progress_tx: Option<mpsc::UnboundedSender<UsageProgress>>,
+    control: Option<JobControl>,
+    progress: UsageProgress,
+}
+
+impl UsageScanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_progress_callback(mut self, tx: mpsc::UnboundedSender<UsageProgress>) -> Self {
+        self.progress_tx = Some(tx);
+        self
+    }
+
+    /// Lets `control` cancel the scan.
+    pub fn with_control(mut self, control: JobControl) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    /// Reads everything below `root`. Reads files on the calling thread.
+    pub fn scan(&mut self, root: &Path) -> Result<DiskUsage> {
+        let mut counted = HashSet::new();
+        let root = self.scan_dir(root, &mut counted)?;
+        Ok(DiskUsage { root, counted })
+    }
+
+    /// Re-reads the folders that changed since they were read, keeping what
+    /// is known about unchanged subfolders. Files that only grew or shrank in
+    /// place don't change their folder, so they need [`Self::rescan`].
+    pub fn refresh(&mut self, usage: &mut DiskUsage) -> Result<()> {
+        let before = usage.counted.clone();
+        self.refresh_dir(&mut usage.root, &mut usage.counted)?;
+        self.recount_links(usage, &before)
+    }
+
+    /// Reads the folder at `path` within `usage` again from scratch.
+    pub fn rescan(&mut self, usage: &mut DiskUsage, path: &Path) -> Result<()> {
+        let before = usage.counted.clone();
+        self.rescan_in(&mut usage.root, path, &mut usage.counted)?;
+        self.recount_links(usage, &before)
+    }
+
+    fn scan_dir(&mut self, path: &Path, counted: &mut HashSet<FileId>) -> Result<UsageNode> {
+        let metadata = fs::symlink_metadata(path)?;
+        let mut node = Self::dir_node(path, &metadata);
+        self.read_entries(&mut node, counted, &mut HashMap::new())?;
+        Ok(node)
+    }
+
+    /// Lists `node`'s entries, reusing folders from `known` and scanning new ones.
+    fn read_entries(
+        &mut self,
+        node: &mut UsageNode,
+        counted: &mut HashSet<FileId>,
+        known: &mut HashMap<PathBuf, UsageNode>,
+    ) -> Result<()> {
+        self.progress.current_dir = node.path.clone();
+        node.children.clear();
+        node.links.clear();
+        node.skipped.clear();
+        node.errors = 0;
+
+        let entries = match fs::read_dir(&node.path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::debug!("Can't read {}: {}", node.path.display(), e);
+                node.errors += 1;
+                node.sum_children();
+                return Ok(());
+            }
+        };
+
+        for entry in entries {
+            self.check()?;
+            let Ok(entry) = entry else {
+                node.errors += 1;
+                continue;
+            };
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata() else {
+                node.errors += 1;
+                continue;
+            };
+
+            if metadata.is_dir() {
+                let child = match known.remove(&path) {
+                    Some(mut child) => {
+                        self.refresh_dir(&mut child, counted)?;
+                        child
+                    }
+                    None => self.scan_dir(&path, counted)?,
+                };
+                node.children.push(child);
+                continue;
+            }
+
+            if let Some(id) = link_id(&metadata) {
+                if !counted.insert(id) {
+                    node.skipped.push(id);
+                    continue;
+                }
+                node.links.push(id);
+            }
+            let size = disk_size(&metadata);
+            node.children.push(UsageNode {
+                name: entry.file_name().to_string_lossy().to_string(),
+                path,
+                is_dir: false,
+                size,
+                files: 1,
+                children: Vec::new(),
+                errors: 0,
+                modified: None,
+                links: Vec::new(),
+                skipped: Vec::new(),
+            });
+            self.advance(size);
+        }
+
+        node.sum_children();
+        Ok(())
+    }
+
+    fn refresh_dir(&mut self, node: &mut UsageNode, counted: &mut HashSet<FileId>) -> Result<()> {
+        self.check()?;
+        let metadata = match fs::symlink_metadata(&node.path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                log::debug!("Can't read {}: {}", node.path.display(), e);
+                return Ok(());
+            }
+        };
+
+        if metadata.modified().ok() == node.modified {
+            for child in node.children.iter_mut().filter(|child| child.is_dir) {
+                self.refresh_dir(child, counted)?;
+            }
+            node.sum_children();
+            return Ok(());
+        }
+
+        // Entries were added, removed or renamed: list the folder again
+        node.modified = metadata.modified().ok();
+        self.relist(node, counted)
+    }
+
+    /// Lists `node` again, keeping what is known about its subfolders.
+    fn relist(&mut self, node: &mut UsageNode, counted: &mut HashSet<FileId>) -> Result<()> {
+        for id in &node.links {
+            counted.remove(id);
+        }
+        let mut known: HashMap<PathBuf, UsageNode> = std::mem::take(&mut node.children)
+            .into_iter()
+            .filter(|child| child.is_dir)
+            .map(|child| (child.path.clone(), child))
+            .collect();
+        self.read_entries(node, counted, &mut known)?;
+
+        // Whatever is left was removed
+        for gone in known.values() {
+            forget_links(gone, counted);
+        }
+        Ok(())
+    }
+
+    /// Hard-linked files counted in a folder that changed may now be gone
+    /// from it while their other links sit in unchanged folders, which skipped
+    /// them. Lists those folders again so each such file is counted somewhere.
+    fn recount_links(&mut self, usage: &mut DiskUsage, before: &HashSet<FileId>) -> Result<()> {
+        let released: HashSet<FileId> = before.difference(&usage.counted).copied().collect();
+        if released.is_empty() {
+            return Ok(());
+        }
+        self.recount_in(&mut usage.root, &released, &mut usage.counted)
+    }
+
+    fn recount_in(&mut self, node: &mut UsageNode, released: &HashSet<FileId>, counted: &mut HashSet<FileId>) -> Result<()> {
+        if node.skipped.iter().any(|id| released.contains(id) && !counted.contains(id)) {
+            self.relist(node, counted)?;
+        }
+        for child in node.children.iter_mut().filter(|child| child.is_dir) {
+            self.recount_in(child, released, counted)?;
+        }
+        node.sum_children();
+        Ok(())
+    }
+
+    fn rescan_in(&mut self, node: &mut UsageNode, path: &Path, counted: &mut HashSet<FileId>) -> Result<()> {
+        if node.path == path {
+            forget_links(node, counted);
+            *node = self.scan_dir(path, counted)?;
+            return Ok(());
+        }
+
+        let Some(child) = node.children.iter_mut().find(|child| child.is_dir && path.starts_with(&child.path)) else {
+            return Err(anyhow::anyhow!("{} is not part of this scan", path.display()));
+        };
+        self.rescan_in(child, path, counted)?;
+        node.sum_children();
+        Ok(())
+    }
+
+    fn dir_node(path: &Path, metadata: &fs::Metadata) -> UsageNode {
+        UsageNode {
+            name: path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.display().to_string()),
+            path: path.to_path_buf(),
+            is_dir: true,
+            size: 0,
+            files: 0,
+            children: Vec::new(),
+            errors: 0,
+            modified: metadata.modified().ok(),
+            links: Vec::new(),
+            skipped: Vec::new(),
+        }
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.control.as_ref().is_some_and(|control| control.is_cancelled()) {
+            return Err(Cancelled.into());
+        }
+        Ok(())
+    }
+
+    fn advance(&mut self, bytes: u64) {
+        self.progress.entries += 1;
+        self.progress.bytes += bytes;
+        if self.progress.entries.is_multiple_of(REPORT_EVERY) {
+            if let Some(ref tx) = self.progress_tx {
+                let _ = tx.send(self.progress.clone());
+            }
+        }
+    }
+}
+
+/// Releases the hard links counted at or below `node`.
+fn forget_links(node: &UsageNode, counted: &mut HashSet<FileId>) {
+    for id in &node.links {
+        counted.remove(id);
+    }
+    for child in node.children.iter().filter(|child| child.is_dir) {
+        forget_links(child, counted);
+    }
+}
+
+/// Space taken on disk, rather than the length, so sparse files count what they use.
+#[cfg(unix)]
+fn disk_size(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn disk_size(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+/// The identity of a file with more than one hard link.
+#[cfg(unix)]
+fn link_id(metadata: &fs::Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn link_id(_metadata: &fs::Metadata) -> Option<FileId> {
+    None
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn size_of(path: &Path) -> u64 {
+        disk_size(&fs::metadata(path).unwrap())
+    }
+
+    /// Which of `folders` below `root` the file `name` was counted in.
+    fn counted_in<'a>(usage: &DiskUsage, root: &Path, folders: &[&'a str], name: &str) -> Vec<&'a str> {
+        folders.iter()
+            .copied()
+            .filter(|folder| {
+                let node = usage.root.find(&root.join(folder)).unwrap();
+                node.children.iter().any(|child| child.name == name)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn counts_hard_linked_files_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("one")).unwrap();
+        fs::create_dir_all(root.join("two")).unwrap();
+        fs::write(root.join("one/data"), vec![1u8; 100_000]).unwrap();
+        fs::hard_link(root.join("one/data"), root.join("two/data")).unwrap();
+        fs::write(root.join("two/other"), vec![2u8; 10_000]).unwrap();
+
+        let usage = UsageScanner::new().scan(root).unwrap();
+        assert_eq!(usage.root.size, size_of(&root.join("one/data")) + size_of(&root.join("two/other")));
+        assert_eq!(usage.root.files, 2);
+        assert_eq!(counted_in(&usage, root, &["one", "two"], "data").len(), 1);
+    }
+
+    #[test]
+    fn refresh_counts_the_remaining_link() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("one")).unwrap();
+        fs::create_dir_all(root.join("two")).unwrap();
+        fs::write(root.join("one/data"), vec![1u8; 100_000]).unwrap();
+        fs::hard_link(root.join("one/data"), root.join("two/data")).unwrap();
+        let size = size_of(&root.join("one/data"));
+
+        let mut scanner = UsageScanner::new();
+        let mut usage = scanner.scan(root).unwrap();
+        let counted = counted_in(&usage, root, &["one", "two"], "data")[0];
+        let other = if counted == "one" { "two" } else { "one" };
+
+        // The link that was counted goes; the one in the unchanged folder takes over
+        fs::remove_file(root.join(counted).join("data")).unwrap();
+        scanner.refresh(&mut usage).unwrap();
+        assert_eq!(usage.root.size, size);
+        assert_eq!(usage.root.files, 1);
+        assert_eq!(counted_in(&usage, root, &["one", "two"], "data"), [other]);
+    }
+
+    #[test]
+    fn rescan_releases_links_below_the_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("one")).unwrap();
+        fs::write(root.join("one/data"), vec![1u8; 100_000]).unwrap();
+        fs::hard_link(root.join("one/data"), root.join("one/again")).unwrap();
+
+        let mut scanner = UsageScanner::new();
+        let mut usage = scanner.scan(root).unwrap();
+        scanner.rescan(&mut usage, &root.join("one")).unwrap();
+        assert_eq!(usage.root.size, size_of(&root.join("one/data")));
+        assert_eq!(usage.root.files, 1);
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use tokio::sync::mpsc;
+use walkdir::WalkDir;
+
+use crate::operations::checksum::{self, Checksum, HashAlgorithm};
+use crate::operations::control::{Cancelled, JobControl};
+use crate::utils::file_utils::is_hidden_file;
+
+/// Bytes from the start of each file compared before hashing whole files.
+const PARTIAL_SIZE: u64 = 64 * 1024;
+
+/// Least bytes hashed between progress reports.
+const REPORT_BYTES: u64 = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateStage {
+    /// Listing files and grouping them by size.
+    Scanning,
+    /// Comparing the first bytes of files of the same size.
+    ComparingStarts,
+    /// Hashing whole files that still look alike, however small.
+    ComparingContents,
+}
+
+impl DuplicateStage {
+    pub fn label(self) -> &'static str {
+        match self {
+            DuplicateStage::Scanning => "Scanning",
+            DuplicateStage::ComparingStarts => "Comparing file starts",
+            DuplicateStage::ComparingContents => "Comparing contents",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DuplicateProgress {
+    pub stage: DuplicateStage,
+    /// Files found while scanning, otherwise files compared so far.
+    pub files_done: usize,
+    /// Files to compare in this stage; zero while scanning.
+    pub files_total: usize,
+    pub bytes_hashed: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DuplicateFile {
+    pub path: PathBuf,
+    pub modified: chrono::DateTime<chrono::Utc>,
+}
+
+/// Files with identical contents.
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    /// Size of each file.
+    pub size: u64,
+    pub checksum: Checksum,
+    /// Sorted by path.
+    pub files: Vec<DuplicateFile>,
+}
+
+/// Which file of a group to keep when removing the others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeepRule {
+    Newest,
+    Oldest,
+}
+
+impl DuplicateGroup {
+    /// Space freed by keeping only one of the files.
+    pub fn reclaimable(&self) -> u64 {
+        self.size * self.files.len().saturating_sub(1) as u64
+    }
+
+    /// The file `rule` keeps.
+    pub fn keeper(&self, rule: KeepRule) -> Option<&DuplicateFile> {
+        match rule {
+            KeepRule::Newest => self.files.iter().max_by_key(|file| file.modified),
+            KeepRule::Oldest => self.files.iter().min_by_key(|file| file.modified),
+        }
+    }
+
+    /// Every file but the one `rule` keeps.
+    pub fn extras(&self, rule: KeepRule) -> Vec<PathBuf> {
+        let keeper = self.keeper(rule).map(|file| file.path.clone());
+        self.files.iter()
+            .filter(|file| Some(&file.path) != keeper.as_ref())
+            .map(|file| file.path.clone())
+            .collect()
+    }
+}
+
+/// Finds files with the same contents below a folder on the local disk.
+///
+/// Files are grouped by size first, then by a quick hash of their first
+/// bytes, and only files that still match are hashed in full with BLAKE3, so
+/// most files are never read completely. Hard links to the same file count
+/// as one file.
+pub struct DuplicateFinder {
+    min_size: u64,
+    include_hidden: bool,
+    progress_tx: Option<mpsc::UnboundedSender<DuplicateProgress>>,
+    control: Option<JobControl>,
+}
+
+impl Default for DuplicateFinder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DuplicateFinder {
+    pub fn new() -> Self {
+        Self {
+            min_size: 1,
+            include_hidden: true,
+            progress_tx: None,
+            control: None,
+        }
+    }
+
+    /// Ignores files smaller than `bytes`. Empty files are ignored unless this is zero.
+    pub fn with_min_size(mut self, bytes: u64) -> Self {
+        self.min_size = bytes;
+        self
+    }
+
+    /// Whether hidden files and the contents of hidden folders are compared.
+    pub fn with_hidden(mut self, include_hidden: bool) -> Self {
+        self.include_hidden = include_hidden;
+        self
+    }
+
+    pub fn with_progress_callback(mut self, tx: mpsc::UnboundedSender<DuplicateProgress>) -> Self {
+        self.progress_tx = Some(tx);
+        self
+    }
+
+    /// Lets `control` cancel the search.
+    pub fn with_control(mut self, control: JobControl) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    /// Returns the groups of duplicates below `root`, largest savings first.
+    /// Reads files on the calling thread.
+    pub fn find(&self, root: &Path) -> Result<Vec<DuplicateGroup>> {
+        let by_size = self.scan(root)?;
+
+        // Compare the starts of files that share a size
+        let candidates: Vec<_> = by_size.into_iter().filter(|(_, files)| files.len() > 1).collect();
+        let mut by_start: HashMap<(u64, Checksum), Vec<DuplicateFile>> = HashMap::new();
+        let mut tracker = self.tracker(DuplicateStage::ComparingStarts, candidates.iter().map(|(_, f)| f.len()).sum());
+        for (size, files) in candidates {
+            for file in files {
+                match self.hash_start(&file.path, &mut tracker) {
+                    Ok(start) => by_start.entry((size, start)).or_default().push(file),
+                    Err(e) if e.is::<Cancelled>() => return Err(e),
+                    Err(e) => log::warn!("Skipping {}: {}", file.path.display(), e),
+                }
+                tracker.file_done();
+            }
+        }
+
+        // Hash the whole of files that still match; the quick hash of the start
+        // isn't enough to call even small files the same
+        let candidates: Vec<_> = by_start.into_iter().filter(|(_, files)| files.len() > 1).collect();
+        let mut groups = Vec::new();
+        let mut tracker = self.tracker(DuplicateStage::ComparingContents, candidates.iter().map(|(_, f)| f.len()).sum());
+        for ((size, _), files) in candidates {
+            let mut by_content: HashMap<Checksum, Vec<DuplicateFile>> = HashMap::new();
+            for file in files {
+                let hashed = checksum::hash_file(
+                    &crate::core::vfs::LocalFs,
+                    &file.path,
+                    HashAlgorithm::Blake3,
+                    &mut |bytes| tracker.hashed(bytes),
+                );
+                match hashed {
+                    Ok(checksum) => by_content.entry(checksum).or_default().push(file),
+                    Err(e) if e.is::<Cancelled>() => return Err(e),
+                    Err(e) => log::warn!("Skipping {}: {}", file.path.display(), e),
+                }
+                tracker.file_done();
+            }
+            groups.extend(
+                by_content.into_iter()
+                    .filter(|(_, files)| files.len() > 1)
+                    .map(|(checksum, files)| DuplicateGroup { size, checksum, files }),
+            );
+        }
+
+        for group in &mut groups {
+            group.files.sort_by(|a, b| a.path.cmp(&b.path));
+        }
+        groups.sort_by(|a, b| b.reclaimable().cmp(&a.reclaimable()).then_with(|| a.files[0].path.cmp(&b.files[0].path)));
+        Ok(groups)
+    }
+
+    /// Lists the regular files below `root` by size, one path per hard-linked file.
+    fn scan(&self, root: &Path) -> Result<HashMap<u64, Vec<DuplicateFile>>> {
+        let mut by_size: HashMap<u64, Vec<DuplicateFile>> = HashMap::new();
+        let mut seen = std::collections::HashSet::new();
+        let mut tracker = self.tracker(DuplicateStage::Scanning, 0);
+
+        let walker = WalkDir::new(root)
+            .follow_links(false)
+            .into_iter()
+            .filter_entry(|entry| self.include_hidden || entry.depth() == 0 || !is_hidden_file(entry.path()));
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    log::warn!("Skipping unreadable entry: {}", e);
+                    continue;
+                }
+            };
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.len() < self.min_size {
+                continue;
+            }
+            if !seen.insert(file_id(&metadata, entry.path())) {
+                continue;
+            }
+
+            by_size.entry(metadata.len()).or_default().push(DuplicateFile {
+                path: entry.into_path(),
+                modified: metadata.modified().map(chrono::DateTime::from).unwrap_or_default(),
+            });
+            tracker.file_done();
+            tracker.check()?;
+        }
+
+        Ok(by_size)
+    }
+
+    fn hash_start(&self, path: &Path, tracker: &mut Tracker<'_>) -> Result<Checksum> {
+        let file = fs::File::open(path)?;
+        checksum::hash_reader(&mut file.take(PARTIAL_SIZE), HashAlgorithm::Xxh3, &mut |bytes| tracker.hashed(bytes))
+    }
+
+    fn tracker(&self, stage: DuplicateStage, files_total: usize) -> Tracker<'_> {
+        let mut tracker = Tracker {
+            finder: self,
+            progress: DuplicateProgress { stage, files_done: 0, files_total, bytes_hashed: 0 },
+            unreported: 0,
+        };
+        tracker.report();
+        tracker
+    }
+}
+
+/// Progress and cancellation for one stage of a search.
+struct Tracker<'a> {
+    finder: &'a DuplicateFinder,
+    progress: DuplicateProgress,
+    /// Bytes hashed since the last report.
+    unreported: u64,
+}
+
+impl Tracker<'_> {
+    fn file_done(&mut self) {
+        self.progress.files_done += 1;
+        // Report every so many files while scanning, after each file otherwise
+        if self.progress.stage != DuplicateStage::Scanning || self.progress.files_done.is_multiple_of(1000) {
+            self.report();
+        }
+    }
+
+    fn hashed(&mut self, bytes: u64) -> Result<()> {
+        self.progress.bytes_hashed += bytes;
+        self.unreported += bytes;
+        if self.unreported >= REPORT_BYTES {
+            self.report();
+        }
+        self.check()
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.finder.control.as_ref().is_some_and(|control| control.is_cancelled()) {
+            return Err(Cancelled.into());
+        }
+        Ok(())
+    }
+
+    fn report(&mut self) {
+        self.unreported = 0;
+        if let Some(ref tx) = self.finder.progress_tx {
+            let _ = tx.send(self.progress.clone());
+        }
+    }
+}
+
+/// Identifies the file behind `path`, shared by all of its hard links.
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata, _path: &Path) -> (u64, u64, PathBuf) {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.dev(), metadata.ino(), PathBuf::new())
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata, path: &Path) -> (u64, u64, PathBuf) {
+    (0, 0, path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(finder: DuplicateFinder, root: &Path) -> Vec<Vec<PathBuf>> {
+        finder.find(root).unwrap()
+            .into_iter()
+            .map(|group| group.files.into_iter().map(|file| file.path.strip_prefix(root).unwrap().to_path_buf()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn groups_files_with_the_same_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("a.txt"), "same").unwrap();
+        fs::write(root.join("sub/b.txt"), "same").unwrap();
+        fs::write(root.join("c.txt"), "diff").unwrap();
+        fs::write(root.join("empty1"), "").unwrap();
+        fs::write(root.join("empty2"), "").unwrap();
+
+        // Large files that only differ past the compared start
+        let mut large = vec![1u8; PARTIAL_SIZE as usize * 2];
+        fs::write(root.join("large1"), &large).unwrap();
+        fs::write(root.join("large2"), &large).unwrap();
+        *large.last_mut().unwrap() = 2;
+        fs::write(root.join("large3"), &large).unwrap();
+
+        let groups = find(DuplicateFinder::new(), root);
+        assert_eq!(groups, [
+            vec![PathBuf::from("large1"), PathBuf::from("large2")],
+            vec![PathBuf::from("a.txt"), PathBuf::from("sub/b.txt")],
+        ]);
+    }
+
+    #[test]
+    fn small_files_are_hashed_in_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let mut content = vec![0u8; PARTIAL_SIZE as usize];
+        fs::write(root.join("a"), &content).unwrap();
+        fs::write(root.join("b"), &content).unwrap();
+
+        let group = DuplicateFinder::new().find(root).unwrap().remove(0);
+        assert_eq!(group.checksum, checksum::hash_file(
+            &crate::core::vfs::LocalFs,
+            &root.join("a"),
+            HashAlgorithm::Blake3,
+            &mut |_| Ok(()),
+        ).unwrap());
+
+        content[0] = 1;
+        fs::write(root.join("b"), &content).unwrap();
+        assert!(DuplicateFinder::new().find(root).unwrap().is_empty());
+    }
+
+    #[test]
+    fn honours_min_size_and_hidden_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".hidden")).unwrap();
+        fs::write(root.join("a"), "12345").unwrap();
+        fs::write(root.join(".hidden/b"), "12345").unwrap();
+        fs::write(root.join("c"), "1").unwrap();
+        fs::write(root.join("d"), "1").unwrap();
+
+        assert_eq!(find(DuplicateFinder::new().with_min_size(2), root), [
+            vec![PathBuf::from(".hidden/b"), PathBuf::from("a")],
+        ]);
+        assert_eq!(find(DuplicateFinder::new().with_hidden(false), root), [
+            vec![PathBuf::from("c"), PathBuf::from("d")],
+        ]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hard_links_count_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("a"), "same").unwrap();
+        fs::hard_link(root.join("a"), root.join("b")).unwrap();
+        assert!(DuplicateFinder::new().find(root).unwrap().is_empty());
+
+        fs::write(root.join("c"), "same").unwrap();
+        let groups = find(DuplicateFinder::new(), root);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].len(), 2);
+    }
+}
@@ -1,8 +1,9 @@
 use std::path::{Path, PathBuf};
-use std::fs::Metadata;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::core::vfs::{LocalFs, Vfs};
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FileType {
     Directory,
@@ -27,25 +28,21 @@ pub struct FileItem {
     pub icon_path: Option<PathBuf>,
     pub thumbnail_path: Option<PathBuf>,
     pub is_selected: bool,
+    /// Cut to the clipboard, to be moved on the next paste.
+    pub is_cut: bool,
 }
 
 impl FileItem {
     pub fn from_path(path: &Path) -> Result<Self, std::io::Error> {
-        let metadata = std::fs::metadata(path)?;
+        Self::from_vfs(&LocalFs, path)
+    }
+
+    pub fn from_vfs<V: Vfs + ?Sized>(vfs: &V, path: &Path) -> Result<Self, std::io::Error> {
+        let metadata = vfs.metadata(path)?;
         let name = path.file_name()
             .unwrap_or_default()
             .to_string_lossy()
             .to_string();
-        
-        let file_type = if metadata.is_dir() {
-            FileType::Directory
-        } else if metadata.is_file() {
-            FileType::RegularFile
-        } else if metadata.file_type().is_symlink() {
-            FileType::SymbolicLink
-        } else {
-            FileType::Other
-        };
 
         let extension = path.extension()
             .map(|ext| ext.to_string_lossy().to_string().to_lowercase());
@@ -53,24 +50,22 @@ impl FileItem {
         let mime_type = extension.as_ref()
             .map(|ext| mime_guess::from_ext(ext).first_or_octet_stream().to_string());
 
-        let is_hidden = Self::is_hidden_file(path);
-        let is_readonly = metadata.permissions().readonly();
-
         Ok(FileItem {
             name,
             path: path.to_path_buf(),
-            file_type,
-            size: metadata.len(),
-            modified: DateTime::from(metadata.modified()?),
-            created: metadata.created().ok().map(DateTime::from),
-            accessed: metadata.accessed().ok().map(DateTime::from),
-            is_hidden,
-            is_readonly,
+            file_type: metadata.file_type,
+            size: metadata.len,
+            modified: DateTime::from(metadata.modified.unwrap_or(std::time::UNIX_EPOCH)),
+            created: metadata.created.map(DateTime::from),
+            accessed: metadata.accessed.map(DateTime::from),
+            is_hidden: metadata.is_hidden,
+            is_readonly: metadata.is_readonly,
             extension,
             mime_type,
             icon_path: None,
             thumbnail_path: None,
             is_selected: false,
+            is_cut: false,
         })
     }
 
@@ -111,32 +106,7 @@ impl FileItem {
     }
 
     pub fn get_size_formatted(&self) -> String {
-        let metadata = std::fs::metadata(&self.path);
-        match metadata {
-            Ok(meta) => {
-                let size = meta.len();
-                crate::utils::format::format_file_size(size)
-            }
-            Err(_) => "Unknown".to_string()
-        }
-    }
-
-    #[cfg(windows)]
-    fn is_hidden_file(path: &Path) -> bool {
-        use std::os::windows::fs::MetadataExt;
-        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
-        
-        std::fs::metadata(path)
-            .map(|metadata| metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
-            .unwrap_or(false)
-    }
-
-    #[cfg(not(windows))]
-    fn is_hidden_file(path: &Path) -> bool {
-        path.file_name()
-            .and_then(|name| name.to_str())
-            .map(|name| name.starts_with('.'))
-            .unwrap_or(false)
+        crate::utils::format::format_file_size(self.size)
     }
 }
 
@@ -157,26 +127,62 @@ pub enum SortOrder {
 
 impl FileItem {
     pub fn sort_items(items: &mut [FileItem], sort_by: SortBy, sort_order: SortOrder) {
-        items.sort_by(|a, b| {
-            let comparison = match sort_by {
-                SortBy::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
-                SortBy::Size => a.size.cmp(&b.size),
-                SortBy::Modified => a.modified.cmp(&b.modified),
-                SortBy::Type => a.file_type.cmp(&b.file_type),
-                SortBy::Created => a.created.cmp(&b.created),
-            };
-
-            match sort_order {
-                SortOrder::Ascending => comparison,
-                SortOrder::Descending => comparison.reverse(),
+        if sort_by != SortBy::Name {
+            items.sort_by(|a, b| Self::compare(a, b, sort_by, sort_order));
+            return;
+        }
+
+        // Lowercasing each name once is much cheaper than on every comparison
+        items.sort_by_cached_key(|item| (item.file_type != FileType::Directory, item.name.to_lowercase()));
+        if sort_order == SortOrder::Descending {
+            let directories = items.iter().take_while(|item| item.file_type == FileType::Directory).count();
+            items[..directories].reverse();
+            items[directories..].reverse();
+        }
+    }
+
+    /// Merges `batch`, already sorted the same way, into the sorted `items`.
+    pub fn merge_sorted(items: &mut Vec<FileItem>, batch: Vec<FileItem>, sort_by: SortBy, sort_order: SortOrder) {
+        let mut existing = std::mem::take(items).into_iter().peekable();
+        let mut batch = batch.into_iter().peekable();
+        let mut merged = Vec::with_capacity(existing.len() + batch.len());
+
+        while let (Some(a), Some(b)) = (existing.peek(), batch.peek()) {
+            if Self::compare(b, a, sort_by, sort_order) == std::cmp::Ordering::Less {
+                merged.extend(batch.next());
+            } else {
+                merged.extend(existing.next());
             }
-        });
+        }
+        merged.extend(existing);
+        merged.extend(batch);
+        *items = merged;
+    }
+
+    /// Orders items the way listings show them, always keeping directories first.
+    pub fn compare(a: &FileItem, b: &FileItem, sort_by: SortBy, sort_order: SortOrder) -> std::cmp::Ordering {
+        let directories_first = (a.file_type != FileType::Directory).cmp(&(b.file_type != FileType::Directory));
+        let comparison = match sort_by {
+            SortBy::Name => compare_ignoring_case(&a.name, &b.name),
+            SortBy::Size => a.size.cmp(&b.size),
+            SortBy::Modified => a.modified.cmp(&b.modified),
+            SortBy::Type => a.file_type.cmp(&b.file_type),
+            SortBy::Created => a.created.cmp(&b.created),
+        };
+
+        directories_first.then(match sort_order {
+            SortOrder::Ascending => comparison,
+            SortOrder::Descending => comparison.reverse(),
+        })
+    }
+}
 
-        // Always keep directories first
-        items.sort_by_key(|item| match item.file_type {
-            FileType::Directory => 0,
-            _ => 1,
-        });
+/// Compares names as if lowercased, without allocating.
+fn compare_ignoring_case(a: &str, b: &str) -> std::cmp::Ordering {
+    if a.is_ascii() && b.is_ascii() {
+        a.bytes().map(|byte| byte.to_ascii_lowercase()).cmp(b.bytes().map(|byte| byte.to_ascii_lowercase()))
+    } else {
+        a.chars().flat_map(char::to_lowercase).cmp(b.chars().flat_map(char::to_lowercase))
     }
 }
 
@@ -1,54 +1,208 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
 use anyhow::Result;
-use notify::{Event, RecursiveMode, Watcher, RecommendedWatcher};
+use serde::{Deserialize, Serialize};
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecursiveMode, Watcher, RecommendedWatcher};
 use std::sync::Arc;
 use tokio::sync::mpsc;
 
+use crate::core::clipboard::{self, Clipboard};
 use crate::core::file_item::{FileItem, SortBy, SortOrder};
+use crate::core::selection::{SelectMode, Selection};
+use crate::core::vfs::{ArchiveOverlay, LocalFs, SftpOverlay, Vfs};
+use crate::operations::control::JobControl;
+use crate::operations::jobs::JobKind;
+
+/// Entries read before the first ones are shown.
+const FIRST_BATCH: usize = 1000;
+
+/// Most entries read between updates of the listing.
+const MAX_BATCH: usize = 50_000;
+
+/// Quiet time after a file change before changes are applied.
+const CHANGE_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Longest a file change waits while more keep arriving.
+const CHANGE_MAX_DELAY: Duration = Duration::from_secs(1);
+
+/// File changes reported by the watcher and not yet applied to the items.
+#[derive(Debug, Default)]
+struct PendingChanges {
+    /// Names of entries in the watched folder that changed in any way.
+    names: HashSet<OsString>,
+    /// Entries renamed within the folder, from old name to new.
+    renames: Vec<(OsString, OsString)>,
+    /// The watcher lost track; only reading the whole folder again will do.
+    rescan: bool,
+    first: Option<Instant>,
+    last: Option<Instant>,
+}
+
+impl PendingChanges {
+    fn touch(&mut self, now: Instant) {
+        self.first.get_or_insert(now);
+        self.last = Some(now);
+    }
+
+    /// Changes have settled, or have waited long enough.
+    fn is_due(&self, now: Instant) -> bool {
+        match (self.first, self.last) {
+            (Some(first), Some(last)) => now - last >= CHANGE_DEBOUNCE || now - first >= CHANGE_MAX_DELAY,
+            _ => false,
+        }
+    }
+}
+
+/// Progress of reading the current folder, which happens in the background.
+#[derive(Debug, Clone, Default)]
+pub struct Listing {
+    /// Entries in the folder, once they've been listed.
+    pub total: Option<usize>,
+    /// Entries read so far, including hidden ones that aren't shown.
+    pub loaded: usize,
+    pub done: bool,
+    /// Stopped through [`FileManager::cancel_loading`] before every entry was read.
+    pub cancelled: bool,
+    pub error: Option<String>,
+}
+
+/// Where a file manager has been and how it lists and selects things, to be
+/// put back later, as when switching tabs or starting again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NavigationState {
+    pub history: Vec<PathBuf>,
+    pub history_index: usize,
+    pub sort_by: SortBy,
+    pub sort_order: SortOrder,
+    pub selected: Vec<PathBuf>,
+    pub focus: Option<PathBuf>,
+}
+
+impl NavigationState {
+    /// A fresh state at `path`, with nothing selected.
+    pub fn new(path: PathBuf, sort_by: SortBy, sort_order: SortOrder) -> Self {
+        Self {
+            history: vec![path],
+            history_index: 0,
+            sort_by,
+            sort_order,
+            selected: Vec::new(),
+            focus: None,
+        }
+    }
+
+    /// The folder the state is at.
+    pub fn path(&self) -> Option<&Path> {
+        self.history.get(self.history_index).map(PathBuf::as_path)
+    }
+}
 
 pub struct FileManager {
+    vfs: Arc<dyn Vfs>,
     current_path: Arc<RwLock<PathBuf>>,
     items: Arc<RwLock<Vec<FileItem>>>,
-    selected_items: Arc<RwLock<Vec<usize>>>,
-    clipboard: Arc<RwLock<Vec<PathBuf>>>,
+    selection: Arc<RwLock<Selection>>,
+    clipboard: Arc<RwLock<Clipboard>>,
     history: Arc<RwLock<Vec<PathBuf>>>,
     history_index: Arc<RwLock<usize>>,
     sort_by: Arc<RwLock<SortBy>>,
     sort_order: Arc<RwLock<SortOrder>>,
     show_hidden: Arc<RwLock<bool>>,
+    listing: Arc<RwLock<Listing>>,
+    /// Stops the background read of the current folder.
+    listing_control: Option<JobControl>,
     watcher: Option<RecommendedWatcher>,
     watcher_rx: Option<mpsc::UnboundedReceiver<notify::Result<Event>>>,
+    /// The watched folder, and where it is on the host filesystem.
+    watched: Option<(PathBuf, PathBuf)>,
+    pending_changes: PendingChanges,
 }
 
 impl FileManager {
     pub fn new() -> Self {
         let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/"));
-        
+        let vfs = Arc::new(SftpOverlay::new(Arc::new(LocalFs)));
+        Self::with_vfs(Arc::new(ArchiveOverlay::new(vfs)), home_dir)
+    }
+
+    /// Creates a file manager that browses `vfs`, starting at `home_dir`.
+    pub fn with_vfs(vfs: Arc<dyn Vfs>, home_dir: PathBuf) -> Self {
         Self {
+            vfs,
             current_path: Arc::new(RwLock::new(home_dir.clone())),
             items: Arc::new(RwLock::new(Vec::new())),
-            selected_items: Arc::new(RwLock::new(Vec::new())),
-            clipboard: Arc::new(RwLock::new(Vec::new())),
+            selection: Arc::new(RwLock::new(Selection::default())),
+            clipboard: Arc::new(RwLock::new(Clipboard::default())),
             history: Arc::new(RwLock::new(vec![home_dir])),
             history_index: Arc::new(RwLock::new(0)),
             sort_by: Arc::new(RwLock::new(SortBy::Name)),
             sort_order: Arc::new(RwLock::new(SortOrder::Ascending)),
             show_hidden: Arc::new(RwLock::new(false)),
+            listing: Arc::new(RwLock::new(Listing::default())),
+            listing_control: None,
             watcher: None,
             watcher_rx: None,
+            watched: None,
+            pending_changes: PendingChanges::default(),
         }
     }
 
+    /// Shares `other`'s clipboard, so files cut in one can be pasted in the other.
+    pub fn with_shared_clipboard(mut self, other: &FileManager) -> Self {
+        self.clipboard = other.clipboard.clone();
+        self
+    }
+
+    /// The filesystem this manager is browsing.
+    pub fn vfs(&self) -> Arc<dyn Vfs> {
+        self.vfs.clone()
+    }
+
     pub async fn navigate_to(&mut self, path: &Path) -> Result<()> {
-        if !path.exists() {
-            return Err(anyhow::anyhow!("Path does not exist: {}", path.display()));
-        }
+        check_folder(self.vfs.clone(), path.to_path_buf()).await?;
+        self.enter_folder(path).await
+    }
 
-        if !path.is_dir() {
-            return Err(anyhow::anyhow!("Path is not a directory: {}", path.display()));
+    /// Like [`Self::navigate_to`], but with `file_manager` unlocked while the
+    /// folder is checked, as that may mean waiting on a server.
+    pub async fn navigate(file_manager: &Mutex<FileManager>, path: &Path) -> Result<()> {
+        let vfs = file_manager.lock().await.vfs();
+        check_folder(vfs, path.to_path_buf()).await?;
+        file_manager.lock().await.enter_folder(path).await
+    }
+
+    /// Goes to the parent folder, keeping the focus on the folder just left.
+    /// The parent is checked with `file_manager` unlocked.
+    pub async fn navigate_up(file_manager: &Mutex<FileManager>) -> Result<()> {
+        let (vfs, child) = {
+            let fm = file_manager.lock().await;
+            (fm.vfs(), fm.get_current_path().await)
+        };
+        let Some(parent) = child.parent().map(Path::to_path_buf) else {
+            return Ok(());
+        };
+
+        // Stop at the top of remote locations rather than climbing into `sftp:`
+        let can_browse = {
+            let parent = parent.clone();
+            tokio::task::spawn_blocking(move || vfs.can_browse(&parent)).await?
+        };
+        if !can_browse {
+            return Ok(());
         }
 
+        let mut fm = file_manager.lock().await;
+        fm.enter_folder(&parent).await?;
+        fm.selection.write().await.select(&[], &child, SelectMode::Replace);
+        Ok(())
+    }
+
+    /// Shows `path`, which has been checked to be a folder, adding it to the history.
+    async fn enter_folder(&mut self, path: &Path) -> Result<()> {
         // Update current path
         {
             let mut current_path = self.current_path.write().await;
@@ -71,7 +225,7 @@ impl FileManager {
         }
 
         // Refresh items
-        self.refresh_items().await?;
+        self.load_items(false).await;
 
         // Setup file watcher
         self.setup_watcher(path)?;
@@ -79,53 +233,63 @@ impl FileManager {
         Ok(())
     }
 
+    /// Reads the current folder again. The current items stay until the first
+    /// entries have been read; the rest follow in batches, see [`Self::listing`].
     pub async fn refresh_items(&mut self) -> Result<()> {
-        let current_path = {
-            let path = self.current_path.read().await;
-            path.clone()
-        };
+        self.load_items(true).await;
+        Ok(())
+    }
 
+    /// Starts reading the current folder in the background, stopping any
+    /// read still going on.
+    async fn load_items(&mut self, keep_items: bool) {
+        if let Some(control) = self.listing_control.take() {
+            control.cancel();
+        }
+
+        let current_path = self.current_path.read().await.clone();
         let show_hidden = *self.show_hidden.read().await;
         let sort_by = *self.sort_by.read().await;
         let sort_order = *self.sort_order.read().await;
 
-        let mut items = Vec::new();
-
-        // Read directory entries
-        let entries = std::fs::read_dir(&current_path)?;
-        
-        for entry in entries {
-            let entry = entry?;
-            let path = entry.path();
-            
-            match FileItem::from_path(&path) {
-                Ok(item) => {
-                    if show_hidden || !item.is_hidden {
-                        items.push(item);
-                    }
-                }
-                Err(e) => {
-                    log::warn!("Failed to read file item {}: {}", path.display(), e);
-                }
+        {
+            let mut items = self.items.write().await;
+            if !keep_items {
+                items.clear();
+                *self.selection.write().await = Selection::default();
             }
+            *self.listing.write().await = Listing::default();
         }
 
-        // Sort items
-        FileItem::sort_items(&mut items, sort_by, sort_order);
+        let control = JobControl::new();
+        self.listing_control = Some(control.clone());
+        let loader = Loader {
+            vfs: self.vfs.clone(),
+            items: self.items.clone(),
+            listing: self.listing.clone(),
+            selection: self.selection.clone(),
+            control,
+            show_hidden,
+            sort_by,
+            sort_order,
+        };
+        tokio::task::spawn_blocking(move || loader.run(&current_path));
+    }
 
-        // Update items
-        {
-            let mut items_lock = self.items.write().await;
-            *items_lock = items;
-        }
+    /// How far reading the current folder has got.
+    pub async fn listing(&self) -> Listing {
+        self.listing.read().await.clone()
+    }
 
-        // Clear selection
-        {
-            let mut selected = self.selected_items.write().await;
-            selected.clear();
+    /// Stops reading the current folder, keeping the items read so far.
+    pub async fn cancel_loading(&self) {
+        if let Some(ref control) = self.listing_control {
+            control.cancel();
+        }
+        let mut listing = self.listing.write().await;
+        if !listing.done {
+            listing.cancelled = true;
         }
-
-        Ok(())
     }
 
     pub async fn get_current_path(&self) -> PathBuf {
@@ -136,35 +300,157 @@ impl FileManager {
         self.items.read().await.clone()
     }
 
-    pub async fn get_selected_items(&self) -> Vec<usize> {
-        self.selected_items.read().await.clone()
+    /// Runs `f` on the current items without copying them.
+    pub async fn with_items<R>(&self, f: impl FnOnce(&[FileItem]) -> R) -> R {
+        f(&self.items.read().await)
     }
 
-    pub async fn select_item(&self, index: usize, multiple: bool) {
-        let mut selected = self.selected_items.write().await;
-        
-        if multiple {
-            if selected.contains(&index) {
-                selected.retain(|&x| x != index);
-            } else {
-                selected.push(index);
-            }
-        } else {
-            selected.clear();
-            selected.push(index);
-        }
+    /// Copies out the items in `range`, which may run past the end, marking
+    /// the selected and cut ones.
+    pub async fn items_in(&self, range: std::ops::Range<usize>) -> Vec<FileItem> {
+        let items = self.items.read().await;
+        let selection = self.selection.read().await;
+        let clipboard = self.clipboard.read().await;
+        let end = range.end.min(items.len());
+        items[range.start.min(end)..end].iter()
+            .map(|item| FileItem {
+                is_selected: selection.contains(&item.path),
+                is_cut: clipboard.is_cut_path(&item.path),
+                ..item.clone()
+            })
+            .collect()
+    }
+
+    /// Runs `f` on the selection without copying it.
+    pub async fn with_selection<R>(&self, f: impl FnOnce(&Selection) -> R) -> R {
+        f(&*self.selection.read().await)
+    }
+
+    /// The selected paths, in listing order.
+    pub async fn selected_paths(&self) -> Vec<PathBuf> {
+        let items = self.items.read().await;
+        self.selection.read().await.ordered(&items)
+    }
+
+    pub async fn select(&self, path: &Path, mode: SelectMode) {
+        let items = self.items.read().await;
+        self.selection.write().await.select(&items, path, mode);
+    }
+
+    /// Moves the keyboard focus to the item `step` picks, given the items and
+    /// where the focus is now, and selects it, or the range to it from the
+    /// anchor with `extend`. Returns where the focus ended up.
+    pub async fn move_focus(
+        &self,
+        step: impl FnOnce(&[FileItem], Option<usize>) -> Option<usize>,
+        extend: bool,
+    ) -> Option<usize> {
+        let items = self.items.read().await;
+        let mut selection = self.selection.write().await;
+        let current = selection.focus().and_then(|focus| items.iter().position(|item| item.path == focus));
+        let index = step(&items, current)?.min(items.len().checked_sub(1)?);
+
+        let mode = if extend { SelectMode::Range } else { SelectMode::Replace };
+        selection.select(&items, &items[index].path, mode);
+        Some(index)
+    }
+
+    /// The item with the keyboard focus.
+    pub async fn focused_item(&self) -> Option<FileItem> {
+        let items = self.items.read().await;
+        let selection = self.selection.read().await;
+        let focus = selection.focus()?;
+        items.iter().find(|item| item.path == focus).cloned()
     }
 
     pub async fn select_all(&self) {
-        let items_count = self.items.read().await.len();
-        let mut selected = self.selected_items.write().await;
-        selected.clear();
-        selected.extend(0..items_count);
+        let items = self.items.read().await;
+        self.selection.write().await.select_all(&items);
+    }
+
+    pub async fn invert_selection(&self) {
+        let items = self.items.read().await;
+        self.selection.write().await.invert(&items);
     }
 
     pub async fn clear_selection(&self) {
-        let mut selected = self.selected_items.write().await;
-        selected.clear();
+        self.selection.write().await.clear();
+    }
+
+    /// Puts `paths` on the clipboard to be copied, or moved when `cut`.
+    pub async fn set_clipboard(&self, paths: Vec<PathBuf>, cut: bool) {
+        self.clipboard.write().await.set(paths, cut);
+    }
+
+    /// Runs `f` on the clipboard without copying it.
+    pub async fn with_clipboard<R>(&self, f: impl FnOnce(&Clipboard) -> R) -> R {
+        f(&*self.clipboard.read().await)
+    }
+
+    /// The job that pastes the clipboard into `destination`, or the current
+    /// folder without one.
+    pub async fn paste_job(&self, destination: Option<&Path>) -> Option<JobKind> {
+        let destination = match destination {
+            Some(destination) => destination.to_path_buf(),
+            None => self.get_current_path().await,
+        };
+        self.clipboard.write().await.paste(&destination)
+    }
+
+    /// The clipboard as `text/uri-list`, for other applications. Only files
+    /// on the local disk can be named this way; the others are listed as
+    /// comments, so the text is never empty and always replaces what an
+    /// earlier copy left on the system clipboard.
+    pub async fn clipboard_uri_list(&self) -> String {
+        let clipboard = self.clipboard.read().await;
+        clipboard.paths().iter()
+            .map(|path| match self.vfs.local_path(path) {
+                Some(local_path) => clipboard::to_uri_list(&[local_path]),
+                None => format!("# {}\r\n", path.display()),
+            })
+            .collect()
+    }
+
+    pub async fn save_state(&self) -> NavigationState {
+        NavigationState {
+            history: self.history.read().await.clone(),
+            history_index: *self.history_index.read().await,
+            sort_by: *self.sort_by.read().await,
+            sort_order: *self.sort_order.read().await,
+            selected: self.selected_paths().await,
+            focus: self.selection.read().await.focus().map(Path::to_path_buf),
+        }
+    }
+
+    /// Goes back to where `state` was, with its history, sort order and
+    /// selection, reading the folder again. Only folders on the host
+    /// filesystem are checked first; remote ones fail in the listing.
+    pub async fn restore_state(&mut self, state: NavigationState) -> Result<()> {
+        let Some(path) = state.path().map(Path::to_path_buf) else {
+            return Err(anyhow::anyhow!("No folder to go back to"));
+        };
+        if self.vfs.local_path(&path).is_some_and(|local_path| !local_path.is_dir()) {
+            return Err(anyhow::anyhow!("Path is not a directory: {}", path.display()));
+        }
+
+        *self.current_path.write().await = path.clone();
+        *self.history.write().await = state.history;
+        *self.history_index.write().await = state.history_index;
+        *self.sort_by.write().await = state.sort_by;
+        *self.sort_order.write().await = state.sort_order;
+        self.load_items(false).await;
+
+        // The listing may already be done, and then won't check the selection against it
+        {
+            let items = self.items.read().await;
+            let mut selection = Selection::restore(state.selected, state.focus);
+            if self.listing.read().await.done {
+                selection.retain_listed(&items);
+            }
+            *self.selection.write().await = selection;
+        }
+
+        self.setup_watcher(&path)
     }
 
     pub async fn can_go_back(&self) -> bool {
@@ -193,9 +479,10 @@ impl FileManager {
         if can_go_back {
             {
                 let mut current_path = self.current_path.write().await;
-                *current_path = new_path;
+                *current_path = new_path.clone();
             }
-            self.refresh_items().await?;
+            self.load_items(false).await;
+            self.setup_watcher(&new_path)?;
         }
 
         Ok(())
@@ -216,27 +503,17 @@ impl FileManager {
         if can_go_forward {
             {
                 let mut current_path = self.current_path.write().await;
-                *current_path = new_path;
+                *current_path = new_path.clone();
             }
-            self.refresh_items().await?;
-        }
-
-        Ok(())
-    }
-
-    pub async fn go_up(&mut self) -> Result<()> {
-        let parent = {
-            let current_path = self.current_path.read().await;
-            current_path.parent().map(|p| p.to_path_buf())
-        };
-
-        if let Some(parent_path) = parent {
-            self.navigate_to(&parent_path).await?;
+            self.load_items(false).await;
+            self.setup_watcher(&new_path)?;
         }
 
         Ok(())
     }
 
+    /// Sorts the items already read in the new order. A read still going on
+    /// sorts as it reads, so it starts over, keeping the items shown so far.
     pub async fn set_sort(&mut self, sort_by: SortBy, sort_order: SortOrder) -> Result<()> {
         {
             let mut sort_by_lock = self.sort_by.write().await;
@@ -244,8 +521,17 @@ impl FileManager {
             *sort_by_lock = sort_by;
             *sort_order_lock = sort_order;
         }
-        
-        self.refresh_items().await
+
+        FileItem::sort_items(&mut self.items.write().await, sort_by, sort_order);
+
+        let loading = {
+            let listing = self.listing.read().await;
+            !listing.done && !listing.cancelled
+        };
+        if loading {
+            self.load_items(true).await;
+        }
+        Ok(())
     }
 
     pub async fn toggle_show_hidden(&mut self) -> Result<()> {
@@ -262,33 +548,247 @@ impl FileManager {
     }
 
     fn setup_watcher(&mut self, path: &Path) -> Result<()> {
+        self.pending_changes = PendingChanges::default();
+
+        // Only paths on the host filesystem can be watched
+        let Some(local_path) = self.vfs.local_path(path) else {
+            self.watcher = None;
+            self.watcher_rx = None;
+            self.watched = None;
+            return Ok(());
+        };
+
         let (tx, rx) = mpsc::unbounded_channel();
         let mut watcher = notify::recommended_watcher(move |res| {
-            if let Err(_) = tx.send(res) {
+            if tx.send(res).is_err() {
                 // Channel closed, ignore
             }
         })?;
-        watcher.watch(path, RecursiveMode::NonRecursive)?;
+        watcher.watch(&local_path, RecursiveMode::NonRecursive)?;
         
         self.watcher = Some(watcher);
         self.watcher_rx = Some(rx);
+        self.watched = Some((path.to_path_buf(), local_path));
         
         Ok(())
     }
 
-    pub fn check_file_changes(&mut self) -> Vec<Event> {
-        let mut events = Vec::new();
-        
-        if let Some(ref mut rx) = self.watcher_rx {
-            while let Ok(result) = rx.try_recv() {
-                if let Ok(event) = result {
-                    events.push(event);
+    /// Whether changes to the current folder are picked up by themselves.
+    /// Remote folders and archives aren't watched.
+    pub fn is_watched(&self) -> bool {
+        self.watcher.is_some()
+    }
+
+    /// Applies changes reported by the watcher to the items once they have
+    /// settled, updating only the entries that changed. The selection is kept,
+    /// following items that were renamed.
+    pub async fn apply_file_changes(&mut self) -> Result<()> {
+        self.collect_file_changes();
+        if !self.pending_changes.is_due(Instant::now()) {
+            return Ok(());
+        }
+
+        // Entries merged in under a read still going on could show up twice
+        {
+            let listing = self.listing.read().await;
+            if !listing.done && !listing.cancelled {
+                return Ok(());
+            }
+        }
+
+        let changes = std::mem::take(&mut self.pending_changes);
+        let current_path = self.current_path.read().await.clone();
+        if changes.rescan || self.watched.as_ref().map(|(path, _)| path) != Some(&current_path) {
+            return self.refresh_items().await;
+        }
+
+        let show_hidden = *self.show_hidden.read().await;
+        let sort_by = *self.sort_by.read().await;
+        let sort_order = *self.sort_order.read().await;
+
+        let changed: HashSet<PathBuf> = changes.names.iter().map(|name| current_path.join(name)).collect();
+        let mut updated = Vec::new();
+        for path in &changed {
+            match FileItem::from_vfs(self.vfs.as_ref(), path) {
+                Ok(item) => {
+                    if show_hidden || !item.is_hidden {
+                        updated.push(item);
+                    }
                 }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => log::warn!("Failed to read file item {}: {}", path.display(), e),
             }
         }
-        
-        events
+        FileItem::sort_items(&mut updated, sort_by, sort_order);
+
+        let mut items = self.items.write().await;
+        let mut selection = self.selection.write().await;
+
+        items.retain(|item| !changed.contains(&item.path));
+        FileItem::merge_sorted(&mut items, updated, sort_by, sort_order);
+
+        for (from, to) in &changes.renames {
+            selection.rename(&current_path.join(from), &current_path.join(to));
+        }
+        selection.retain_listed(&items);
+
+        Ok(())
     }
+
+    /// Moves events from the watcher into the pending changes.
+    fn collect_file_changes(&mut self) {
+        let (Some(rx), Some((_, watched))) = (self.watcher_rx.as_mut(), self.watched.as_ref()) else {
+            return;
+        };
+        let pending = &mut self.pending_changes;
+        let now = Instant::now();
+
+        while let Ok(result) = rx.try_recv() {
+            let event = match result {
+                Ok(event) => event,
+                Err(e) => {
+                    log::warn!("File watcher error: {}", e);
+                    pending.rescan = true;
+                    pending.touch(now);
+                    continue;
+                }
+            };
+
+            // Opening and reading files changes nothing shown
+            if matches!(event.kind, EventKind::Access(_)) {
+                continue;
+            }
+            if event.need_rescan() {
+                pending.rescan = true;
+            }
+
+            let name_in_folder = |path: &PathBuf| {
+                path.file_name().filter(|_| path.parent() == Some(watched.as_path())).map(|name| name.to_os_string())
+            };
+            if let (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, to]) = (event.kind, event.paths.as_slice()) {
+                if let (Some(from), Some(to)) = (name_in_folder(from), name_in_folder(to)) {
+                    pending.renames.push((from, to));
+                }
+            }
+
+            for path in &event.paths {
+                if path == watched {
+                    // The folder itself was removed or moved
+                    if matches!(event.kind, EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))) {
+                        pending.rescan = true;
+                    }
+                } else if let Some(name) = name_in_folder(path) {
+                    pending.names.insert(name);
+                }
+            }
+            pending.touch(now);
+        }
+    }
+}
+
+/// Reads a folder on a blocking thread, publishing sorted items in growing batches.
+struct Loader {
+    vfs: Arc<dyn Vfs>,
+    items: Arc<RwLock<Vec<FileItem>>>,
+    listing: Arc<RwLock<Listing>>,
+    selection: Arc<RwLock<Selection>>,
+    control: JobControl,
+    show_hidden: bool,
+    sort_by: SortBy,
+    sort_order: SortOrder,
+}
+
+impl Loader {
+    fn run(&self, path: &Path) {
+        let entries = match self.vfs.read_dir(path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::error!("Failed to read {}: {}", path.display(), e);
+                self.publish(Vec::new(), true, |listing| {
+                    listing.error = Some(e.to_string());
+                    listing.done = true;
+                });
+                return;
+            }
+        };
+        let total = entries.len();
+        self.listing.blocking_write().total = Some(total);
+
+        let mut batch = Vec::new();
+        let mut batch_size = FIRST_BATCH;
+        let mut first = true;
+        for (read, path) in entries.into_iter().enumerate() {
+            if self.control.is_cancelled() {
+                return;
+            }
+            match FileItem::from_vfs(self.vfs.as_ref(), &path) {
+                Ok(item) => {
+                    if self.show_hidden || !item.is_hidden {
+                        batch.push(item);
+                    }
+                }
+                Err(e) => {
+                    log::warn!("Failed to read file item {}: {}", path.display(), e);
+                }
+            }
+
+            // Batches grow with the listing so merging them stays cheap
+            let loaded = read + 1;
+            if loaded % batch_size == 0 && loaded < total {
+                if !self.publish(std::mem::take(&mut batch), first, |listing| listing.loaded = loaded) {
+                    return;
+                }
+                first = false;
+                batch_size = (loaded / 4).clamp(FIRST_BATCH, MAX_BATCH);
+            }
+        }
+
+        self.publish(batch, first, |listing| {
+            listing.loaded = total;
+            listing.done = true;
+        });
+    }
+
+    /// Sorts `batch` into the items, replacing them if it is the `first`.
+    /// Returns `false` once the read has been stopped.
+    fn publish(&self, mut batch: Vec<FileItem>, first: bool, update: impl FnOnce(&mut Listing)) -> bool {
+        FileItem::sort_items(&mut batch, self.sort_by, self.sort_order);
+
+        // Checked under the lock, as a newer read clears the items under it
+        let mut items = self.items.blocking_write();
+        if self.control.is_cancelled() {
+            return false;
+        }
+        if first {
+            *items = batch;
+        } else {
+            FileItem::merge_sorted(&mut items, batch, self.sort_by, self.sort_order);
+        }
+        let mut listing = self.listing.blocking_write();
+        update(&mut listing);
+
+        // Items gone since the last read drop out of the selection
+        if listing.done {
+            self.selection.blocking_write().retain_listed(&items);
+        }
+        true
+    }
+}
+
+/// Fails unless `path` is a folder that can be browsed. Checked on a
+/// blocking thread, as remote folders are checked over the network.
+async fn check_folder(vfs: Arc<dyn Vfs>, path: PathBuf) -> Result<()> {
+    tokio::task::spawn_blocking(move || {
+        if !vfs.exists(&path) {
+            return Err(anyhow::anyhow!("Path does not exist: {}", path.display()));
+        }
+
+        if !vfs.can_browse(&path) {
+            return Err(anyhow::anyhow!("Path is not a directory: {}", path.display()));
+        }
+
+        Ok(())
+    }).await?
 }
 
 impl Default for FileManager {
@@ -296,3 +796,89 @@ impl Default for FileManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vfs::{self, MemoryFs};
+
+    fn file_manager() -> Mutex<FileManager> {
+        let vfs = MemoryFs::new()
+            .with_file("/home/notes.txt", "notes")
+            .with_dir("/home/projects/app");
+        Mutex::new(FileManager::with_vfs(Arc::new(vfs), PathBuf::from("/home")))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn navigates_into_folders_only() {
+        let file_manager = file_manager();
+
+        FileManager::navigate(&file_manager, Path::new("/home/projects")).await.unwrap();
+        assert_eq!(file_manager.lock().await.get_current_path().await, Path::new("/home/projects"));
+        assert!(file_manager.lock().await.can_go_back().await);
+
+        assert!(FileManager::navigate(&file_manager, Path::new("/home/notes.txt")).await.is_err());
+        assert!(FileManager::navigate(&file_manager, Path::new("/home/missing")).await.is_err());
+        assert_eq!(file_manager.lock().await.get_current_path().await, Path::new("/home/projects"));
+    }
+
+    async fn wait_for_listing(file_manager: &Mutex<FileManager>) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !file_manager.lock().await.listing().await.done {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await.unwrap();
+    }
+
+    async fn names(file_manager: &Mutex<FileManager>) -> Vec<String> {
+        file_manager.lock().await.with_items(|items| items.iter().map(|item| item.name.clone()).collect()).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn sorting_reorders_without_reading_again() {
+        let vfs = MemoryFs::new()
+            .with_file("/dir/b.txt", "bb")
+            .with_file("/dir/a.txt", "aaa")
+            .with_file("/dir/c.txt", "c");
+        let file_manager = Mutex::new(FileManager::with_vfs(Arc::new(vfs.clone()), PathBuf::from("/")));
+        FileManager::navigate(&file_manager, Path::new("/dir")).await.unwrap();
+        wait_for_listing(&file_manager).await;
+        assert_eq!(names(&file_manager).await, ["a.txt", "b.txt", "c.txt"]);
+
+        // A file added since isn't picked up, as the folder isn't read again
+        vfs::write(&vfs, Path::new("/dir/d.txt"), b"").unwrap();
+        file_manager.lock().await.set_sort(SortBy::Size, SortOrder::Descending).await.unwrap();
+        assert!(file_manager.lock().await.listing().await.done);
+        assert_eq!(names(&file_manager).await, ["a.txt", "b.txt", "c.txt"]);
+
+        file_manager.lock().await.set_sort(SortBy::Name, SortOrder::Descending).await.unwrap();
+        assert_eq!(names(&file_manager).await, ["c.txt", "b.txt", "a.txt"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn going_up_focuses_the_folder_left() {
+        let file_manager = file_manager();
+        FileManager::navigate(&file_manager, Path::new("/home/projects/app")).await.unwrap();
+
+        FileManager::navigate_up(&file_manager).await.unwrap();
+        let fm = file_manager.lock().await;
+        assert_eq!(fm.get_current_path().await, Path::new("/home/projects"));
+        assert_eq!(
+            fm.with_selection(|selection| selection.focus().map(Path::to_path_buf)).await,
+            Some(PathBuf::from("/home/projects/app")),
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn clipboard_text_is_never_empty() {
+        let file_manager = file_manager();
+        let fm = file_manager.lock().await;
+        fm.set_clipboard(vec![PathBuf::from("/home/notes.txt")], false).await;
+
+        // Nothing here is on the local disk, so other apps get no files, and
+        // pasting the text back pastes the clipboard's own
+        let text = fm.clipboard_uri_list().await;
+        assert_eq!(text, "# /home/notes.txt\r\n");
+        assert!(clipboard::parse_uri_list(&text).is_empty());
+    }
+}
@@ -0,0 +1,371 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+use crate::core::vfs::Vfs;
+
+/// Quiet time after a change in an expanded folder before it is read again.
+const CHANGE_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// A folder directly inside an expanded one.
+#[derive(Debug, Clone)]
+pub struct Subfolder {
+    pub path: PathBuf,
+    pub name: String,
+    pub is_hidden: bool,
+}
+
+/// What is known of the folders inside an expanded folder.
+#[derive(Debug, Clone)]
+pub enum Subfolders {
+    Loading,
+    /// Sorted by name.
+    Loaded(Vec<Subfolder>),
+    Failed(String),
+}
+
+type LoadResult = (PathBuf, io::Result<Vec<Subfolder>>);
+
+/// A tree of folders read as it is expanded, as shown in the sidebar.
+///
+/// Subfolders are read on a blocking thread when a folder is expanded and
+/// forgotten when it is collapsed. Expanded folders on the host filesystem
+/// are watched, so folders created, removed or renamed in them show up.
+pub struct FolderTree {
+    vfs: Arc<dyn Vfs>,
+    runtime: tokio::runtime::Handle,
+    expanded: HashSet<PathBuf>,
+    subfolders: HashMap<PathBuf, Subfolders>,
+    loaded_tx: mpsc::UnboundedSender<LoadResult>,
+    loaded_rx: mpsc::UnboundedReceiver<LoadResult>,
+    watcher: Option<RecommendedWatcher>,
+    watcher_rx: mpsc::UnboundedReceiver<notify::Result<Event>>,
+    /// Watched host paths, and the folders they stand for.
+    watched: HashMap<PathBuf, PathBuf>,
+    /// Expanded folders with changes not read yet.
+    changed: HashSet<PathBuf>,
+    last_change: Option<Instant>,
+}
+
+impl FolderTree {
+    pub fn new(vfs: Arc<dyn Vfs>, runtime: tokio::runtime::Handle) -> Self {
+        let (loaded_tx, loaded_rx) = mpsc::unbounded_channel();
+        let (watcher_tx, watcher_rx) = mpsc::unbounded_channel();
+        let watcher = notify::recommended_watcher(move |res| {
+            if watcher_tx.send(res).is_err() {
+                // Channel closed, ignore
+            }
+        });
+        let watcher = match watcher {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                log::warn!("Folder tree won't follow changes: {}", e);
+                None
+            }
+        };
+
+        Self {
+            vfs,
+            runtime,
+            expanded: HashSet::new(),
+            subfolders: HashMap::new(),
+            loaded_tx,
+            loaded_rx,
+            watcher,
+            watcher_rx,
+            watched: HashMap::new(),
+            changed: HashSet::new(),
+            last_change: None,
+        }
+    }
+
+    pub fn is_expanded(&self, path: &Path) -> bool {
+        self.expanded.contains(path)
+    }
+
+    /// The folders inside `path`, if it is expanded.
+    pub fn subfolders(&self, path: &Path) -> Option<&Subfolders> {
+        self.subfolders.get(path)
+    }
+
+    pub fn set_expanded(&mut self, path: &Path, expanded: bool) {
+        if expanded {
+            self.expand(path);
+        } else {
+            self.collapse(path);
+        }
+    }
+
+    /// Expands `path`, reading its subfolders unless they are already known.
+    pub fn expand(&mut self, path: &Path) {
+        self.expanded.insert(path.to_path_buf());
+        if !self.subfolders.contains_key(path) {
+            self.subfolders.insert(path.to_path_buf(), Subfolders::Loading);
+            self.load(path);
+        }
+    }
+
+    /// Collapses `path` and every folder below it, forgetting what was read.
+    pub fn collapse(&mut self, path: &Path) {
+        self.expanded.retain(|expanded| !expanded.starts_with(path));
+        let forgotten: Vec<PathBuf> = self.subfolders.keys()
+            .filter(|folder| folder.starts_with(path))
+            .cloned()
+            .collect();
+        for folder in forgotten {
+            self.subfolders.remove(&folder);
+            self.unwatch(&folder);
+        }
+    }
+
+    /// Expands the folders from `root` down to `path`, so that `path` shows.
+    pub fn reveal(&mut self, root: &Path, path: &Path) {
+        let Ok(relative) = path.strip_prefix(root) else {
+            return;
+        };
+        let mut folder = root.to_path_buf();
+        for component in relative.components() {
+            self.expand(&folder);
+            folder.push(component);
+        }
+    }
+
+    /// Takes in subfolders that have been read, and reads again folders the
+    /// watcher reported changes in once they have settled.
+    pub fn poll(&mut self) {
+        while let Ok((path, result)) = self.loaded_rx.try_recv() {
+            // Collapsed while it was being read
+            if !self.expanded.contains(&path) {
+                continue;
+            }
+            match result {
+                Ok(folders) => {
+                    // Expanded folders that went away are forgotten with what is below them
+                    let kept: HashSet<&Path> = folders.iter().map(|folder| folder.path.as_path()).collect();
+                    let gone: Vec<PathBuf> = self.expanded.iter()
+                        .filter(|expanded| expanded.parent() == Some(path.as_path()) && !kept.contains(expanded.as_path()))
+                        .cloned()
+                        .collect();
+                    for folder in gone {
+                        self.collapse(&folder);
+                    }
+                    self.subfolders.insert(path.clone(), Subfolders::Loaded(folders));
+                    self.watch(&path);
+                }
+                Err(e) => {
+                    self.subfolders.insert(path, Subfolders::Failed(e.to_string()));
+                }
+            }
+        }
+
+        self.collect_changes();
+        if self.last_change.is_some_and(|last| last.elapsed() >= CHANGE_DEBOUNCE) {
+            self.last_change = None;
+            for path in std::mem::take(&mut self.changed) {
+                if self.expanded.contains(&path) {
+                    self.load(&path);
+                }
+            }
+        }
+    }
+
+    /// Reads the subfolders of `path` on a blocking thread, for [`Self::poll`]
+    /// to take in. What was read before stays until then.
+    fn load(&self, path: &Path) {
+        let vfs = self.vfs.clone();
+        let loaded_tx = self.loaded_tx.clone();
+        let path = path.to_path_buf();
+        self.runtime.spawn_blocking(move || {
+            let result = read_subfolders(vfs.as_ref(), &path);
+            if loaded_tx.send((path, result)).is_err() {
+                // Tree dropped, ignore
+            }
+        });
+    }
+
+    fn watch(&mut self, path: &Path) {
+        // Only paths on the host filesystem can be watched
+        let (Some(watcher), Some(local_path)) = (self.watcher.as_mut(), self.vfs.local_path(path)) else {
+            return;
+        };
+        if self.watched.contains_key(&local_path) {
+            return;
+        }
+        match watcher.watch(&local_path, RecursiveMode::NonRecursive) {
+            Ok(()) => {
+                self.watched.insert(local_path, path.to_path_buf());
+            }
+            Err(e) => log::debug!("Can't watch {}: {}", local_path.display(), e),
+        }
+    }
+
+    fn unwatch(&mut self, path: &Path) {
+        let (Some(watcher), Some(local_path)) = (self.watcher.as_mut(), self.vfs.local_path(path)) else {
+            return;
+        };
+        if self.watched.remove(&local_path).is_some() {
+            if let Err(e) = watcher.unwatch(&local_path) {
+                log::debug!("Can't stop watching {}: {}", local_path.display(), e);
+            }
+        }
+    }
+
+    /// Notes which expanded folders the watcher reported changes in.
+    fn collect_changes(&mut self) {
+        let now = Instant::now();
+        while let Ok(result) = self.watcher_rx.try_recv() {
+            let event = match result {
+                Ok(event) => event,
+                Err(e) => {
+                    log::warn!("Folder tree watcher error: {}", e);
+                    self.changed.extend(self.watched.values().cloned());
+                    self.last_change = Some(now);
+                    continue;
+                }
+            };
+
+            // Only folders appearing, going away or changing names matter
+            if matches!(
+                event.kind,
+                EventKind::Access(_) | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Metadata(_))
+            ) {
+                continue;
+            }
+            if event.need_rescan() {
+                self.changed.extend(self.watched.values().cloned());
+            }
+
+            for path in &event.paths {
+                // A change inside a watched folder, or to a watched folder itself
+                let folders = [path.parent(), Some(path.as_path())];
+                for folder in folders.into_iter().flatten() {
+                    if let Some(folder) = self.watched.get(folder) {
+                        self.changed.insert(folder.clone());
+                    }
+                }
+            }
+            self.last_change = Some(now);
+        }
+    }
+}
+
+/// The folders directly inside `path`, sorted by name.
+fn read_subfolders(vfs: &dyn Vfs, path: &Path) -> io::Result<Vec<Subfolder>> {
+    let mut folders: Vec<Subfolder> = vfs.read_dir(path)?
+        .into_iter()
+        .filter_map(|path| {
+            let metadata = vfs.metadata(&path).ok()?;
+            if !metadata.is_dir() {
+                return None;
+            }
+            let name = path.file_name()?.to_string_lossy().to_string();
+            Some(Subfolder { path, name, is_hidden: metadata.is_hidden })
+        })
+        .collect();
+    folders.sort_by_cached_key(|folder| folder.name.to_lowercase());
+    Ok(folders)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vfs::{LocalFs, MemoryFs};
+
+    fn tree(vfs: impl Vfs + 'static) -> FolderTree {
+        FolderTree::new(Arc::new(vfs), tokio::runtime::Handle::current())
+    }
+
+    /// The names of the subfolders read for `path`, once they are read.
+    fn names(tree: &FolderTree, path: impl AsRef<Path>) -> Option<Vec<String>> {
+        match tree.subfolders(path.as_ref())? {
+            Subfolders::Loaded(folders) => Some(folders.iter().map(|folder| folder.name.clone()).collect()),
+            _ => None,
+        }
+    }
+
+    /// Polls `tree` until `done` holds, for at most a few seconds.
+    async fn poll_until(tree: &mut FolderTree, done: impl Fn(&FolderTree) -> bool) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            tree.poll();
+            if done(tree) {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        false
+    }
+
+    fn folders() -> MemoryFs {
+        MemoryFs::new()
+            .with_dir("/root/b/c")
+            .with_dir("/root/B2")
+            .with_dir("/root/a")
+            .with_file("/root/file.txt", "")
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn expands_and_collapses_folders() {
+        let mut tree = tree(folders());
+        tree.expand(Path::new("/root"));
+        assert!(matches!(tree.subfolders(Path::new("/root")), Some(Subfolders::Loading)));
+        assert!(poll_until(&mut tree, |tree| names(tree, "/root").is_some()).await);
+        // Folders only, sorted regardless of case
+        assert_eq!(names(&tree, "/root").unwrap(), ["a", "b", "B2"]);
+
+        tree.expand(Path::new("/root/b"));
+        assert!(poll_until(&mut tree, |tree| names(tree, "/root/b").is_some()).await);
+        assert_eq!(names(&tree, "/root/b").unwrap(), ["c"]);
+
+        // Collapsing forgets everything below
+        tree.collapse(Path::new("/root"));
+        assert!(!tree.is_expanded(Path::new("/root")));
+        assert!(!tree.is_expanded(Path::new("/root/b")));
+        assert!(tree.subfolders(Path::new("/root/b")).is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reveal_expands_the_folders_above_a_path() {
+        let mut tree = tree(folders());
+        tree.reveal(Path::new("/root"), Path::new("/root/b/c"));
+
+        assert!(tree.is_expanded(Path::new("/root")));
+        assert!(tree.is_expanded(Path::new("/root/b")));
+        assert!(!tree.is_expanded(Path::new("/root/b/c")));
+        assert!(poll_until(&mut tree, |tree| names(tree, "/root/b").is_some()).await);
+
+        // Paths outside the root are left alone
+        tree.reveal(Path::new("/root/a"), Path::new("/elsewhere/x"));
+        assert!(!tree.is_expanded(Path::new("/root/a")));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn follows_folders_created_and_removed_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("old")).unwrap();
+        let mut tree = tree(LocalFs);
+        if tree.watcher.is_none() {
+            // No file watching here
+            return;
+        }
+
+        tree.expand(dir.path());
+        tree.expand(&dir.path().join("old"));
+        assert!(poll_until(&mut tree, |tree| names(tree, dir.path()).is_some() && names(tree, dir.path().join("old")).is_some()).await);
+        assert_eq!(names(&tree, dir.path()).unwrap(), ["old"]);
+
+        std::fs::create_dir(dir.path().join("new")).unwrap();
+        assert!(poll_until(&mut tree, |tree| names(tree, dir.path()).unwrap() == ["new", "old"]).await);
+
+        // A removed folder goes, and so does what was expanded in it
+        std::fs::remove_dir(dir.path().join("old")).unwrap();
+        assert!(poll_until(&mut tree, |tree| names(tree, dir.path()).unwrap() == ["new"]).await);
+        assert!(!tree.is_expanded(&dir.path().join("old")));
+        assert!(tree.subfolders(&dir.path().join("old")).is_none());
+    }
+}
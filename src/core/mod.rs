@@ -3,4 +3,10 @@ pub mod file_manager;
 pub mod bookmark;
 pub mod settings;
 pub mod search;
+pub mod selection;
+pub mod clipboard;
+pub mod duplicates;
+pub mod disk_usage;
+pub mod folder_tree;
 pub mod thumbnail;
+pub mod vfs;
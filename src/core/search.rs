@@ -3,6 +3,7 @@ use anyhow::Result;
 use regex::Regex;
 use walkdir::WalkDir;
 
+#[derive(Default)]
 pub struct SearchQuery {
     pub pattern: String,
     pub is_regex: bool,
@@ -15,22 +16,6 @@ pub struct SearchQuery {
     pub modified_before: Option<chrono::DateTime<chrono::Utc>>,
 }
 
-impl Default for SearchQuery {
-    fn default() -> Self {
-        Self {
-            pattern: String::new(),
-            is_regex: false,
-            case_sensitive: false,
-            search_in_content: false,
-            file_types: Vec::new(),
-            size_min: None,
-            size_max: None,
-            modified_after: None,
-            modified_before: None,
-        }
-    }
-}
-
 #[derive(Debug, Clone)]
 pub struct SearchResult {
     pub path: PathBuf,
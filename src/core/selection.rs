@@ -0,0 +1,275 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::core::file_item::FileItem;
+
+/// How clicking an item changes the selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectMode {
+    /// Select only this item (plain click).
+    Replace,
+    /// Add or remove this item (Ctrl+click).
+    Toggle,
+    /// Select from the anchor to this item, and nothing else (Shift+click).
+    Range,
+    /// Add the items from the anchor to this item (Ctrl+Shift+click).
+    AddRange,
+}
+
+impl SelectMode {
+    /// The mode a click with these modifier keys asks for.
+    pub fn from_modifiers(ctrl: bool, shift: bool) -> Self {
+        match (ctrl, shift) {
+            (false, false) => SelectMode::Replace,
+            (true, false) => SelectMode::Toggle,
+            (false, true) => SelectMode::Range,
+            (true, true) => SelectMode::AddRange,
+        }
+    }
+}
+
+/// The selected items of a listing, kept by path so it stays on the same
+/// files when the listing is sorted, refreshed or changed on disk.
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    paths: HashSet<PathBuf>,
+    /// Where range selections start: the last item picked without Shift.
+    anchor: Option<PathBuf>,
+    /// The item last picked.
+    focus: Option<PathBuf>,
+    /// Combined size of the selected items.
+    bytes: u64,
+}
+
+impl Selection {
+    /// A selection of `paths` focused on `focus`, as saved from another one.
+    /// Its size is counted once it is checked against a listing.
+    pub fn restore(paths: Vec<PathBuf>, focus: Option<PathBuf>) -> Self {
+        Self {
+            paths: paths.into_iter().collect(),
+            anchor: focus.clone(),
+            focus,
+            bytes: 0,
+        }
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.paths.contains(path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn anchor(&self) -> Option<&Path> {
+        self.anchor.as_deref()
+    }
+
+    pub fn focus(&self) -> Option<&Path> {
+        self.focus.as_deref()
+    }
+
+    /// The selected paths in the order `items` lists them.
+    pub fn ordered(&self, items: &[FileItem]) -> Vec<PathBuf> {
+        items.iter()
+            .filter(|item| self.paths.contains(&item.path))
+            .map(|item| item.path.clone())
+            .collect()
+    }
+
+    /// Picks the item at `path` in `items`, the current listing.
+    pub fn select(&mut self, items: &[FileItem], path: &Path, mode: SelectMode) {
+        match mode {
+            SelectMode::Replace => {
+                self.paths.clear();
+                self.paths.insert(path.to_path_buf());
+                self.anchor = Some(path.to_path_buf());
+            }
+            SelectMode::Toggle => {
+                if !self.paths.remove(path) {
+                    self.paths.insert(path.to_path_buf());
+                }
+                self.anchor = Some(path.to_path_buf());
+            }
+            SelectMode::Range | SelectMode::AddRange => {
+                if mode == SelectMode::Range {
+                    self.paths.clear();
+                }
+                let anchor = self.anchor.get_or_insert_with(|| path.to_path_buf());
+                let start = items.iter().position(|item| &item.path == anchor);
+                let end = items.iter().position(|item| item.path == path);
+                match (start, end) {
+                    (Some(start), Some(end)) => {
+                        let range = start.min(end)..=start.max(end);
+                        self.paths.extend(items[range].iter().map(|item| item.path.clone()));
+                    }
+                    _ => {
+                        self.paths.insert(path.to_path_buf());
+                    }
+                }
+            }
+        }
+        self.focus = Some(path.to_path_buf());
+        self.recount(items);
+    }
+
+    pub fn select_all(&mut self, items: &[FileItem]) {
+        self.paths = items.iter().map(|item| item.path.clone()).collect();
+        self.recount(items);
+    }
+
+    /// Selects exactly the items that weren't selected.
+    pub fn invert(&mut self, items: &[FileItem]) {
+        self.paths = items.iter()
+            .filter(|item| !self.paths.contains(&item.path))
+            .map(|item| item.path.clone())
+            .collect();
+        self.recount(items);
+    }
+
+    /// Deselects everything, keeping the focus where it is.
+    pub fn clear(&mut self) {
+        self.paths.clear();
+        self.bytes = 0;
+    }
+
+    /// Follows an item renamed from `from` to `to`.
+    pub fn rename(&mut self, from: &Path, to: &Path) {
+        if self.paths.remove(from) {
+            self.paths.insert(to.to_path_buf());
+        }
+        for path in [&mut self.anchor, &mut self.focus].into_iter().flatten() {
+            if path == from {
+                *path = to.to_path_buf();
+            }
+        }
+    }
+
+    /// Drops whatever `items` no longer lists, and recounts the size.
+    pub fn retain_listed(&mut self, items: &[FileItem]) {
+        let listed: HashSet<&Path> = items.iter().map(|item| item.path.as_path()).collect();
+        self.paths.retain(|path| listed.contains(path.as_path()));
+        for path in [&mut self.anchor, &mut self.focus] {
+            if path.as_deref().is_some_and(|path| !listed.contains(path)) {
+                *path = None;
+            }
+        }
+        self.recount(items);
+    }
+
+    fn recount(&mut self, items: &[FileItem]) {
+        self.bytes = items.iter()
+            .filter(|item| self.paths.contains(&item.path))
+            .map(|item| item.size)
+            .sum();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vfs::MemoryFs;
+
+    /// Items `/a` to `/e`, each one byte larger than the one before.
+    fn items() -> Vec<FileItem> {
+        let vfs = MemoryFs::new()
+            .with_file("/a", "1")
+            .with_file("/b", "22")
+            .with_file("/c", "333")
+            .with_file("/d", "4444")
+            .with_file("/e", "55555");
+        ["/a", "/b", "/c", "/d", "/e"].iter()
+            .map(|path| FileItem::from_vfs(&vfs, Path::new(path)).unwrap())
+            .collect()
+    }
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn replace_and_toggle() {
+        let items = items();
+        let mut selection = Selection::default();
+        selection.select(&items, Path::new("/b"), SelectMode::Replace);
+        selection.select(&items, Path::new("/d"), SelectMode::Toggle);
+        assert_eq!(selection.ordered(&items), paths(&["/b", "/d"]));
+        assert_eq!(selection.bytes(), 6);
+
+        selection.select(&items, Path::new("/b"), SelectMode::Toggle);
+        assert_eq!(selection.ordered(&items), paths(&["/d"]));
+        assert_eq!(selection.anchor(), Some(Path::new("/b")));
+        assert_eq!(selection.focus(), Some(Path::new("/b")));
+
+        selection.select(&items, Path::new("/a"), SelectMode::Replace);
+        assert_eq!(selection.ordered(&items), paths(&["/a"]));
+        assert_eq!(selection.bytes(), 1);
+    }
+
+    #[test]
+    fn ranges_run_from_the_anchor() {
+        let items = items();
+        let mut selection = Selection::default();
+        selection.select(&items, Path::new("/d"), SelectMode::Replace);
+        selection.select(&items, Path::new("/b"), SelectMode::Range);
+        assert_eq!(selection.ordered(&items), paths(&["/b", "/c", "/d"]));
+
+        // The anchor stays, so the range shrinks or flips around it
+        selection.select(&items, Path::new("/e"), SelectMode::Range);
+        assert_eq!(selection.ordered(&items), paths(&["/d", "/e"]));
+        assert_eq!(selection.anchor(), Some(Path::new("/d")));
+        assert_eq!(selection.focus(), Some(Path::new("/e")));
+    }
+
+    #[test]
+    fn added_ranges_keep_the_rest() {
+        let items = items();
+        let mut selection = Selection::default();
+        selection.select(&items, Path::new("/a"), SelectMode::Replace);
+        selection.select(&items, Path::new("/d"), SelectMode::Toggle);
+        selection.select(&items, Path::new("/e"), SelectMode::AddRange);
+        assert_eq!(selection.ordered(&items), paths(&["/a", "/d", "/e"]));
+        assert_eq!(selection.bytes(), 10);
+    }
+
+    #[test]
+    fn invert_selects_the_others() {
+        let items = items();
+        let mut selection = Selection::default();
+        selection.select(&items, Path::new("/a"), SelectMode::Replace);
+        selection.select(&items, Path::new("/c"), SelectMode::Toggle);
+        selection.invert(&items);
+        assert_eq!(selection.ordered(&items), paths(&["/b", "/d", "/e"]));
+        assert_eq!(selection.bytes(), 11);
+
+        selection.select_all(&items);
+        selection.invert(&items);
+        assert!(selection.is_empty());
+        assert_eq!(selection.bytes(), 0);
+    }
+
+    #[test]
+    fn follows_renames_and_drops_gone_items() {
+        let mut items = items();
+        let mut selection = Selection::default();
+        selection.select(&items, Path::new("/b"), SelectMode::Replace);
+        selection.select(&items, Path::new("/c"), SelectMode::Toggle);
+
+        selection.rename(Path::new("/c"), Path::new("/z"));
+        assert!(selection.contains(Path::new("/z")));
+        assert_eq!(selection.focus(), Some(Path::new("/z")));
+
+        items.retain(|item| item.path != Path::new("/b"));
+        selection.retain_listed(&items);
+        assert!(selection.is_empty());
+        assert_eq!(selection.focus(), None);
+    }
+}
@@ -1,7 +1,11 @@
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+use crate::operations::checksum::HashAlgorithm;
+use crate::operations::copy::{self, CopyErrorMode};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Settings {
     pub show_hidden_files: bool,
     pub default_view_mode: String,
@@ -13,6 +17,11 @@ pub struct Settings {
     pub recent_paths: Vec<PathBuf>,
     pub window_width: f32,
     pub window_height: f32,
+    /// Files copied at the same time by each copy.
+    pub copy_workers: usize,
+    pub copy_error_mode: CopyErrorMode,
+    /// Checksum copies and moves are verified with, if any.
+    pub verify_copies: Option<HashAlgorithm>,
 }
 
 impl Default for Settings {
@@ -28,6 +37,9 @@ impl Default for Settings {
             recent_paths: Vec::new(),
             window_width: 1200.0,
             window_height: 800.0,
+            copy_workers: copy::DEFAULT_WORKERS,
+            copy_error_mode: CopyErrorMode::default(),
+            verify_copies: None,
         }
     }
 }
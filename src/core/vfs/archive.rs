@@ -0,0 +1,793 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::core::file_item::FileType;
+use crate::core::vfs::{Vfs, VfsMetadata, VfsReader, VfsWriter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+}
+
+impl ArchiveKind {
+    /// Detects a browsable archive from its file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "zip" => Some(ArchiveKind::Zip),
+            "tar" => Some(ArchiveKind::Tar),
+            _ => None,
+        }
+    }
+}
+
+/// Symbolic links followed in a row before giving up, as on Linux.
+const MAX_LINK_DEPTH: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    Stored,
+    Deflated,
+}
+
+/// Where the contents of a file lie in the archive file.
+#[derive(Debug, Clone, Copy)]
+struct EntryData {
+    offset: u64,
+    /// Length of the data as stored, compressed or not.
+    stored_len: u64,
+    compression: Compression,
+    /// Checksum of the uncompressed contents, which ZIP archives keep.
+    crc32: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+struct ArchiveEntry {
+    file_type: FileType,
+    size: u64,
+    modified: Option<SystemTime>,
+    /// Where a file's contents are; `None` if they can't be read back.
+    data: Option<EntryData>,
+    /// What a symbolic link points to.
+    link: Option<PathBuf>,
+}
+
+impl ArchiveEntry {
+    fn directory(modified: Option<SystemTime>) -> Self {
+        Self { file_type: FileType::Directory, size: 0, modified, data: None, link: None }
+    }
+
+    fn file(size: u64, modified: Option<SystemTime>, data: Option<EntryData>) -> Self {
+        Self { file_type: FileType::RegularFile, size, modified, data, link: None }
+    }
+
+    fn symlink(target: PathBuf, modified: Option<SystemTime>) -> Self {
+        let size = target.as_os_str().len() as u64;
+        Self { file_type: FileType::SymbolicLink, size, modified, data: None, link: Some(target) }
+    }
+}
+
+/// A read-only view of a single ZIP or TAR archive.
+///
+/// Entries are addressed below the archive's own path, so `/data/logs.zip`
+/// contains `/data/logs.zip/2024/app.log`. The index is read once when the
+/// archive is opened; file contents are streamed from the archive file as
+/// they are read.
+pub struct ArchiveFs {
+    source: Arc<dyn Vfs>,
+    archive_path: PathBuf,
+    kind: ArchiveKind,
+    entries: BTreeMap<PathBuf, ArchiveEntry>,
+}
+
+impl ArchiveFs {
+    /// Opens the archive at `archive_path` on `source` and indexes its entries.
+    pub fn open(source: Arc<dyn Vfs>, archive_path: &Path) -> io::Result<Self> {
+        let kind = ArchiveKind::from_path(archive_path).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Unsupported, format!("Unsupported archive format: {}", archive_path.display()))
+        })?;
+
+        // Entries without their own timestamp inherit the archive's
+        let archive_modified = source.metadata(archive_path)?.modified;
+
+        let mut entries = BTreeMap::new();
+        entries.insert(PathBuf::new(), ArchiveEntry::directory(archive_modified));
+
+        match kind {
+            ArchiveKind::Zip => {
+                let mut archive = zip::ZipArchive::new(source.open_read(archive_path)?)
+                    .map_err(io::Error::other)?;
+
+                for index in 0..archive.len() {
+                    let file = archive.by_index_raw(index).map_err(io::Error::other)?;
+                    let Some(inner_path) = file.enclosed_name().and_then(|p| normalize(&p)) else {
+                        continue;
+                    };
+                    let modified = zip_time(file.last_modified()).or(archive_modified);
+
+                    let entry = if file.is_dir() {
+                        ArchiveEntry::directory(modified)
+                    } else if file.is_symlink() {
+                        // The link's target is stored as its contents
+                        drop(file);
+                        let mut target = String::new();
+                        archive.by_index(index).map_err(io::Error::other)?.read_to_string(&mut target)?;
+                        ArchiveEntry::symlink(PathBuf::from(target), modified)
+                    } else {
+                        let compression = match file.compression() {
+                            zip::CompressionMethod::Stored => Some(Compression::Stored),
+                            zip::CompressionMethod::Deflated => Some(Compression::Deflated),
+                            _ => None,
+                        };
+                        let data = compression.filter(|_| !file.encrypted()).map(|compression| EntryData {
+                            offset: file.data_start(),
+                            stored_len: file.compressed_size(),
+                            compression,
+                            crc32: Some(file.crc32()),
+                        });
+                        ArchiveEntry::file(file.size(), modified, data)
+                    };
+                    insert_entry(&mut entries, inner_path, entry, archive_modified);
+                }
+            }
+            ArchiveKind::Tar => {
+                let mut archive = tar::Archive::new(source.open_read(archive_path)?);
+                let mut hard_links = Vec::new();
+
+                for entry in archive.entries()? {
+                    let entry = entry?;
+                    let Some(inner_path) = normalize(&entry.path()?) else {
+                        continue;
+                    };
+
+                    let header = entry.header();
+                    let modified = header.mtime().ok()
+                        .map(|secs| SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs))
+                        .or(archive_modified);
+                    let entry_type = header.entry_type();
+
+                    let entry = if entry_type.is_dir() {
+                        ArchiveEntry::directory(modified)
+                    } else if entry_type.is_file() {
+                        ArchiveEntry::file(entry.size(), modified, Some(EntryData {
+                            offset: entry.raw_file_position(),
+                            stored_len: entry.size(),
+                            compression: Compression::Stored,
+                            crc32: None,
+                        }))
+                    } else if entry_type.is_symlink() {
+                        let Some(target) = entry.link_name()? else {
+                            continue;
+                        };
+                        ArchiveEntry::symlink(target.into_owned(), modified)
+                    } else if entry_type.is_hard_link() {
+                        // Shares the data of an earlier entry, found once all are known
+                        if let Some(target) = entry.link_name()?.and_then(|target| normalize(&target)) {
+                            hard_links.push((inner_path, target, modified));
+                        }
+                        continue;
+                    } else {
+                        ArchiveEntry {
+                            file_type: FileType::Other,
+                            size: 0,
+                            modified,
+                            data: None,
+                            link: None,
+                        }
+                    };
+                    insert_entry(&mut entries, inner_path, entry, archive_modified);
+                }
+
+                for (inner_path, target, modified) in hard_links {
+                    match entries.get(&target).filter(|entry| entry.file_type == FileType::RegularFile) {
+                        Some(target) => {
+                            let entry = ArchiveEntry { modified, ..target.clone() };
+                            insert_entry(&mut entries, inner_path, entry, archive_modified);
+                        }
+                        None => log::warn!(
+                            "Skipping hard link {} in {}: {} is not a file in the archive",
+                            inner_path.display(),
+                            archive_path.display(),
+                            target.display()
+                        ),
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            source,
+            archive_path: archive_path.to_path_buf(),
+            kind,
+            entries,
+        })
+    }
+
+    pub fn archive_path(&self) -> &Path {
+        &self.archive_path
+    }
+
+    pub fn kind(&self) -> ArchiveKind {
+        self.kind
+    }
+
+    fn entry(&self, path: &Path) -> io::Result<(&PathBuf, &ArchiveEntry)> {
+        let inner_path = path.strip_prefix(&self.archive_path).map_err(|_| not_found(path))?;
+        self.entries.get_key_value(inner_path).ok_or_else(|| not_found(path))
+    }
+
+    /// Looks up `path`, following symbolic links that point within the archive.
+    fn resolved_entry(&self, path: &Path) -> io::Result<(&PathBuf, &ArchiveEntry)> {
+        let (mut inner_path, mut entry) = self.entry(path)?;
+        for _ in 0..MAX_LINK_DEPTH {
+            let Some(ref target) = entry.link else {
+                return Ok((inner_path, entry));
+            };
+            (inner_path, entry) = link_target(inner_path, target)
+                .and_then(|target| self.entries.get_key_value(&target))
+                .ok_or_else(|| not_found(path))?;
+        }
+        Err(io::Error::other(format!("Too many levels of symbolic links: {}", path.display())))
+    }
+}
+
+impl Vfs for ArchiveFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let (inner_path, entry) = self.resolved_entry(path)?;
+        if entry.file_type != FileType::Directory {
+            return Err(io::Error::other(format!("Not a directory: {}", path.display())));
+        }
+
+        // Entries of a linked folder are listed under the link
+        Ok(self.entries.keys()
+            .filter(|p| !p.as_os_str().is_empty() && p.parent() == Some(inner_path.as_path()))
+            .filter_map(|p| p.file_name())
+            .map(|name| path.join(name))
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<VfsMetadata> {
+        let (_, entry) = self.resolved_entry(path)?;
+        Ok(entry_metadata(path, entry))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<VfsMetadata> {
+        let (_, entry) = self.entry(path)?;
+        Ok(entry_metadata(path, entry))
+    }
+
+    fn open_read(&self, path: &Path) -> io::Result<Box<dyn VfsReader>> {
+        let (_, entry) = self.resolved_entry(path)?;
+        match (&entry.file_type, entry.data) {
+            (FileType::RegularFile, Some(data)) => {
+                Ok(Box::new(EntryReader::open(self.source.clone(), &self.archive_path, data, entry.size)?))
+            }
+            (FileType::RegularFile, None) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("Cannot read {}: its compression or encryption is not supported", path.display()),
+            )),
+            _ => Err(io::Error::other(format!("Not a regular file: {}", path.display()))),
+        }
+    }
+
+    fn open_write(&self, path: &Path) -> io::Result<Box<dyn VfsWriter>> {
+        Err(read_only(path))
+    }
+
+    fn rename(&self, from: &Path, _to: &Path) -> io::Result<()> {
+        Err(read_only(from))
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        Err(read_only(path))
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        Err(read_only(path))
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        Err(read_only(path))
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        Err(read_only(path))
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        let (_, entry) = self.entry(path)?;
+        entry.link.clone().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("Not a symbolic link: {}", path.display()))
+        })
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let (inner_path, _) = self.resolved_entry(path)?;
+        Ok(self.archive_path.join(inner_path))
+    }
+}
+
+/// Streams one file from the archive file, decompressing it on the way.
+/// Seeking ahead reads up to the new position, and seeking back in
+/// compressed data starts over from the beginning.
+struct EntryReader {
+    source: Arc<dyn Vfs>,
+    archive_path: PathBuf,
+    data: EntryData,
+    size: u64,
+    inner: Box<dyn Read + Send>,
+    position: u64,
+    /// Checksum of what was read, while the entry is read straight through.
+    crc: Option<crc32fast::Hasher>,
+}
+
+impl EntryReader {
+    fn open(source: Arc<dyn Vfs>, archive_path: &Path, data: EntryData, size: u64) -> io::Result<Self> {
+        let mut reader = Self {
+            source,
+            archive_path: archive_path.to_path_buf(),
+            data,
+            size,
+            inner: Box::new(io::empty()),
+            position: 0,
+            crc: None,
+        };
+        reader.start_at(0)?;
+        Ok(reader)
+    }
+
+    /// Opens the archive file again to read from `position` on.
+    fn start_at(&mut self, position: u64) -> io::Result<()> {
+        // Stored data can be seeked into, compressed data only read through
+        let skip = match self.data.compression {
+            Compression::Stored => position.min(self.data.stored_len),
+            Compression::Deflated => 0,
+        };
+        let mut source = self.source.open_read(&self.archive_path)?;
+        source.seek(SeekFrom::Start(self.data.offset + skip))?;
+        let stored = source.take(self.data.stored_len - skip);
+
+        self.inner = match self.data.compression {
+            Compression::Stored => Box::new(stored),
+            Compression::Deflated => Box::new(flate2::read::DeflateDecoder::new(stored)),
+        };
+        self.position = skip;
+        self.crc = (skip == 0).then(crc32fast::Hasher::new);
+        self.skip(position - skip)
+    }
+
+    fn skip(&mut self, len: u64) -> io::Result<()> {
+        io::copy(&mut self.by_ref().take(len), &mut io::sink())?;
+        Ok(())
+    }
+
+    /// Checks an entry read from start to end against its size and checksum.
+    fn finish(&mut self) -> io::Result<()> {
+        let Some(crc) = self.crc.take() else {
+            return Ok(());
+        };
+        if self.position != self.size {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!(
+                "An entry of {} ends after {} of {} bytes",
+                self.archive_path.display(),
+                self.position,
+                self.size
+            )));
+        }
+        if self.data.crc32.is_some_and(|expected| expected != crc.finalize()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                "Checksum mismatch reading an entry of {}",
+                self.archive_path.display()
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Read for EntryReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if let Some(ref mut crc) = self.crc {
+            crc.update(&buf[..read]);
+        }
+        self.position += read as u64;
+        if read == 0 && !buf.is_empty() {
+            self.finish()?;
+        }
+        Ok(read)
+    }
+}
+
+impl Seek for EntryReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.size.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        }.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Seek before the start of an archive entry"))?;
+
+        if target < self.position || (target > self.position && self.data.compression == Compression::Stored) {
+            self.start_at(target)?;
+        } else {
+            self.skip(target - self.position)?;
+        }
+        self.position = target;
+        Ok(target)
+    }
+}
+
+/// Wraps another filesystem and lets archives on it be browsed like folders.
+///
+/// Paths that pass through a ZIP or TAR file (`/data/logs.zip/2024`) are served
+/// by an [`ArchiveFs`]; everything else goes straight to the wrapped filesystem.
+/// The archive file itself still reports as a file so it lists normally.
+pub struct ArchiveOverlay {
+    base: Arc<dyn Vfs>,
+    /// Opened archives, keyed by path, with the modification time they were indexed at.
+    archives: Mutex<HashMap<PathBuf, MountedArchive>>,
+}
+
+type MountedArchive = (Option<SystemTime>, Arc<ArchiveFs>);
+
+impl ArchiveOverlay {
+    pub fn new(base: Arc<dyn Vfs>) -> Self {
+        Self {
+            base,
+            archives: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Finds the archive `path` lies in (or is), returning it together with
+    /// whether `path` is the archive file itself.
+    fn resolve(&self, path: &Path) -> io::Result<Option<(Arc<ArchiveFs>, bool)>> {
+        // Outermost archive wins; only paths with an archive extension are stat'ed
+        let candidates: Vec<&Path> = path.ancestors()
+            .filter(|p| ArchiveKind::from_path(p).is_some())
+            .collect();
+
+        for archive_path in candidates.into_iter().rev() {
+            let Ok(metadata) = self.base.metadata(archive_path) else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+
+            let archive = self.mount(archive_path, metadata.modified)?;
+            return Ok(Some((archive, archive_path == path)));
+        }
+
+        Ok(None)
+    }
+
+    fn mount(&self, archive_path: &Path, modified: Option<SystemTime>) -> io::Result<Arc<ArchiveFs>> {
+        let mut archives = self.archives.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Re-index the archive if it changed on disk
+        if let Some((indexed_at, archive)) = archives.get(archive_path) {
+            if *indexed_at == modified {
+                return Ok(archive.clone());
+            }
+        }
+
+        let archive = Arc::new(ArchiveFs::open(self.base.clone(), archive_path)?);
+        archives.insert(archive_path.to_path_buf(), (modified, archive.clone()));
+        Ok(archive)
+    }
+
+    /// The archive serving `path` if `path` lies strictly inside one.
+    fn inside_archive(&self, path: &Path) -> io::Result<Option<Arc<ArchiveFs>>> {
+        Ok(match self.resolve(path)? {
+            Some((archive, false)) => Some(archive),
+            _ => None,
+        })
+    }
+}
+
+impl Vfs for ArchiveOverlay {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        match self.resolve(path)? {
+            Some((archive, _)) => archive.read_dir(path),
+            None => self.base.read_dir(path),
+        }
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<VfsMetadata> {
+        match self.inside_archive(path)? {
+            Some(archive) => archive.metadata(path),
+            None => self.base.metadata(path),
+        }
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<VfsMetadata> {
+        match self.inside_archive(path)? {
+            Some(archive) => archive.symlink_metadata(path),
+            None => self.base.symlink_metadata(path),
+        }
+    }
+
+    fn open_read(&self, path: &Path) -> io::Result<Box<dyn VfsReader>> {
+        match self.inside_archive(path)? {
+            Some(archive) => archive.open_read(path),
+            None => self.base.open_read(path),
+        }
+    }
+
+    fn open_write(&self, path: &Path) -> io::Result<Box<dyn VfsWriter>> {
+        match self.inside_archive(path)? {
+            Some(_) => Err(read_only(path)),
+            None => self.base.open_write(path),
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        if self.inside_archive(from)?.is_some() || self.inside_archive(to)?.is_some() {
+            return Err(read_only(from));
+        }
+        self.base.rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        match self.inside_archive(path)? {
+            Some(_) => Err(read_only(path)),
+            None => self.base.remove_file(path),
+        }
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        match self.inside_archive(path)? {
+            Some(_) => Err(read_only(path)),
+            None => self.base.remove_dir_all(path),
+        }
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        match self.inside_archive(path)? {
+            Some(_) => Err(read_only(path)),
+            None => self.base.create_dir(path),
+        }
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        match self.inside_archive(path)? {
+            Some(_) => Err(read_only(path)),
+            None => self.base.create_dir_all(path),
+        }
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        match self.inside_archive(path)? {
+            Some(archive) => archive.read_link(path),
+            None => self.base.read_link(path),
+        }
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        match self.inside_archive(link)? {
+            Some(_) => Err(read_only(link)),
+            None => self.base.symlink(target, link),
+        }
+    }
+
+    fn set_times(&self, path: &Path, accessed: Option<SystemTime>, modified: Option<SystemTime>) -> io::Result<()> {
+        match self.inside_archive(path)? {
+            Some(_) => Err(read_only(path)),
+            None => self.base.set_times(path, accessed, modified),
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        match self.inside_archive(path)? {
+            Some(archive) => archive.canonicalize(path),
+            None => self.base.canonicalize(path),
+        }
+    }
+
+    fn local_path(&self, path: &Path) -> Option<PathBuf> {
+        match self.inside_archive(path) {
+            Ok(None) => self.base.local_path(path),
+            _ => None,
+        }
+    }
+
+    fn can_browse(&self, path: &Path) -> bool {
+        match self.resolve(path) {
+            Ok(Some((_, true))) => true,
+            Ok(Some((archive, false))) => archive.is_dir(path),
+            Ok(None) => self.base.can_browse(path),
+            Err(e) => {
+                log::warn!("Failed to open archive {}: {}", path.display(), e);
+                false
+            }
+        }
+    }
+}
+
+/// Keeps only the normal components of an archive path, rejecting anything
+/// that would climb out of the archive.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!normalized.as_os_str().is_empty()).then_some(normalized)
+}
+
+/// Resolves the target of the link at `inner_path` to the entry it names,
+/// if that lies within the archive.
+fn link_target(inner_path: &Path, target: &Path) -> Option<PathBuf> {
+    let mut resolved = inner_path.parent()?.to_path_buf();
+    for component in target.components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            Component::ParentDir if resolved.pop() => {}
+            _ => return None,
+        }
+    }
+    Some(resolved)
+}
+
+fn entry_metadata(path: &Path, entry: &ArchiveEntry) -> VfsMetadata {
+    VfsMetadata {
+        file_type: entry.file_type.clone(),
+        len: entry.size,
+        modified: entry.modified,
+        created: None,
+        accessed: None,
+        is_readonly: true,
+        is_hidden: path.file_name()
+            .map(|name| name.to_string_lossy().starts_with('.'))
+            .unwrap_or(false),
+    }
+}
+
+/// Adds `entry`, creating directory entries for any parents the archive omits.
+fn insert_entry(
+    entries: &mut BTreeMap<PathBuf, ArchiveEntry>,
+    inner_path: PathBuf,
+    entry: ArchiveEntry,
+    archive_modified: Option<SystemTime>,
+) {
+    for parent in inner_path.ancestors().skip(1) {
+        entries.entry(parent.to_path_buf())
+            .or_insert_with(|| ArchiveEntry::directory(archive_modified));
+    }
+    entries.insert(inner_path, entry);
+}
+
+fn zip_time(time: Option<zip::DateTime>) -> Option<SystemTime> {
+    let time = time?;
+    let local = chrono::NaiveDate::from_ymd_opt(time.year() as i32, time.month() as u32, time.day() as u32)?
+        .and_hms_opt(time.hour() as u32, time.minute() as u32, time.second() as u32)?
+        .and_local_timezone(chrono::Local)
+        .earliest()?;
+    Some(local.into())
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("No such file or directory: {}", path.display()))
+}
+
+fn read_only(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, format!("Archives are read-only: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vfs::{self, MemoryFs};
+    use std::io::Write;
+
+    fn tar_archive() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        builder.append_data(&mut header, "dir/a.txt", &b"hello"[..]).unwrap();
+
+        for (entry_type, path, target) in [
+            (tar::EntryType::Symlink, "dir/link", "a.txt"),
+            (tar::EntryType::Symlink, "up", "dir"),
+            (tar::EntryType::Link, "dir/hard", "dir/a.txt"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(entry_type);
+            header.set_size(0);
+            builder.append_link(&mut header, path, target).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn zip_archive(content: &[u8]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        for (name, method) in [("stored.bin", zip::CompressionMethod::Stored), ("deflated.bin", zip::CompressionMethod::Deflated)] {
+            let options = zip::write::SimpleFileOptions::default().compression_method(method);
+            writer.start_file(name, options).unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn overlay(name: &str, archive: Vec<u8>) -> ArchiveOverlay {
+        ArchiveOverlay::new(Arc::new(MemoryFs::new().with_file(name, archive)))
+    }
+
+    #[test]
+    fn reads_tar_links() {
+        let vfs = overlay("/a.tar", tar_archive());
+
+        assert_eq!(vfs.read_link(Path::new("/a.tar/dir/link")).unwrap(), PathBuf::from("a.txt"));
+        assert_eq!(vfs.symlink_metadata(Path::new("/a.tar/dir/link")).unwrap().file_type, FileType::SymbolicLink);
+        assert!(vfs.metadata(Path::new("/a.tar/dir/link")).unwrap().is_file());
+        assert_eq!(vfs::read(&vfs, Path::new("/a.tar/dir/link")).unwrap(), b"hello");
+        assert_eq!(vfs.read_dir(Path::new("/a.tar/up")).unwrap().len(), 3);
+
+        // Hard links are plain files with their target's contents
+        let hard = vfs.symlink_metadata(Path::new("/a.tar/dir/hard")).unwrap();
+        assert!(hard.is_file() && hard.len == 5);
+        assert_eq!(vfs::read(&vfs, Path::new("/a.tar/dir/hard")).unwrap(), b"hello");
+        assert!(vfs.read_link(Path::new("/a.tar/dir/hard")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn copies_folders_with_links_out_of_archives() {
+        use crate::core::vfs::LocalFs;
+        use crate::operations::copy::CopyOperation;
+
+        let dir = tempfile::tempdir().unwrap();
+        CopyOperation::new(vec![PathBuf::from("/a.tar/dir")], dir.path().to_path_buf())
+            .with_source_vfs(Arc::new(overlay("/a.tar", tar_archive())))
+            .with_destination_vfs(Arc::new(LocalFs))
+            .execute()
+            .await
+            .unwrap();
+
+        let copy = dir.path().join("dir");
+        assert_eq!(std::fs::read_link(copy.join("link")).unwrap(), PathBuf::from("a.txt"));
+        assert_eq!(std::fs::read(copy.join("hard")).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn streams_and_seeks_zip_entries() {
+        let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let vfs = overlay("/a.zip", zip_archive(&content));
+
+        for name in ["/a.zip/stored.bin", "/a.zip/deflated.bin"] {
+            assert_eq!(vfs::read(&vfs, Path::new(name)).unwrap(), content);
+
+            let mut reader = vfs.open_read(Path::new(name)).unwrap();
+            let mut buf = [0; 4];
+            reader.seek(SeekFrom::Start(150_000)).unwrap();
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, content[150_000..150_004]);
+            reader.seek(SeekFrom::Current(-100_004)).unwrap();
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, content[50_000..50_004]);
+        }
+    }
+
+    #[test]
+    fn refuses_corrupt_zip_entries() {
+        let content = vec![7u8; 1000];
+        let mut archive = zip_archive(&content);
+        // The stored entry comes first, its data after the local header's
+        // 30 fixed bytes, name and extra field
+        let extra_len = u16::from_le_bytes([archive[28], archive[29]]) as usize;
+        archive[30 + "stored.bin".len() + extra_len + 10] ^= 0xff;
+        let vfs = overlay("/a.zip", archive);
+
+        let error = vfs::read(&vfs, Path::new("/a.zip/stored.bin")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(vfs::read(&vfs, Path::new("/a.zip/deflated.bin")).unwrap(), content);
+    }
+}
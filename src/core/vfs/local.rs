@@ -0,0 +1,122 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use filetime::FileTime;
+
+use crate::core::file_item::FileType;
+use crate::core::vfs::{Vfs, VfsMetadata, VfsReader, VfsWriter};
+use crate::utils::file_utils::is_hidden_file;
+
+/// The host filesystem, backed directly by `std::fs`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalFs;
+
+impl LocalFs {
+    fn convert(path: &Path, metadata: fs::Metadata) -> VfsMetadata {
+        let file_type = if metadata.is_dir() {
+            FileType::Directory
+        } else if metadata.is_file() {
+            FileType::RegularFile
+        } else if metadata.file_type().is_symlink() {
+            FileType::SymbolicLink
+        } else {
+            FileType::Other
+        };
+
+        VfsMetadata {
+            file_type,
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            created: metadata.created().ok(),
+            accessed: metadata.accessed().ok(),
+            is_readonly: metadata.permissions().readonly(),
+            is_hidden: is_hidden_file(path),
+        }
+    }
+}
+
+impl Vfs for LocalFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect()
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<VfsMetadata> {
+        Ok(Self::convert(path, fs::metadata(path)?))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<VfsMetadata> {
+        Ok(Self::convert(path, fs::symlink_metadata(path)?))
+    }
+
+    fn open_read(&self, path: &Path) -> io::Result<Box<dyn VfsReader>> {
+        Ok(Box::new(fs::File::open(path)?))
+    }
+
+    fn open_write(&self, path: &Path) -> io::Result<Box<dyn VfsWriter>> {
+        Ok(Box::new(fs::File::create(path)?))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir_all(path)
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir(path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(target, link)
+        }
+
+        #[cfg(windows)]
+        {
+            // Windows needs to know up front whether the link is to a folder
+            let resolved = link.parent().unwrap_or(Path::new(".")).join(target);
+            if resolved.is_dir() {
+                std::os::windows::fs::symlink_dir(target, link)
+            } else {
+                std::os::windows::fs::symlink_file(target, link)
+            }
+        }
+    }
+
+    fn set_times(&self, path: &Path, accessed: Option<SystemTime>, modified: Option<SystemTime>) -> io::Result<()> {
+        let current = fs::symlink_metadata(path)?;
+        let accessed = accessed.map(FileTime::from)
+            .unwrap_or_else(|| FileTime::from_last_access_time(&current));
+        let modified = modified.map(FileTime::from)
+            .unwrap_or_else(|| FileTime::from_last_modification_time(&current));
+
+        // Works on the path without opening it, which would block on a FIFO
+        filetime::set_symlink_file_times(path, accessed, modified)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+
+    fn local_path(&self, path: &Path) -> Option<PathBuf> {
+        Some(path.to_path_buf())
+    }
+}
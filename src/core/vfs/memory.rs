@@ -0,0 +1,364 @@
+use std::collections::BTreeMap;
+use std::io::{self, Cursor, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use crate::core::file_item::FileType;
+use crate::core::vfs::{Vfs, VfsMetadata, VfsReader, VfsWriter};
+
+#[derive(Debug, Clone)]
+enum NodeKind {
+    Directory,
+    File(Arc<Vec<u8>>),
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    kind: NodeKind,
+    created: SystemTime,
+    modified: SystemTime,
+}
+
+impl Node {
+    fn directory() -> Self {
+        let now = SystemTime::now();
+        Self { kind: NodeKind::Directory, created: now, modified: now }
+    }
+
+    fn file(content: Vec<u8>) -> Self {
+        let now = SystemTime::now();
+        Self { kind: NodeKind::File(Arc::new(content)), created: now, modified: now }
+    }
+}
+
+type Nodes = Arc<RwLock<BTreeMap<PathBuf, Node>>>;
+
+/// A filesystem held entirely in memory, rooted at `/`.
+///
+/// Useful as a test fixture and as a scratch area that never touches disk.
+/// Clones share the same tree.
+#[derive(Debug, Clone)]
+pub struct MemoryFs {
+    nodes: Nodes,
+}
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(PathBuf::from("/"), Node::directory());
+        Self {
+            nodes: Arc::new(RwLock::new(nodes)),
+        }
+    }
+
+    /// Adds a file, creating any missing parent directories.
+    pub fn with_file(self, path: impl AsRef<Path>, content: impl Into<Vec<u8>>) -> Self {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            let _ = self.create_dir_all(parent);
+        }
+        self.write_nodes().insert(path.to_path_buf(), Node::file(content.into()));
+        self
+    }
+
+    /// Adds a directory, creating any missing parents.
+    pub fn with_dir(self, path: impl AsRef<Path>) -> Self {
+        let _ = self.create_dir_all(path.as_ref());
+        self
+    }
+
+    fn read_nodes(&self) -> std::sync::RwLockReadGuard<'_, BTreeMap<PathBuf, Node>> {
+        self.nodes.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write_nodes(&self) -> std::sync::RwLockWriteGuard<'_, BTreeMap<PathBuf, Node>> {
+        self.nodes.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn not_found(path: &Path) -> io::Error {
+        io::Error::new(io::ErrorKind::NotFound, format!("No such file or directory: {}", path.display()))
+    }
+
+    fn already_exists(path: &Path) -> io::Error {
+        io::Error::new(io::ErrorKind::AlreadyExists, format!("File exists: {}", path.display()))
+    }
+
+    fn not_a_directory(path: &Path) -> io::Error {
+        io::Error::other(format!("Not a directory: {}", path.display()))
+    }
+
+    fn is_a_directory(path: &Path) -> io::Error {
+        io::Error::other(format!("Is a directory: {}", path.display()))
+    }
+
+    fn require_parent_dir(nodes: &BTreeMap<PathBuf, Node>, path: &Path) -> io::Result<()> {
+        let parent = path.parent().ok_or_else(|| Self::already_exists(path))?;
+        match nodes.get(parent) {
+            Some(Node { kind: NodeKind::Directory, .. }) => Ok(()),
+            Some(_) => Err(Self::not_a_directory(parent)),
+            None => Err(Self::not_found(parent)),
+        }
+    }
+}
+
+impl Default for MemoryFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vfs for MemoryFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let nodes = self.read_nodes();
+        match nodes.get(path) {
+            Some(Node { kind: NodeKind::Directory, .. }) => {}
+            Some(_) => return Err(Self::not_a_directory(path)),
+            None => return Err(Self::not_found(path)),
+        }
+
+        Ok(nodes.keys()
+            .filter(|p| p.parent() == Some(path))
+            .cloned()
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<VfsMetadata> {
+        let nodes = self.read_nodes();
+        let node = nodes.get(path).ok_or_else(|| Self::not_found(path))?;
+
+        let (file_type, len) = match node.kind {
+            NodeKind::Directory => (FileType::Directory, 0),
+            NodeKind::File(ref content) => (FileType::RegularFile, content.len() as u64),
+        };
+
+        Ok(VfsMetadata {
+            file_type,
+            len,
+            modified: Some(node.modified),
+            created: Some(node.created),
+            accessed: Some(node.modified),
+            is_readonly: false,
+            is_hidden: path.file_name()
+                .map(|name| name.to_string_lossy().starts_with('.'))
+                .unwrap_or(false),
+        })
+    }
+
+    fn open_read(&self, path: &Path) -> io::Result<Box<dyn VfsReader>> {
+        let nodes = self.read_nodes();
+        match nodes.get(path) {
+            Some(Node { kind: NodeKind::File(content), .. }) => {
+                Ok(Box::new(Cursor::new(content.as_ref().clone())))
+            }
+            Some(_) => Err(Self::is_a_directory(path)),
+            None => Err(Self::not_found(path)),
+        }
+    }
+
+    fn open_write(&self, path: &Path) -> io::Result<Box<dyn VfsWriter>> {
+        {
+            let mut nodes = self.write_nodes();
+            Self::require_parent_dir(&nodes, path)?;
+            if let Some(Node { kind: NodeKind::Directory, .. }) = nodes.get(path) {
+                return Err(Self::is_a_directory(path));
+            }
+            nodes.insert(path.to_path_buf(), Node::file(Vec::new()));
+        }
+
+        Ok(Box::new(MemoryWriter {
+            nodes: self.nodes.clone(),
+            path: path.to_path_buf(),
+            buffer: Cursor::new(Vec::new()),
+        }))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut nodes = self.write_nodes();
+        if !nodes.contains_key(from) {
+            return Err(Self::not_found(from));
+        }
+        Self::require_parent_dir(&nodes, to)?;
+        if to.starts_with(from) && to != from {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Cannot move a directory into itself"));
+        }
+
+        let moved: Vec<PathBuf> = nodes.keys()
+            .filter(|p| p.starts_with(from))
+            .cloned()
+            .collect();
+
+        for old_path in moved {
+            if let Some(node) = nodes.remove(&old_path) {
+                let suffix = old_path.strip_prefix(from).unwrap_or(Path::new(""));
+                let new_path = if suffix.as_os_str().is_empty() {
+                    to.to_path_buf()
+                } else {
+                    to.join(suffix)
+                };
+                nodes.insert(new_path, node);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let mut nodes = self.write_nodes();
+        match nodes.get(path) {
+            Some(Node { kind: NodeKind::File(_), .. }) => {
+                nodes.remove(path);
+                Ok(())
+            }
+            Some(_) => Err(Self::is_a_directory(path)),
+            None => Err(Self::not_found(path)),
+        }
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        let mut nodes = self.write_nodes();
+        if !nodes.contains_key(path) {
+            return Err(Self::not_found(path));
+        }
+        nodes.retain(|p, _| !p.starts_with(path));
+        Ok(())
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        let mut nodes = self.write_nodes();
+        if nodes.contains_key(path) {
+            return Err(Self::already_exists(path));
+        }
+        Self::require_parent_dir(&nodes, path)?;
+        nodes.insert(path.to_path_buf(), Node::directory());
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let mut nodes = self.write_nodes();
+        for ancestor in path.ancestors().collect::<Vec<_>>().into_iter().rev() {
+            match nodes.get(ancestor) {
+                Some(Node { kind: NodeKind::Directory, .. }) => {}
+                Some(_) => return Err(Self::already_exists(ancestor)),
+                None => {
+                    nodes.insert(ancestor.to_path_buf(), Node::directory());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Buffers writes and publishes them to the tree on flush and drop.
+struct MemoryWriter {
+    nodes: Nodes,
+    path: PathBuf,
+    buffer: Cursor<Vec<u8>>,
+}
+
+impl MemoryWriter {
+    fn commit(&self) {
+        let mut nodes = self.nodes.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(node) = nodes.get_mut(&self.path) {
+            node.kind = NodeKind::File(Arc::new(self.buffer.get_ref().clone()));
+            node.modified = SystemTime::now();
+        }
+    }
+}
+
+impl Write for MemoryWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.commit();
+        Ok(())
+    }
+}
+
+impl Seek for MemoryWriter {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.buffer.seek(pos)
+    }
+}
+
+impl Drop for MemoryWriter {
+    fn drop(&mut self) {
+        self.commit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vfs;
+
+    fn listed(vfs: &MemoryFs, path: &str) -> Vec<PathBuf> {
+        vfs.read_dir(Path::new(path)).unwrap()
+    }
+
+    #[test]
+    fn reads_back_what_was_written() {
+        let vfs = MemoryFs::new().with_dir("/docs");
+        vfs::write(&vfs, Path::new("/docs/a.txt"), b"hello").unwrap();
+
+        assert_eq!(vfs::read(&vfs, Path::new("/docs/a.txt")).unwrap(), b"hello");
+        let metadata = vfs.metadata(Path::new("/docs/a.txt")).unwrap();
+        assert_eq!((metadata.file_type, metadata.len), (FileType::RegularFile, 5));
+        assert!(vfs.is_dir(Path::new("/docs")));
+        assert!(vfs.metadata(Path::new("/docs/missing")).is_err());
+        assert!(MemoryFs::new().with_file("/.hidden", "").metadata(Path::new("/.hidden")).unwrap().is_hidden);
+    }
+
+    #[test]
+    fn lists_only_direct_children() {
+        let vfs = MemoryFs::new()
+            .with_file("/a/b/c.txt", "c")
+            .with_file("/a/d.txt", "d")
+            .with_file("/ab.txt", "ab");
+
+        assert_eq!(listed(&vfs, "/"), [PathBuf::from("/a"), PathBuf::from("/ab.txt")]);
+        assert_eq!(listed(&vfs, "/a"), [PathBuf::from("/a/b"), PathBuf::from("/a/d.txt")]);
+        assert!(vfs.read_dir(Path::new("/a/d.txt")).is_err());
+        assert_eq!(vfs::walk(&vfs, Path::new("/a")).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn renames_move_whole_folders() {
+        let vfs = MemoryFs::new()
+            .with_file("/a/b/c.txt", "c")
+            .with_file("/ab/keep.txt", "keep")
+            .with_dir("/dst");
+        vfs.rename(Path::new("/a"), Path::new("/dst/a")).unwrap();
+
+        assert_eq!(vfs::read(&vfs, Path::new("/dst/a/b/c.txt")).unwrap(), b"c");
+        assert!(!vfs.exists(Path::new("/a")));
+        assert!(vfs.exists(Path::new("/ab/keep.txt")));
+        assert!(vfs.rename(Path::new("/dst"), Path::new("/dst/inside")).is_err());
+        assert!(vfs.rename(Path::new("/missing"), Path::new("/dst/missing")).is_err());
+    }
+
+    #[test]
+    fn creating_and_removing() {
+        let vfs = MemoryFs::new().with_file("/file", "x");
+        assert_eq!(vfs.create_dir(Path::new("/file")).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(vfs.create_dir(Path::new("/a/b")).unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert!(vfs.create_dir_all(Path::new("/file/b")).is_err());
+        assert!(vfs.open_write(Path::new("/missing/new.txt")).is_err());
+
+        vfs.create_dir_all(Path::new("/a/b/c")).unwrap();
+        assert!(vfs.remove_file(Path::new("/a")).is_err());
+        vfs.remove_dir_all(Path::new("/a")).unwrap();
+        vfs.remove_file(Path::new("/file")).unwrap();
+        assert!(listed(&vfs, "/").is_empty());
+    }
+
+    #[test]
+    fn clones_share_the_tree() {
+        let vfs = MemoryFs::new();
+        let clone = vfs.clone();
+        vfs::write(&clone, Path::new("/shared.txt"), b"shared").unwrap();
+        assert_eq!(vfs::read(&vfs, Path::new("/shared.txt")).unwrap(), b"shared");
+    }
+}
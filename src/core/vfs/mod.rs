@@ -0,0 +1,165 @@
+//! Virtual filesystem layer.
+//!
+//! `FileManager` and the `operations` talk to a [`Vfs`] instead of calling
+//! `std::fs` directly, so the same browsing and file operations work on the
+//! local disk, in-memory fixtures, SFTP servers and any other backend
+//! implementing the trait.
+
+pub mod archive;
+pub mod local;
+pub mod memory;
+pub mod sftp;
+
+pub use archive::{ArchiveFs, ArchiveKind, ArchiveOverlay};
+pub use local::LocalFs;
+pub use memory::MemoryFs;
+pub use sftp::{SftpFs, SftpLocation, SftpOverlay, SshConfig};
+
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::core::file_item::FileType;
+
+/// Metadata for a single entry, as reported by a [`Vfs`] backend.
+#[derive(Debug, Clone)]
+pub struct VfsMetadata {
+    pub file_type: FileType,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    pub is_readonly: bool,
+    pub is_hidden: bool,
+}
+
+impl VfsMetadata {
+    pub fn is_dir(&self) -> bool {
+        self.file_type == FileType::Directory
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.file_type == FileType::RegularFile
+    }
+}
+
+/// A readable, seekable handle returned by [`Vfs::open_read`].
+pub trait VfsReader: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> VfsReader for T {}
+
+/// A writable handle returned by [`Vfs::open_write`].
+pub trait VfsWriter: Write + Seek + Send {}
+
+impl<T: Write + Seek + Send> VfsWriter for T {}
+
+pub trait Vfs: Send + Sync {
+    /// Lists the paths of all entries directly inside `path`.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Returns metadata for `path`, following symbolic links.
+    fn metadata(&self, path: &Path) -> io::Result<VfsMetadata>;
+
+    /// Returns metadata for `path` itself, without following a symbolic link.
+    fn symlink_metadata(&self, path: &Path) -> io::Result<VfsMetadata> {
+        self.metadata(path)
+    }
+
+    fn open_read(&self, path: &Path) -> io::Result<Box<dyn VfsReader>>;
+
+    /// Creates or truncates the file at `path` and opens it for writing.
+    fn open_write(&self, path: &Path) -> io::Result<Box<dyn VfsWriter>>;
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    /// Returns what the symbolic link at `path` points to.
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        Err(unsupported("Symbolic links", path))
+    }
+
+    /// Creates a symbolic link at `link` pointing to `target`.
+    fn symlink(&self, _target: &Path, link: &Path) -> io::Result<()> {
+        Err(unsupported("Symbolic links", link))
+    }
+
+    /// Sets the access and modification times of `path`; `None` leaves that
+    /// time as it is.
+    fn set_times(&self, path: &Path, _accessed: Option<SystemTime>, _modified: Option<SystemTime>) -> io::Result<()> {
+        Err(unsupported("Setting file times", path))
+    }
+
+    /// Resolves symbolic links and `.`/`..` in `path`. Backends without links
+    /// return the path as it is.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(path.to_path_buf())
+    }
+
+    /// Maps `path` to a real path on the local disk, if this backend has one.
+    /// Used for things only the host OS can do: watching, trash, opening files.
+    fn local_path(&self, _path: &Path) -> Option<PathBuf> {
+        None
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.metadata(path).map(|m| m.is_dir()).unwrap_or(false)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.metadata(path).map(|m| m.is_file()).unwrap_or(false)
+    }
+
+    /// Whether `path` can be opened like a folder. Backends that expose
+    /// containers such as archives return `true` for those too.
+    fn can_browse(&self, path: &Path) -> bool {
+        self.is_dir(path)
+    }
+}
+
+fn unsupported(what: &str, path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, format!("{} are not supported for {}", what, path.display()))
+}
+
+/// Recursively lists every entry below `root` (not including `root` itself),
+/// parents before their children. Symbolic links are reported but not followed.
+pub fn walk<V: Vfs + ?Sized>(vfs: &V, root: &Path) -> io::Result<Vec<(PathBuf, VfsMetadata)>> {
+    let mut entries = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        for path in vfs.read_dir(&dir)? {
+            let metadata = vfs.symlink_metadata(&path)?;
+            if metadata.is_dir() {
+                pending.push(path.clone());
+            }
+            entries.push((path, metadata));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Reads the whole file at `path` into memory.
+pub fn read<V: Vfs + ?Sized>(vfs: &V, path: &Path) -> io::Result<Vec<u8>> {
+    let mut content = Vec::new();
+    vfs.open_read(path)?.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+/// Creates or replaces the file at `path` with `content`.
+pub fn write<V: Vfs + ?Sized>(vfs: &V, path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut writer = vfs.open_write(path)?;
+    writer.write_all(content)?;
+    writer.flush()
+}
@@ -0,0 +1,718 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use ssh2::{CheckResult, HashType, KnownHostFileKind, Session, Sftp};
+
+use crate::core::file_item::FileType;
+use crate::core::vfs::{Vfs, VfsMetadata, VfsReader, VfsWriter};
+
+const SCHEME: &str = "sftp://";
+const DEFAULT_PORT: u16 = 22;
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+/// The `user@host:port` part of an `sftp://` location.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SftpLocation {
+    pub user: String,
+    pub host: String,
+    pub port: u16,
+}
+
+impl SftpLocation {
+    /// Splits `sftp://user@host:port/remote/path` into the server and the
+    /// absolute path on it. The user defaults to the local user name and the
+    /// port to 22.
+    pub fn parse(path: &Path) -> Option<(Self, PathBuf)> {
+        let rest = path.to_str()?.strip_prefix(SCHEME)?;
+        let (authority, remote_path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, "/"),
+        };
+
+        let (user, host_port) = match authority.rsplit_once('@') {
+            Some((user, host_port)) => (user.to_string(), host_port),
+            None => (local_user_name()?, authority),
+        };
+
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().ok()?),
+            None => (host_port, DEFAULT_PORT),
+        };
+
+        if user.is_empty() || host.is_empty() {
+            return None;
+        }
+
+        let location = Self { user, host: host.to_string(), port };
+        Some((location, PathBuf::from(remote_path)))
+    }
+
+    /// The explorer path for `remote_path` on this server.
+    pub fn to_path(&self, remote_path: &Path) -> PathBuf {
+        PathBuf::from(format!("{}{}", self, remote_path.display()))
+    }
+}
+
+impl fmt::Display for SftpLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}@{}", SCHEME, self.user, self.host)?;
+        if self.port != DEFAULT_PORT {
+            write!(f, ":{}", self.port)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `path` names a location on an SFTP server.
+pub fn is_sftp_path(path: &Path) -> bool {
+    path.to_str().is_some_and(|p| p.starts_with(SCHEME))
+}
+
+/// Where a connection looks for the host keys it trusts and the keys it
+/// logs in with.
+#[derive(Debug, Clone)]
+pub struct SshConfig {
+    pub known_hosts: PathBuf,
+    /// Private keys to try in turn, each with its `.pub` file if there is one.
+    pub identities: Vec<PathBuf>,
+    /// Whether to try the running SSH agent before the keys.
+    pub use_agent: bool,
+}
+
+impl SshConfig {
+    /// The agent, then `~/.ssh/known_hosts` and the default keys in `~/.ssh`.
+    pub fn from_ssh_dir() -> io::Result<Self> {
+        let ssh_dir = ssh_dir()?;
+        Ok(Self {
+            known_hosts: ssh_dir.join("known_hosts"),
+            identities: ["id_ed25519", "id_ecdsa", "id_rsa"].iter().map(|name| ssh_dir.join(name)).collect(),
+            use_agent: true,
+        })
+    }
+}
+
+/// A single SFTP connection, addressed with `sftp://user@host/path` paths.
+///
+/// Authentication uses the running SSH agent, then the default keys in
+/// `~/.ssh`. Host keys are checked against `~/.ssh/known_hosts`; hosts not
+/// in it and changed keys are refused, so a new server has to be trusted
+/// first, e.g. by connecting to it once with `ssh`.
+pub struct SftpFs {
+    location: SftpLocation,
+    // Keeps the connection alive for as long as the SFTP channel is in use
+    _session: Session,
+    sftp: Sftp,
+}
+
+impl SftpFs {
+    pub fn connect(location: SftpLocation) -> io::Result<Self> {
+        Self::connect_with(location, &SshConfig::from_ssh_dir()?)
+    }
+
+    /// Connects with the host keys and login keys in `config`.
+    pub fn connect_with(location: SftpLocation, config: &SshConfig) -> io::Result<Self> {
+        let stream = TcpStream::connect((location.host.as_str(), location.port))?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        let mut session = Session::new()?;
+        session.set_tcp_stream(stream);
+        session.set_timeout(TIMEOUT.as_millis() as u32);
+        session.handshake()?;
+
+        verify_host_key(&session, &location, &config.known_hosts)?;
+        authenticate(&session, &location, config)?;
+
+        let sftp = session.sftp()?;
+        log::info!("Connected to {}", location);
+
+        Ok(Self {
+            location,
+            _session: session,
+            sftp,
+        })
+    }
+
+    pub fn location(&self) -> &SftpLocation {
+        &self.location
+    }
+
+    fn remote_path(&self, path: &Path) -> io::Result<PathBuf> {
+        match SftpLocation::parse(path) {
+            Some((location, remote_path)) if location == self.location => Ok(remote_path),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not on {}", path.display(), self.location),
+            )),
+        }
+    }
+
+    fn convert(path: &Path, stat: ssh2::FileStat) -> VfsMetadata {
+        let file_type = stat.file_type();
+        let file_type = if file_type.is_dir() {
+            FileType::Directory
+        } else if file_type.is_file() {
+            FileType::RegularFile
+        } else if file_type.is_symlink() {
+            FileType::SymbolicLink
+        } else {
+            FileType::Other
+        };
+
+        let time = |secs: Option<u64>| secs.map(|s| SystemTime::UNIX_EPOCH + Duration::from_secs(s));
+
+        VfsMetadata {
+            file_type,
+            len: stat.size.unwrap_or(0),
+            modified: time(stat.mtime),
+            created: None,
+            accessed: time(stat.atime),
+            is_readonly: stat.perm.is_some_and(|perm| perm & 0o222 == 0),
+            is_hidden: path.file_name()
+                .map(|name| name.to_string_lossy().starts_with('.'))
+                .unwrap_or(false),
+        }
+    }
+
+    fn remove_remote_dir_all(&self, remote_path: &Path) -> io::Result<()> {
+        for (entry, stat) in self.sftp.readdir(remote_path)? {
+            let is_dir = self.sftp.lstat(&entry).map(|s| s.is_dir()).unwrap_or(stat.is_dir());
+            if is_dir {
+                self.remove_remote_dir_all(&entry)?;
+            } else {
+                self.sftp.unlink(&entry)?;
+            }
+        }
+        Ok(self.sftp.rmdir(remote_path)?)
+    }
+}
+
+impl Vfs for SftpFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let remote_path = self.remote_path(path)?;
+        Ok(self.sftp.readdir(&remote_path)?
+            .into_iter()
+            .map(|(entry, _)| self.location.to_path(&entry))
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<VfsMetadata> {
+        let stat = self.sftp.stat(&self.remote_path(path)?)?;
+        Ok(Self::convert(path, stat))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<VfsMetadata> {
+        let stat = self.sftp.lstat(&self.remote_path(path)?)?;
+        Ok(Self::convert(path, stat))
+    }
+
+    fn open_read(&self, path: &Path) -> io::Result<Box<dyn VfsReader>> {
+        Ok(Box::new(self.sftp.open(self.remote_path(path)?)?))
+    }
+
+    fn open_write(&self, path: &Path) -> io::Result<Box<dyn VfsWriter>> {
+        Ok(Box::new(self.sftp.create(&self.remote_path(path)?)?))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        Ok(self.sftp.rename(&self.remote_path(from)?, &self.remote_path(to)?, None)?)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        Ok(self.sftp.unlink(&self.remote_path(path)?)?)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        self.remove_remote_dir_all(&self.remote_path(path)?)
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        Ok(self.sftp.mkdir(&self.remote_path(path)?, 0o755)?)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let remote_path = self.remote_path(path)?;
+        let missing: Vec<&Path> = remote_path.ancestors()
+            .take_while(|p| self.sftp.stat(p).is_err())
+            .collect();
+
+        for dir in missing.into_iter().rev() {
+            self.sftp.mkdir(dir, 0o755)?;
+        }
+        Ok(())
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(self.sftp.readlink(&self.remote_path(path)?)?)
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        Ok(self.sftp.symlink(target, &self.remote_path(link)?)?)
+    }
+
+    fn set_times(&self, path: &Path, accessed: Option<SystemTime>, modified: Option<SystemTime>) -> io::Result<()> {
+        let remote_path = self.remote_path(path)?;
+        let secs = |time: SystemTime| time.duration_since(SystemTime::UNIX_EPOCH).ok().map(|d| d.as_secs());
+
+        // SFTP sets both times at once, so keep whichever isn't being changed
+        let current = self.sftp.stat(&remote_path)?;
+        let stat = ssh2::FileStat {
+            size: None,
+            uid: None,
+            gid: None,
+            perm: None,
+            atime: accessed.and_then(secs).or(current.atime),
+            mtime: modified.and_then(secs).or(current.mtime),
+        };
+        Ok(self.sftp.setstat(&remote_path, stat)?)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let resolved = self.sftp.realpath(&self.remote_path(path)?)?;
+        Ok(self.location.to_path(&resolved))
+    }
+}
+
+/// Wraps another filesystem and serves `sftp://` paths from SFTP servers.
+///
+/// A connection is opened the first time a server is used and then shared by
+/// every later call; everything else goes straight to the wrapped filesystem.
+/// While a server is being connected to, only calls for that server wait.
+pub struct SftpOverlay {
+    base: Arc<dyn Vfs>,
+    connections: Mutex<HashMap<SftpLocation, Arc<ConnectionSlot>>>,
+}
+
+/// The connection to one server, once it has been opened.
+type ConnectionSlot = Mutex<Option<Arc<SftpFs>>>;
+
+impl SftpOverlay {
+    pub fn new(base: Arc<dyn Vfs>) -> Self {
+        Self {
+            base,
+            connections: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The connection serving `path`, or `None` for non-SFTP paths.
+    fn resolve(&self, path: &Path) -> io::Result<Option<Arc<SftpFs>>> {
+        if !is_sftp_path(path) {
+            return Ok(None);
+        }
+
+        let (location, _) = SftpLocation::parse(path).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid SFTP location: {}", path.display()))
+        })?;
+
+        let slot = self.connections.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(location.clone())
+            .or_default()
+            .clone();
+
+        let mut connection = slot.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(connection) = connection.as_ref() {
+            return Ok(Some(connection.clone()));
+        }
+
+        let connected = Arc::new(SftpFs::connect(location)?);
+        *connection = Some(connected.clone());
+        Ok(Some(connected))
+    }
+
+    /// Drops the cached connection to `location`; the next access reconnects.
+    pub fn disconnect(&self, location: &SftpLocation) {
+        self.connections.lock().unwrap_or_else(|e| e.into_inner()).remove(location);
+    }
+}
+
+impl Vfs for SftpOverlay {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        match self.resolve(path)? {
+            Some(sftp) => sftp.read_dir(path),
+            None => self.base.read_dir(path),
+        }
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<VfsMetadata> {
+        match self.resolve(path)? {
+            Some(sftp) => sftp.metadata(path),
+            None => self.base.metadata(path),
+        }
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<VfsMetadata> {
+        match self.resolve(path)? {
+            Some(sftp) => sftp.symlink_metadata(path),
+            None => self.base.symlink_metadata(path),
+        }
+    }
+
+    fn open_read(&self, path: &Path) -> io::Result<Box<dyn VfsReader>> {
+        match self.resolve(path)? {
+            Some(sftp) => sftp.open_read(path),
+            None => self.base.open_read(path),
+        }
+    }
+
+    fn open_write(&self, path: &Path) -> io::Result<Box<dyn VfsWriter>> {
+        match self.resolve(path)? {
+            Some(sftp) => sftp.open_write(path),
+            None => self.base.open_write(path),
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        match (self.resolve(from)?, self.resolve(to)?) {
+            (Some(sftp), Some(_)) => sftp.rename(from, to),
+            (None, None) => self.base.rename(from, to),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("Cannot rename {} across filesystems", from.display()),
+            )),
+        }
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        match self.resolve(path)? {
+            Some(sftp) => sftp.remove_file(path),
+            None => self.base.remove_file(path),
+        }
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        match self.resolve(path)? {
+            Some(sftp) => sftp.remove_dir_all(path),
+            None => self.base.remove_dir_all(path),
+        }
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        match self.resolve(path)? {
+            Some(sftp) => sftp.create_dir(path),
+            None => self.base.create_dir(path),
+        }
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        match self.resolve(path)? {
+            Some(sftp) => sftp.create_dir_all(path),
+            None => self.base.create_dir_all(path),
+        }
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        match self.resolve(path)? {
+            Some(sftp) => sftp.read_link(path),
+            None => self.base.read_link(path),
+        }
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        match self.resolve(link)? {
+            Some(sftp) => sftp.symlink(target, link),
+            None => self.base.symlink(target, link),
+        }
+    }
+
+    fn set_times(&self, path: &Path, accessed: Option<SystemTime>, modified: Option<SystemTime>) -> io::Result<()> {
+        match self.resolve(path)? {
+            Some(sftp) => sftp.set_times(path, accessed, modified),
+            None => self.base.set_times(path, accessed, modified),
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        match self.resolve(path)? {
+            Some(sftp) => sftp.canonicalize(path),
+            None => self.base.canonicalize(path),
+        }
+    }
+
+    fn local_path(&self, path: &Path) -> Option<PathBuf> {
+        if is_sftp_path(path) {
+            None
+        } else {
+            self.base.local_path(path)
+        }
+    }
+
+    fn can_browse(&self, path: &Path) -> bool {
+        if is_sftp_path(path) {
+            self.is_dir(path)
+        } else {
+            self.base.can_browse(path)
+        }
+    }
+}
+
+fn local_user_name() -> Option<String> {
+    std::env::var("USER").or_else(|_| std::env::var("USERNAME")).ok()
+}
+
+fn ssh_dir() -> io::Result<PathBuf> {
+    dirs::home_dir()
+        .map(|home| home.join(".ssh"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Home directory not found"))
+}
+
+/// Checks the server's key against `known_hosts_path`, refusing servers not
+/// in it like OpenSSH's `StrictHostKeyChecking=yes`.
+fn verify_host_key(session: &Session, location: &SftpLocation, known_hosts_path: &Path) -> io::Result<()> {
+    let (key, _) = session.host_key()
+        .ok_or_else(|| io::Error::other("Server did not send a host key"))?;
+
+    let mut known_hosts = session.known_hosts()?;
+    if known_hosts_path.exists() {
+        known_hosts.read_file(known_hosts_path, KnownHostFileKind::OpenSSH)?;
+    }
+
+    match known_hosts.check_port(&location.host, location.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::NotFound => {
+            let fingerprint = session.host_key_hash(HashType::Sha256)
+                .map(|hash| format!(" (SHA256:{})", base64(hash)))
+                .unwrap_or_default();
+            Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "{} is not a known host{}; check its key with `ssh -p {} {}@{}` and trust it there first",
+                    location.host, fingerprint, location.port, location.user, location.host,
+                ),
+            ))
+        }
+        CheckResult::Mismatch => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("Host key for {} does not match known_hosts", location.host),
+        )),
+        CheckResult::Failure => Err(io::Error::other(
+            format!("Failed to check the host key for {}", location.host),
+        )),
+    }
+}
+
+/// Tries the SSH agent, then each private key in `config`.
+fn authenticate(session: &Session, location: &SftpLocation, config: &SshConfig) -> io::Result<()> {
+    if config.use_agent && session.userauth_agent(&location.user).is_ok() && session.authenticated() {
+        return Ok(());
+    }
+
+    for private_key in &config.identities {
+        if !private_key.exists() {
+            continue;
+        }
+
+        let public_key = private_key.with_extension("pub");
+        let public_key = public_key.exists().then_some(public_key.as_path());
+        match session.userauth_pubkey_file(&location.user, public_key, private_key, None) {
+            Ok(()) if session.authenticated() => return Ok(()),
+            Ok(()) => {}
+            Err(e) => log::debug!("Key {} rejected: {}", private_key.display(), e),
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("No SSH key was accepted for {}@{}", location.user, location.host),
+    ))
+}
+
+/// Standard base64 without padding, the way OpenSSH prints fingerprints.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter()
+            .enumerate()
+            .fold(0u32, |bits, (i, &byte)| bits | u32::from(byte) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char);
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vfs::{self, LocalFs};
+    use crate::operations::copy::CopyOperation;
+    use std::process::{Child, Command, Stdio};
+    use std::time::Instant;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn parses_locations() {
+        let (location, remote_path) = SftpLocation::parse(Path::new("sftp://alice@example.com:2222/srv/data")).unwrap();
+        assert_eq!(location, SftpLocation { user: "alice".into(), host: "example.com".into(), port: 2222 });
+        assert_eq!(remote_path, Path::new("/srv/data"));
+        assert_eq!(location.to_path(&remote_path), Path::new("sftp://alice@example.com:2222/srv/data"));
+
+        let (location, remote_path) = SftpLocation::parse(Path::new("sftp://bob@example.com")).unwrap();
+        assert_eq!(location.port, DEFAULT_PORT);
+        assert_eq!(remote_path, Path::new("/"));
+        assert_eq!(location.to_string(), "sftp://bob@example.com");
+
+        assert!(SftpLocation::parse(Path::new("/home/alice")).is_none());
+        assert!(SftpLocation::parse(Path::new("sftp://alice@example.com:port/")).is_none());
+        assert!(SftpLocation::parse(Path::new("sftp://@example.com/")).is_none());
+    }
+
+    #[test]
+    fn encodes_fingerprints_like_openssh() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg");
+        assert_eq!(base64(b"fo"), "Zm8");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    /// An sshd serving SFTP on a free local port, stopped when dropped.
+    struct Sshd {
+        child: Child,
+        port: u16,
+        host_key: PathBuf,
+        client: SshConfig,
+    }
+
+    impl Drop for Sshd {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+
+    impl Sshd {
+        /// Starts sshd with fresh host and client keys kept in `dir`, or
+        /// returns `None` when there is no sshd to run.
+        fn start(dir: &Path) -> Option<Self> {
+            let sshd = ["/usr/sbin/sshd", "/usr/bin/sshd", "/usr/local/sbin/sshd"]
+                .into_iter()
+                .map(Path::new)
+                .find(|path| path.exists())?;
+
+            let host_key = dir.join("host_key");
+            let client_key = dir.join("id_ed25519");
+            for key in [&host_key, &client_key] {
+                let status = Command::new("ssh-keygen")
+                    .args(["-q", "-t", "ed25519", "-N", "", "-f"])
+                    .arg(key)
+                    .status()
+                    .expect("ssh-keygen runs");
+                assert!(status.success());
+            }
+            std::fs::copy(client_key.with_extension("pub"), dir.join("authorized_keys")).unwrap();
+
+            let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+            let config = dir.join("sshd_config");
+            std::fs::write(&config, format!(
+                "Port {port}\nListenAddress 127.0.0.1\nHostKey {}\nAuthorizedKeysFile {}\n\
+                 PidFile {}\nPasswordAuthentication no\nStrictModes no\nUsePAM no\n\
+                 Subsystem sftp internal-sftp\n",
+                host_key.display(),
+                dir.join("authorized_keys").display(),
+                dir.join("sshd.pid").display(),
+            )).unwrap();
+
+            let child = Command::new(sshd)
+                .args(["-D", "-e", "-f"])
+                .arg(&config)
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("sshd starts");
+
+            let started = Instant::now();
+            while TcpStream::connect(("127.0.0.1", port)).is_err() {
+                assert!(started.elapsed() < Duration::from_secs(10), "sshd did not start listening");
+                std::thread::sleep(Duration::from_millis(50));
+            }
+
+            Some(Self {
+                child,
+                port,
+                host_key,
+                client: SshConfig {
+                    known_hosts: dir.join("known_hosts"),
+                    identities: vec![client_key],
+                    use_agent: false,
+                },
+            })
+        }
+
+        fn location(&self) -> SftpLocation {
+            let user = Command::new("id").arg("-un").output().expect("id runs").stdout;
+            SftpLocation {
+                user: String::from_utf8_lossy(&user).trim().to_string(),
+                host: "127.0.0.1".to_string(),
+                port: self.port,
+            }
+        }
+
+        fn trust_host_key(&self) {
+            let key = std::fs::read_to_string(self.host_key.with_extension("pub")).unwrap();
+            std::fs::write(&self.client.known_hosts, format!("[127.0.0.1]:{} {}", self.port, key)).unwrap();
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn transfers_files_with_a_local_sshd() {
+        let dir = tempfile::tempdir().unwrap();
+        let Some(sshd) = Sshd::start(dir.path()) else {
+            eprintln!("sshd not found, skipping");
+            return;
+        };
+        let location = sshd.location();
+
+        // Unknown hosts are refused, and known_hosts is left alone
+        let error = SftpFs::connect_with(location.clone(), &sshd.client).err().expect("unknown host refused");
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert!(error.to_string().contains("SHA256:"));
+        assert!(!sshd.client.known_hosts.exists());
+
+        sshd.trust_host_key();
+        let sftp: Arc<dyn Vfs> = Arc::new(SftpFs::connect_with(location.clone(), &sshd.client).unwrap());
+
+        // The server runs here, so remote paths are paths in the temp dir
+        let remote = dir.path().join("remote");
+        let remote_path = location.to_path(&remote);
+        sftp.create_dir(&remote_path).unwrap();
+        vfs::write(sftp.as_ref(), &remote_path.join("hello.txt"), b"hello").unwrap();
+        assert_eq!(std::fs::read(remote.join("hello.txt")).unwrap(), b"hello");
+        assert_eq!(vfs::read(sftp.as_ref(), &remote_path.join("hello.txt")).unwrap(), b"hello");
+        assert!(sftp.is_dir(&remote_path));
+
+        let local = dir.path().join("local");
+        std::fs::create_dir_all(local.join("folder")).unwrap();
+        std::fs::write(local.join("folder/data.bin"), vec![7u8; 300_000]).unwrap();
+
+        // Upload a folder through CopyOperation, reporting progress
+        let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+        CopyOperation::new(vec![local.join("folder")], remote_path.clone())
+            .with_source_vfs(Arc::new(LocalFs))
+            .with_destination_vfs(sftp.clone())
+            .with_progress_callback(progress_tx)
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(remote.join("folder/data.bin")).unwrap(), vec![7u8; 300_000]);
+        let mut last = None;
+        while let Ok(progress) = progress_rx.try_recv() {
+            last = Some(progress);
+        }
+        let last = last.expect("progress reported");
+        assert_eq!(last.bytes_copied, 300_000);
+        assert_eq!(last.total_bytes, 300_000);
+
+        let mut names: Vec<PathBuf> = sftp.read_dir(&remote_path).unwrap();
+        names.sort();
+        assert_eq!(names, [remote_path.join("folder"), remote_path.join("hello.txt")]);
+
+        sftp.remove_dir_all(&remote_path).unwrap();
+        assert!(!remote.exists());
+    }
+}
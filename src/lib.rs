@@ -0,0 +1,6 @@
+//! ChexExplorer - the file explorer engine and its egui front end.
+
+pub mod core;
+pub mod operations;
+pub mod ui;
+pub mod utils;
@@ -1,8 +1,10 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+#[allow(clippy::field_reassign_with_default)]
 mod app;
 mod settings;
 mod theme;
+#[allow(clippy::needless_borrows_for_generic_args, clippy::manual_filter)]
 mod file_operations;
 
 use eframe::egui;
@@ -0,0 +1,123 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+/// What a copy carries over besides file contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreserveOptions {
+    /// Modification and access times.
+    pub timestamps: bool,
+    /// Permission bits, or the read-only flag on Windows.
+    pub permissions: bool,
+    /// Owner and group. Usually needs administrator rights, so failures are ignored.
+    pub ownership: bool,
+    /// Extended attributes (Unix only).
+    pub xattrs: bool,
+    /// Copy symbolic links as links instead of copying what they point to.
+    pub symlinks: bool,
+}
+
+impl PreserveOptions {
+    /// Everything, e.g. for a move that has to fall back to copying.
+    pub fn all() -> Self {
+        Self {
+            timestamps: true,
+            permissions: true,
+            ownership: true,
+            xattrs: true,
+            symlinks: true,
+        }
+    }
+}
+
+impl Default for PreserveOptions {
+    fn default() -> Self {
+        Self {
+            timestamps: true,
+            permissions: true,
+            ownership: false,
+            xattrs: false,
+            symlinks: true,
+        }
+    }
+}
+
+/// Copies permissions, ownership and extended attributes from `source` to
+/// `dest`, both on the local disk, as selected by `options`. Symbolic links
+/// are handled themselves rather than what they point to.
+pub fn copy_local_attributes(source: &Path, dest: &Path, options: &PreserveOptions) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(source)?;
+    let is_symlink = metadata.file_type().is_symlink();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        if options.ownership {
+            // Only root can give files away; keep going as the current user otherwise
+            if let Err(e) = std::os::unix::fs::lchown(dest, Some(metadata.uid()), Some(metadata.gid())) {
+                log::debug!("Could not keep the owner of {}: {}", dest.display(), e);
+            }
+        }
+
+        if options.xattrs {
+            for name in xattr::list(source)? {
+                if let Some(value) = xattr::get(source, &name)? {
+                    if let Err(e) = xattr::set(dest, &name, &value) {
+                        log::debug!("Could not copy attribute {:?} to {}: {}", name, dest.display(), e);
+                    }
+                }
+            }
+        }
+    }
+
+    // Link permissions can't be changed (and changing them would follow the link)
+    if options.permissions && !is_symlink {
+        fs::set_permissions(dest, metadata.permissions())?;
+    }
+
+    Ok(())
+}
+
+/// Recreates the FIFO or device node at `source` as `dest`. Device nodes
+/// usually need administrator rights; sockets can't be copied at all.
+#[cfg(unix)]
+pub fn copy_special_file(source: &Path, dest: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    let metadata = fs::symlink_metadata(source)?;
+    let file_type = metadata.file_type();
+    if file_type.is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Sockets can't be copied: {}", source.display()),
+        ));
+    }
+
+    let path = CString::new(dest.as_os_str().as_bytes())?;
+    let mode = metadata.mode() as libc::mode_t;
+    // SAFETY: `path` is a valid NUL-terminated string that outlives the call
+    let result = unsafe {
+        if file_type.is_fifo() {
+            libc::mkfifo(path.as_ptr(), mode & 0o7777)
+        } else {
+            libc::mknod(path.as_ptr(), mode, metadata.rdev() as libc::dev_t)
+        }
+    };
+
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn copy_special_file(source: &Path, _dest: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("Special files can't be copied: {}", source.display()),
+    ))
+}
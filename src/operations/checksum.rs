@@ -0,0 +1,137 @@
+use std::fmt;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+
+use crate::core::vfs::Vfs;
+
+/// Bytes hashed between progress and cancel checks.
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Hash used to compare files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum HashAlgorithm {
+    /// Fast and cryptographically strong.
+    #[default]
+    Blake3,
+    Sha256,
+    /// XXH3 (128 bit): fastest, but only guards against accidental damage.
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    pub const ALL: [HashAlgorithm; 3] = [HashAlgorithm::Blake3, HashAlgorithm::Sha256, HashAlgorithm::Xxh3];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            HashAlgorithm::Blake3 => "BLAKE3",
+            HashAlgorithm::Sha256 => "SHA-256",
+            HashAlgorithm::Xxh3 => "xxHash (XXH3)",
+        }
+    }
+}
+
+/// A file's hash, shown as lowercase hex.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Checksum(pub Vec<u8>);
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+enum Hasher {
+    Blake3(Box<blake3::Hasher>),
+    Sha256(sha2::Sha256),
+    Xxh3(Box<xxhash_rust::xxh3::Xxh3>),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::default()),
+            HashAlgorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            HashAlgorithm::Xxh3 => Hasher::Xxh3(Box::default()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Xxh3(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finish(self) -> Checksum {
+        Checksum(match self {
+            Hasher::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+            Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+            Hasher::Xxh3(hasher) => hasher.digest128().to_be_bytes().to_vec(),
+        })
+    }
+}
+
+/// Hashes everything `reader` yields. `progress` is called with the bytes
+/// hashed after each block and can fail to stop early.
+pub fn hash_reader(
+    reader: &mut dyn Read,
+    algorithm: HashAlgorithm,
+    progress: &mut dyn FnMut(u64) -> Result<()>,
+) -> Result<Checksum> {
+    let mut hasher = Hasher::new(algorithm);
+    let mut buffer = vec![0; BUFFER_SIZE];
+
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        hasher.update(&buffer[..read]);
+        progress(read as u64)?;
+    }
+
+    Ok(hasher.finish())
+}
+
+/// Hashes the file at `path` on `vfs`.
+pub fn hash_file(
+    vfs: &dyn Vfs,
+    path: &Path,
+    algorithm: HashAlgorithm,
+    progress: &mut dyn FnMut(u64) -> Result<()>,
+) -> Result<Checksum> {
+    let mut reader = vfs.open_read(path)?;
+    hash_reader(&mut reader, algorithm, progress)
+}
+
+/// Error for a copy whose contents don't hash the same as its source.
+#[derive(Debug, Clone)]
+pub struct ChecksumMismatch {
+    pub source: PathBuf,
+    pub copy: PathBuf,
+    pub algorithm: HashAlgorithm,
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} does not match its source ({} differs)",
+            self.copy.display(),
+            self.algorithm.label()
+        )
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {}
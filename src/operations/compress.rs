@@ -1,66 +1,124 @@
 use std::path::{Path, PathBuf};
 use anyhow::Result;
-use std::fs::File;
 use std::io::Write;
 
+use crate::core::vfs::{self, LocalFs, Vfs, VfsMetadata, VfsWriter};
+
 pub async fn create_zip_archive(files: Vec<PathBuf>, output_path: &Path) -> Result<()> {
-    let file = File::create(output_path)?;
+    create_zip_archive_in(&LocalFs, files, output_path).await
+}
+
+pub async fn create_zip_archive_in<V: Vfs + ?Sized>(
+    vfs: &V,
+    files: Vec<PathBuf>,
+    output_path: &Path,
+) -> Result<()> {
+    let file = vfs.open_write(output_path)?;
     let mut zip = zip::ZipWriter::new(file);
 
     for file_path in files {
-        if file_path.is_file() {
+        let metadata = vfs.metadata(&file_path)?;
+        if metadata.is_file() {
             let file_name = file_path.file_name()
                 .ok_or_else(|| anyhow::anyhow!("Invalid file name"))?
                 .to_string_lossy();
             
             zip.start_file(file_name, zip::write::SimpleFileOptions::default())?;
-            let content = std::fs::read(&file_path)?;
-            zip.write_all(&content)?;
-        } else if file_path.is_dir() {
-            add_directory_to_zip(&mut zip, &file_path, &file_path)?;
+            std::io::copy(&mut vfs.open_read(&file_path)?, &mut zip)?;
+        } else if metadata.is_dir() {
+            add_directory_to_zip(vfs, &mut zip, &file_path, &file_path)?;
         }
     }
 
-    zip.finish()?;
+    zip.finish()?.flush()?;
     Ok(())
 }
 
-fn add_directory_to_zip(
-    zip: &mut zip::ZipWriter<File>,
+fn add_directory_to_zip<V: Vfs + ?Sized>(
+    vfs: &V,
+    zip: &mut zip::ZipWriter<Box<dyn VfsWriter>>,
     dir_path: &Path,
     base_path: &Path,
 ) -> Result<()> {
-    for entry in walkdir::WalkDir::new(dir_path) {
-        let entry = entry?;
-        let path = entry.path();
-        
-        if path.is_file() {
+    for (path, _) in vfs::walk(vfs, dir_path)? {
+        if vfs.is_file(&path) {
             let relative_path = path.strip_prefix(base_path)?;
             zip.start_file(
                 relative_path.to_string_lossy(),
                 zip::write::SimpleFileOptions::default(),
             )?;
-            let content = std::fs::read(path)?;
-            zip.write_all(&content)?;
+            std::io::copy(&mut vfs.open_read(&path)?, zip)?;
         }
     }
     Ok(())
 }
 
 pub async fn create_tar_archive(files: Vec<PathBuf>, output_path: &Path) -> Result<()> {
-    let file = File::create(output_path)?;
+    create_tar_archive_in(&LocalFs, files, output_path).await
+}
+
+pub async fn create_tar_archive_in<V: Vfs + ?Sized>(
+    vfs: &V,
+    files: Vec<PathBuf>,
+    output_path: &Path,
+) -> Result<()> {
+    let file = vfs.open_write(output_path)?;
     let mut archive = tar::Builder::new(file);
 
     for file_path in files {
-        if file_path.is_file() {
+        let metadata = vfs.metadata(&file_path)?;
+        // The tar crate reads local files itself, keeping their modes and owners
+        let local_path = vfs.local_path(&file_path);
+
+        if metadata.is_file() {
             let file_name = file_path.file_name()
                 .ok_or_else(|| anyhow::anyhow!("Invalid file name"))?;
-            archive.append_path_with_name(&file_path, file_name)?;
-        } else if file_path.is_dir() {
-            archive.append_dir_all(".", &file_path)?;
+            match local_path {
+                Some(local_path) => archive.append_path_with_name(&local_path, file_name)?,
+                None => append_vfs_entry(vfs, &mut archive, &file_path, Path::new(file_name), &metadata)?,
+            }
+        } else if metadata.is_dir() {
+            match local_path {
+                Some(local_path) => archive.append_dir_all(".", &local_path)?,
+                None => {
+                    for (path, entry) in vfs::walk(vfs, &file_path)? {
+                        let relative_path = path.strip_prefix(&file_path)?;
+                        append_vfs_entry(vfs, &mut archive, &path, relative_path, &entry)?;
+                    }
+                }
+            }
         }
     }
 
-    archive.finish()?;
+    archive.into_inner()?.flush()?;
+    Ok(())
+}
+
+fn append_vfs_entry<V: Vfs + ?Sized>(
+    vfs: &V,
+    archive: &mut tar::Builder<Box<dyn VfsWriter>>,
+    path: &Path,
+    name: &Path,
+    metadata: &VfsMetadata,
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    let mtime = metadata.modified
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    header.set_mtime(mtime);
+
+    if metadata.is_dir() {
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_mode(0o755);
+        header.set_size(0);
+        archive.append_data(&mut header, name, std::io::empty())?;
+    } else if metadata.is_file() {
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_mode(if metadata.is_readonly { 0o444 } else { 0o644 });
+        header.set_size(metadata.len);
+        archive.append_data(&mut header, name, vfs.open_read(path)?)?;
+    }
+
     Ok(())
 }
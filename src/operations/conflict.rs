@@ -0,0 +1,350 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::core::vfs::Vfs;
+use crate::operations::control::Cancelled;
+use crate::utils::file_utils::ensure_unique_filename_in;
+
+/// What to do when a copied or moved item already exists at the destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ConflictPolicy {
+    /// Ask through the conflict resolver; fail if there is none.
+    #[default]
+    Ask,
+    Skip,
+    Overwrite,
+    /// Overwrite only when the source was modified more recently.
+    OverwriteIfNewer,
+    /// Overwrite only when the sizes differ.
+    OverwriteIfDifferentSize,
+    /// Copy under a new name such as `report (1).txt`.
+    KeepBoth,
+}
+
+impl ConflictPolicy {
+    pub const ALL: [ConflictPolicy; 6] = [
+        ConflictPolicy::Ask,
+        ConflictPolicy::Skip,
+        ConflictPolicy::Overwrite,
+        ConflictPolicy::OverwriteIfNewer,
+        ConflictPolicy::OverwriteIfDifferentSize,
+        ConflictPolicy::KeepBoth,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Ask => "Ask",
+            ConflictPolicy::Skip => "Skip",
+            ConflictPolicy::Overwrite => "Overwrite",
+            ConflictPolicy::OverwriteIfNewer => "Overwrite if newer",
+            ConflictPolicy::OverwriteIfDifferentSize => "Overwrite if size differs",
+            ConflictPolicy::KeepBoth => "Keep both",
+        }
+    }
+}
+
+/// The choice made for a single conflict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictResolution {
+    Skip,
+    Overwrite,
+    OverwriteIfNewer,
+    OverwriteIfDifferentSize,
+    KeepBoth,
+    /// Stop the whole operation.
+    Cancel,
+}
+
+/// Both sides of a conflict, for showing to the user.
+#[derive(Debug, Clone)]
+pub struct FileConflict {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub source_size: u64,
+    pub source_modified: Option<SystemTime>,
+    pub destination_size: u64,
+    pub destination_modified: Option<SystemTime>,
+    pub is_dir: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ConflictAnswer {
+    pub resolution: ConflictResolution,
+    /// Use the same resolution for the remaining conflicts of this operation.
+    pub apply_to_all: bool,
+}
+
+/// A conflict waiting for an answer; reply through `reply`.
+#[derive(Debug)]
+pub struct ConflictRequest {
+    pub conflict: FileConflict,
+    pub reply: oneshot::Sender<ConflictAnswer>,
+}
+
+/// Decides, per item, how an operation handles existing destinations.
+#[derive(Debug, Default)]
+pub struct ConflictHandler {
+    policy: ConflictPolicy,
+    resolver: Option<mpsc::UnboundedSender<ConflictRequest>>,
+    /// Set once the user picks "apply to all".
+    remembered: Mutex<Option<ConflictResolution>>,
+}
+
+impl ConflictHandler {
+    pub fn new(policy: ConflictPolicy) -> Self {
+        Self {
+            policy,
+            resolver: None,
+            remembered: Mutex::new(None),
+        }
+    }
+
+    /// Sends conflicts to `resolver` when the policy is [`ConflictPolicy::Ask`].
+    pub fn with_resolver(mut self, resolver: mpsc::UnboundedSender<ConflictRequest>) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    pub fn policy(&self) -> ConflictPolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: ConflictPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn set_resolver(&mut self, resolver: Option<mpsc::UnboundedSender<ConflictRequest>>) {
+        self.resolver = resolver;
+    }
+
+    /// Works out where `source` should go given that `destination` may exist.
+    ///
+    /// Returns the path to write to (either `destination`, which may then be
+    /// replaced, or a fresh name next to it), or `None` to skip the item.
+    pub async fn resolve(
+        &self,
+        source_vfs: &dyn Vfs,
+        source: &Path,
+        destination_vfs: &dyn Vfs,
+        destination: &Path,
+    ) -> Result<Option<PathBuf>> {
+        let Ok(existing) = destination_vfs.metadata(destination) else {
+            return Ok(Some(destination.to_path_buf()));
+        };
+        let incoming = source_vfs.metadata(source)?;
+
+        let remembered = *self.remembered.lock().unwrap_or_else(|e| e.into_inner());
+        let resolution = match (remembered, self.policy) {
+            (Some(resolution), _) => resolution,
+            (None, ConflictPolicy::Skip) => ConflictResolution::Skip,
+            (None, ConflictPolicy::Overwrite) => ConflictResolution::Overwrite,
+            (None, ConflictPolicy::OverwriteIfNewer) => ConflictResolution::OverwriteIfNewer,
+            (None, ConflictPolicy::OverwriteIfDifferentSize) => ConflictResolution::OverwriteIfDifferentSize,
+            (None, ConflictPolicy::KeepBoth) => ConflictResolution::KeepBoth,
+            (None, ConflictPolicy::Ask) => {
+                let conflict = FileConflict {
+                    source: source.to_path_buf(),
+                    destination: destination.to_path_buf(),
+                    source_size: incoming.len,
+                    source_modified: incoming.modified,
+                    destination_size: existing.len,
+                    destination_modified: existing.modified,
+                    is_dir: existing.is_dir(),
+                };
+                self.ask(conflict).await?
+            }
+        };
+
+        let overwrite = match resolution {
+            ConflictResolution::Cancel => return Err(Cancelled.into()),
+            ConflictResolution::Skip => false,
+            ConflictResolution::Overwrite => true,
+            ConflictResolution::OverwriteIfNewer => match (incoming.modified, existing.modified) {
+                (Some(incoming), Some(existing)) => incoming > existing,
+                _ => false,
+            },
+            ConflictResolution::OverwriteIfDifferentSize => incoming.len != existing.len,
+            ConflictResolution::KeepBoth => {
+                return Ok(Some(ensure_unique_filename_in(destination_vfs, destination)));
+            }
+        };
+
+        Ok(overwrite.then(|| destination.to_path_buf()))
+    }
+
+    async fn ask(&self, conflict: FileConflict) -> Result<ConflictResolution> {
+        let Some(ref resolver) = self.resolver else {
+            return Err(anyhow::anyhow!("Destination file already exists: {}", conflict.destination.display()));
+        };
+
+        let (reply, answer) = oneshot::channel();
+        resolver.send(ConflictRequest { conflict, reply })
+            .map_err(|_| anyhow::anyhow!("Nobody is available to resolve the conflict"))?;
+        // A dropped request (e.g. the dialog closed) cancels the operation
+        let answer = answer.await.map_err(|_| Cancelled)?;
+
+        if answer.apply_to_all {
+            *self.remembered.lock().unwrap_or_else(|e| e.into_inner()) = Some(answer.resolution);
+        }
+        Ok(answer.resolution)
+    }
+}
+
+/// An item being replaced, moved out of the way under a hidden name in its
+/// folder. It is only deleted once its replacement is fully in place, and
+/// goes back if the replacement fails.
+#[derive(Debug)]
+pub struct SetAside {
+    original: PathBuf,
+    aside: PathBuf,
+}
+
+impl SetAside {
+    /// Renames what is at `path` out of the way.
+    pub fn new(vfs: &dyn Vfs, path: &Path) -> io::Result<Self> {
+        let name = path.file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid file name: {}", path.display())))?;
+        let aside = path.with_file_name(format!(
+            ".{}.replaced-{}",
+            name.to_string_lossy(),
+            uuid::Uuid::new_v4().simple()
+        ));
+        vfs.rename(path, &aside)?;
+        Ok(Self { original: path.to_path_buf(), aside })
+    }
+
+    /// Deletes the old item, now that its replacement is in place.
+    pub fn discard(self, vfs: &dyn Vfs) {
+        let removed = match vfs.symlink_metadata(&self.aside) {
+            Ok(metadata) if metadata.is_dir() => vfs.remove_dir_all(&self.aside),
+            Ok(_) => vfs.remove_file(&self.aside),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = removed {
+            log::warn!("Failed to remove replaced {} (left as {}): {}", self.original.display(), self.aside.display(), e);
+        }
+    }
+
+    /// Puts the old item back after its replacement failed. Whatever the
+    /// replacement left behind must already be gone.
+    pub fn restore(self, vfs: &dyn Vfs) {
+        if let Err(e) = vfs.rename(&self.aside, &self.original) {
+            log::error!("Failed to put back {} (left as {}): {}", self.original.display(), self.aside.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vfs::{self, MemoryFs};
+
+    async fn resolve(handler: &ConflictHandler, vfs: &MemoryFs, source: &str, destination: &str) -> Result<Option<PathBuf>> {
+        handler.resolve(vfs, Path::new(source), vfs, Path::new(destination)).await
+    }
+
+    #[tokio::test]
+    async fn keep_both_picks_the_next_free_name() {
+        let vfs = MemoryFs::new()
+            .with_file("/src/report.txt", "new")
+            .with_file("/dst/report.txt", "old")
+            .with_file("/dst/report (1).txt", "older")
+            .with_dir("/src/photos")
+            .with_dir("/dst/photos")
+            .with_file("/src/README", "new")
+            .with_file("/dst/README", "old");
+        let handler = ConflictHandler::new(ConflictPolicy::KeepBoth);
+
+        let target = resolve(&handler, &vfs, "/src/report.txt", "/dst/report.txt").await.unwrap();
+        assert_eq!(target, Some(PathBuf::from("/dst/report (2).txt")));
+        let target = resolve(&handler, &vfs, "/src/photos", "/dst/photos").await.unwrap();
+        assert_eq!(target, Some(PathBuf::from("/dst/photos (1)")));
+        let target = resolve(&handler, &vfs, "/src/README", "/dst/README").await.unwrap();
+        assert_eq!(target, Some(PathBuf::from("/dst/README (1)")));
+    }
+
+    #[tokio::test]
+    async fn free_destinations_need_no_decision() {
+        let vfs = MemoryFs::new().with_file("/src/a.txt", "a").with_dir("/dst");
+        let handler = ConflictHandler::new(ConflictPolicy::Ask);
+        let target = resolve(&handler, &vfs, "/src/a.txt", "/dst/a.txt").await.unwrap();
+        assert_eq!(target, Some(PathBuf::from("/dst/a.txt")));
+    }
+
+    #[tokio::test]
+    async fn policies_decide_without_asking() {
+        let vfs = MemoryFs::new()
+            .with_file("/dst/same.txt", "abc")
+            .with_file("/dst/other.txt", "abc")
+            .with_dir("/src");
+        // Written after the destinations, so the sources are newer
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        vfs::write(&vfs, Path::new("/src/same.txt"), b"xyz").unwrap();
+        vfs::write(&vfs, Path::new("/src/other.txt"), b"longer").unwrap();
+
+        // Whether the newer same-size, newer other-size and older same-size files go in
+        let expected = [
+            (ConflictPolicy::Skip, [false, false, false]),
+            (ConflictPolicy::Overwrite, [true, true, true]),
+            (ConflictPolicy::OverwriteIfNewer, [true, true, false]),
+            (ConflictPolicy::OverwriteIfDifferentSize, [false, true, false]),
+        ];
+        for (policy, expected) in expected {
+            let handler = ConflictHandler::new(policy);
+            let decided = [
+                resolve(&handler, &vfs, "/src/same.txt", "/dst/same.txt").await.unwrap().is_some(),
+                resolve(&handler, &vfs, "/src/other.txt", "/dst/other.txt").await.unwrap().is_some(),
+                resolve(&handler, &vfs, "/dst/same.txt", "/src/same.txt").await.unwrap().is_some(),
+            ];
+            assert_eq!(decided, expected, "{:?}", policy);
+        }
+    }
+
+    #[tokio::test]
+    async fn asks_and_remembers_apply_to_all() {
+        let vfs = MemoryFs::new()
+            .with_file("/src/a.txt", "a")
+            .with_file("/src/b.txt", "b")
+            .with_file("/dst/a.txt", "old")
+            .with_file("/dst/b.txt", "old");
+
+        assert!(resolve(&ConflictHandler::new(ConflictPolicy::Ask), &vfs, "/src/a.txt", "/dst/a.txt").await.is_err());
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<ConflictRequest>();
+        tokio::spawn(async move {
+            let request = rx.recv().await.unwrap();
+            assert_eq!(request.conflict.destination, Path::new("/dst/a.txt"));
+            assert_eq!((request.conflict.source_size, request.conflict.destination_size), (1, 3));
+            let _ = request.reply.send(ConflictAnswer { resolution: ConflictResolution::KeepBoth, apply_to_all: true });
+        });
+
+        let handler = ConflictHandler::new(ConflictPolicy::Ask).with_resolver(tx);
+        let target = resolve(&handler, &vfs, "/src/a.txt", "/dst/a.txt").await.unwrap();
+        assert_eq!(target, Some(PathBuf::from("/dst/a (1).txt")));
+        // Answered once for all, with nobody left to ask
+        let target = resolve(&handler, &vfs, "/src/b.txt", "/dst/b.txt").await.unwrap();
+        assert_eq!(target, Some(PathBuf::from("/dst/b (1).txt")));
+    }
+
+    #[test]
+    fn set_aside_hides_the_item_until_discarded_or_restored() {
+        let vfs = MemoryFs::new().with_file("/dst/a.txt", "old").with_file("/dst/b.txt", "old");
+
+        let aside = SetAside::new(&vfs, Path::new("/dst/a.txt")).unwrap();
+        assert!(!vfs.exists(Path::new("/dst/a.txt")));
+        let hidden: Vec<_> = vfs.read_dir(Path::new("/dst")).unwrap().into_iter()
+            .filter(|path| path.file_name().unwrap().to_string_lossy().starts_with(".a.txt.replaced-"))
+            .collect();
+        assert_eq!(hidden.len(), 1);
+        aside.restore(&vfs);
+        assert_eq!(vfs::read(&vfs, Path::new("/dst/a.txt")).unwrap(), b"old");
+
+        SetAside::new(&vfs, Path::new("/dst/b.txt")).unwrap().discard(&vfs);
+        assert_eq!(vfs.read_dir(Path::new("/dst")).unwrap(), [PathBuf::from("/dst/a.txt")]);
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use anyhow::Result;
+use tokio::sync::Notify;
+
+const RUNNING: u8 = 0;
+const PAUSED: u8 = 1;
+const CANCELLED: u8 = 2;
+
+/// Error returned by an operation that was stopped through its [`JobControl`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Operation cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Pause, resume and cancel switch shared between a running operation and
+/// whoever started it. Clones control the same operation.
+///
+/// Operations call [`JobControl::checkpoint`] between units of work; it waits
+/// while paused and fails with [`Cancelled`] once cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct JobControl {
+    inner: Arc<ControlInner>,
+}
+
+#[derive(Debug, Default)]
+struct ControlInner {
+    state: AtomicU8,
+    changed: Notify,
+}
+
+impl JobControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pause(&self) {
+        // A cancelled operation stays cancelled
+        let _ = self.inner.state.compare_exchange(RUNNING, PAUSED, Ordering::SeqCst, Ordering::SeqCst);
+        self.inner.changed.notify_waiters();
+    }
+
+    pub fn resume(&self) {
+        let _ = self.inner.state.compare_exchange(PAUSED, RUNNING, Ordering::SeqCst, Ordering::SeqCst);
+        self.inner.changed.notify_waiters();
+    }
+
+    pub fn cancel(&self) {
+        self.inner.state.store(CANCELLED, Ordering::SeqCst);
+        self.inner.changed.notify_waiters();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.inner.state.load(Ordering::SeqCst) == PAUSED
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.state.load(Ordering::SeqCst) == CANCELLED
+    }
+
+    /// Waits while the operation is paused. Fails once it has been cancelled.
+    pub async fn checkpoint(&self) -> Result<()> {
+        loop {
+            // Register for wakeups before reading the state so none are missed
+            let changed = self.inner.changed.notified();
+            match self.inner.state.load(Ordering::SeqCst) {
+                RUNNING => return Ok(()),
+                PAUSED => changed.await,
+                _ => return Err(Cancelled.into()),
+            }
+        }
+    }
+}
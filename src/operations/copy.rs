@@ -1,22 +1,252 @@
+use std::ffi::OsString;
+use std::fmt;
 use std::path::{Path, PathBuf};
-use std::fs;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use anyhow::Result;
-use tokio::sync::mpsc;
+use serde::{Deserialize, Serialize};
+use tokio::runtime::Handle;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::{JoinError, JoinSet};
 
-#[derive(Debug, Clone)]
+use crate::core::file_item::FileType;
+use crate::core::vfs::{LocalFs, Vfs, VfsMetadata};
+use crate::operations::attributes::{self, PreserveOptions};
+use crate::operations::checksum::{self, ChecksumMismatch, HashAlgorithm};
+use crate::operations::conflict::{ConflictHandler, ConflictPolicy, ConflictRequest, SetAside};
+use crate::operations::control::{Cancelled, JobControl};
+use crate::operations::local_copy;
+use crate::utils::format::{format_duration, format_file_size};
+
+/// Size of the blocks copied between pause/cancel checks.
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Least time between progress reports while a file is being copied.
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Least time between throughput samples, so the speed doesn't jitter.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Weight of the newest sample in the smoothed throughput.
+const SMOOTHING: f64 = 0.3;
+
+/// Files copied at the same time unless set otherwise.
+pub const DEFAULT_WORKERS: usize = 4;
+
+#[derive(Debug, Clone, Default)]
 pub struct CopyProgress {
     pub current_file: PathBuf,
     pub total_files: usize,
     pub completed_files: usize,
     pub bytes_copied: u64,
     pub total_bytes: u64,
+    /// Bytes copied so far of `current_file`.
+    pub current_file_bytes: u64,
+    pub current_file_size: u64,
+    /// Smoothed throughput, once there has been enough time to measure it.
+    pub bytes_per_second: Option<f64>,
+    /// Estimated time until the whole copy finishes.
+    pub eta: Option<Duration>,
+}
+
+impl CopyProgress {
+    /// Share of all bytes copied so far, from 0 to 1. Jobs that count items
+    /// rather than bytes, such as deletes, report no bytes and go by files.
+    pub fn fraction(&self) -> f32 {
+        if self.total_bytes > 0 {
+            (self.bytes_copied as f64 / self.total_bytes as f64).min(1.0) as f32
+        } else if self.total_files > 0 {
+            (self.completed_files as f64 / self.total_files as f64).min(1.0) as f32
+        } else {
+            0.0
+        }
+    }
+
+    /// Speed and time left, e.g. "12.5 MB/s, 3:04 left".
+    pub fn rate_text(&self) -> Option<String> {
+        let rate = self.bytes_per_second?;
+        let speed = format!("{}/s", format_file_size(rate as u64));
+        Some(match self.eta {
+            Some(eta) => format!("{}, {} left", speed, format_duration(eta)),
+            None => speed,
+        })
+    }
+}
+
+/// Running totals for the progress reports of one copy.
+#[derive(Debug, Default)]
+struct Tally {
+    total_files: usize,
+    completed_files: usize,
+    bytes_copied: u64,
+    total_bytes: u64,
+    throughput: Throughput,
+    last_report: Option<Instant>,
+}
+
+impl Tally {
+    fn add_bytes(&mut self, bytes: u64) {
+        self.bytes_copied += bytes;
+        self.throughput.update(self.bytes_copied);
+    }
+
+    /// Whether enough time has passed to send another report.
+    fn report_due(&self) -> bool {
+        self.last_report.is_none_or(|at| at.elapsed() >= REPORT_INTERVAL)
+    }
+
+    fn eta(&self) -> Option<Duration> {
+        let rate = self.throughput.rate.filter(|rate| *rate > 0.0)?;
+        let remaining = self.total_bytes.saturating_sub(self.bytes_copied);
+        Some(Duration::from_secs_f64(remaining as f64 / rate))
+    }
+}
+
+/// Exponentially smoothed transfer rate.
+#[derive(Debug)]
+struct Throughput {
+    sampled_at: Instant,
+    sampled_bytes: u64,
+    rate: Option<f64>,
+}
+
+impl Default for Throughput {
+    fn default() -> Self {
+        Self {
+            sampled_at: Instant::now(),
+            sampled_bytes: 0,
+            rate: None,
+        }
+    }
+}
+
+impl Throughput {
+    fn update(&mut self, total_bytes: u64) {
+        let elapsed = self.sampled_at.elapsed();
+        if elapsed < SAMPLE_INTERVAL {
+            return;
+        }
+
+        let sample = total_bytes.saturating_sub(self.sampled_bytes) as f64 / elapsed.as_secs_f64();
+        self.rate = Some(match self.rate {
+            Some(rate) => SMOOTHING * sample + (1.0 - SMOOTHING) * rate,
+            None => sample,
+        });
+        self.sampled_at = Instant::now();
+        self.sampled_bytes = total_bytes;
+    }
+}
+
+/// What a copy does when an item can't be copied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CopyErrorMode {
+    /// Stop at the first failure.
+    #[default]
+    StopOnFirst,
+    /// Copy everything else and report all failures at the end.
+    CollectAll,
+}
+
+impl CopyErrorMode {
+    pub const ALL: [CopyErrorMode; 2] = [CopyErrorMode::StopOnFirst, CopyErrorMode::CollectAll];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CopyErrorMode::StopOnFirst => "Stop at first error",
+            CopyErrorMode::CollectAll => "Keep going",
+        }
+    }
+}
+
+/// The items a [`CopyErrorMode::CollectAll`] copy could not copy, and the
+/// copies of any copy that did not verify.
+#[derive(Debug, Clone)]
+pub struct CopyErrors {
+    /// Each failed source with its error message.
+    pub failures: Vec<(PathBuf, String)>,
+    /// Copies whose contents differ from their source.
+    pub mismatches: Vec<ChecksumMismatch>,
+}
+
+impl fmt::Display for CopyErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.failures.as_slice() {
+            [] => {
+                return match self.mismatches.as_slice() {
+                    [] => write!(f, "Nothing failed to copy"),
+                    [mismatch] => write!(f, "{}", mismatch),
+                    mismatches => write!(f, "{} copies do not match their source", mismatches.len()),
+                };
+            }
+            [(path, error)] => write!(f, "Could not copy {}: {}", path.display(), error)?,
+            [(path, error), ..] => write!(
+                f,
+                "Could not copy {} items, first {}: {}",
+                self.failures.len(),
+                path.display(),
+                error
+            )?,
+        }
+        if !self.mismatches.is_empty() {
+            write!(f, ", and {} copies do not match their source", self.mismatches.len())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CopyErrors {}
+
+/// Returned by file workers that gave up because another file failed.
+#[derive(Debug)]
+struct Stopped;
+
+impl fmt::Display for Stopped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Stopped after another file failed")
+    }
+}
+
+impl std::error::Error for Stopped {}
+
+/// One item of a copy, worked out before anything is written.
+#[derive(Debug)]
+struct PlannedEntry {
+    source: PathBuf,
+    target: PathBuf,
+    /// The source's metadata, with links resolved when they are followed.
+    metadata: VfsMetadata,
+    /// Something at `target` is being replaced. It is set aside while the
+    /// entry is written and only deleted once the entry is in place.
+    replaces: bool,
+}
+
+/// Everything a copy will do, from a single walk of the sources.
+#[derive(Debug, Default)]
+struct Plan {
+    /// Folders, links and special files, parents before children.
+    entries: Vec<PlannedEntry>,
+    files: Vec<PlannedEntry>,
+    total_bytes: u64,
+    created: Vec<(PathBuf, PathBuf)>,
 }
 
 pub struct CopyOperation {
     source_paths: Vec<PathBuf>,
     destination: PathBuf,
-    overwrite: bool,
+    target_name: Option<OsString>,
+    conflicts: ConflictHandler,
+    preserve: PreserveOptions,
+    workers: usize,
+    error_mode: CopyErrorMode,
+    verify: Option<HashAlgorithm>,
     progress_tx: Option<mpsc::UnboundedSender<CopyProgress>>,
+    control: Option<JobControl>,
+    source_vfs: Arc<dyn Vfs>,
+    destination_vfs: Arc<dyn Vfs>,
+    /// Top-level `(source, copy)` pairs whose copy did not exist beforehand.
+    created: Mutex<Vec<(PathBuf, PathBuf)>>,
 }
 
 impl CopyOperation {
@@ -24,13 +254,82 @@ impl CopyOperation {
         Self {
             source_paths,
             destination,
-            overwrite: false,
+            target_name: None,
+            conflicts: ConflictHandler::default(),
+            preserve: PreserveOptions::default(),
+            workers: DEFAULT_WORKERS,
+            error_mode: CopyErrorMode::default(),
+            verify: None,
             progress_tx: None,
+            control: None,
+            source_vfs: Arc::new(LocalFs),
+            destination_vfs: Arc::new(LocalFs),
+            created: Mutex::new(Vec::new()),
         }
     }
 
-    pub fn with_overwrite(mut self, overwrite: bool) -> Self {
-        self.overwrite = overwrite;
+    /// Reads the sources from `vfs` instead of the local disk.
+    pub fn with_source_vfs(mut self, vfs: Arc<dyn Vfs>) -> Self {
+        self.source_vfs = vfs;
+        self
+    }
+
+    /// Writes the copies to `vfs` instead of the local disk.
+    pub fn with_destination_vfs(mut self, vfs: Arc<dyn Vfs>) -> Self {
+        self.destination_vfs = vfs;
+        self
+    }
+
+    /// Names the copy `name` instead of after its source. Only meaningful
+    /// when copying a single source.
+    pub fn with_target_name(mut self, name: OsString) -> Self {
+        self.target_name = Some(name);
+        self
+    }
+
+    /// Shorthand for [`ConflictPolicy::Overwrite`], or [`ConflictPolicy::Ask`] when false.
+    pub fn with_overwrite(self, overwrite: bool) -> Self {
+        self.with_conflict_policy(if overwrite { ConflictPolicy::Overwrite } else { ConflictPolicy::Ask })
+    }
+
+    /// Sets what happens to files that already exist at the destination.
+    pub fn with_conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.conflicts.set_policy(policy);
+        self
+    }
+
+    /// Sends conflicts to `resolver` to be answered when the policy is
+    /// [`ConflictPolicy::Ask`]. Without one, a conflict fails the copy.
+    pub fn with_conflict_resolver(mut self, resolver: mpsc::UnboundedSender<ConflictRequest>) -> Self {
+        self.conflicts.set_resolver(Some(resolver));
+        self
+    }
+
+    /// Sets which attributes are kept and whether symbolic links are copied
+    /// as links or followed.
+    pub fn with_preserve(mut self, preserve: PreserveOptions) -> Self {
+        self.preserve = preserve;
+        self
+    }
+
+    /// Copies up to `workers` files at the same time. At least one.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Sets whether the copy stops at the first failure or copies what it can.
+    pub fn with_error_mode(mut self, mode: CopyErrorMode) -> Self {
+        self.error_mode = mode;
+        self
+    }
+
+    /// Hashes every file and its copy with `algorithm` once copied. Copies
+    /// that differ don't stop the copy; they are listed in
+    /// [`CopyErrors::mismatches`] at the end and left in place, unless they
+    /// replaced something, which then goes back.
+    pub fn with_verify(mut self, algorithm: HashAlgorithm) -> Self {
+        self.verify = Some(algorithm);
         self
     }
 
@@ -39,122 +338,569 @@ impl CopyOperation {
         self
     }
 
-    pub async fn execute(&self) -> Result<()> {
-        if !self.destination.exists() {
-            fs::create_dir_all(&self.destination)?;
-        }
+    /// Lets `control` pause or cancel the copy. A file cancelled part way
+    /// through is removed from the destination.
+    pub fn with_control(mut self, control: JobControl) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    pub fn source_paths(&self) -> &[PathBuf] {
+        &self.source_paths
+    }
 
-        let mut total_files = 0;
-        let mut total_bytes = 0;
+    pub fn destination(&self) -> &Path {
+        &self.destination
+    }
 
-        // Calculate total work
-        for source in &self.source_paths {
-            let (files, bytes) = self.calculate_work(source)?;
-            total_files += files;
-            total_bytes += bytes;
-        }
+    pub fn destination_vfs(&self) -> Arc<dyn Vfs> {
+        self.destination_vfs.clone()
+    }
 
-        let mut completed_files = 0;
-        let mut bytes_copied = 0;
+    /// The top-level items this copy created, as `(source, copy)` pairs.
+    /// Skipped, overwritten and merged items are left out.
+    pub fn created(&self) -> Vec<(PathBuf, PathBuf)> {
+        self.created.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
 
-        // Copy files
+    /// Walks the sources once to plan the copy, creates the folders, links
+    /// and special files, then copies file contents on a pool of workers.
+    pub async fn execute(&self) -> Result<()> {
+        if !self.destination_vfs.exists(&self.destination) {
+            self.destination_vfs.create_dir_all(&self.destination)?;
+        }
+
+        let mut plan = Plan::default();
         for source in &self.source_paths {
-            self.copy_recursive(
-                source,
-                &self.destination,
-                &mut completed_files,
-                &mut bytes_copied,
-                total_files,
-                total_bytes,
-            ).await?;
+            self.plan_entry(source, &self.destination, false, &mut plan, &mut Vec::new()).await?;
         }
+        *self.created.lock().unwrap_or_else(|e| e.into_inner()) = std::mem::take(&mut plan.created);
 
-        Ok(())
-    }
+        let mut failures = Vec::new();
+        let mut replaced_folders = Vec::new();
+        let mut stopped = None;
+        for entry in &plan.entries {
+            if let Some(ref control) = self.control {
+                if let Err(e) = control.checkpoint().await {
+                    stopped = Some(e);
+                    break;
+                }
+            }
+            match self.create_entry(entry) {
+                Ok(Some(replaced)) => replaced_folders.push((entry, replaced)),
+                Ok(None) => {}
+                Err(e) if self.error_mode == CopyErrorMode::StopOnFirst => {
+                    stopped = Some(e);
+                    break;
+                }
+                Err(e) => failures.push((entry.source.clone(), e)),
+            }
+        }
+        if let Some(e) = stopped {
+            self.finish_replacing(replaced_folders, false, &failures);
+            return Err(e);
+        }
 
-    fn calculate_work(&self, path: &Path) -> Result<(usize, u64)> {
-        let mut files = 0;
-        let mut bytes = 0;
+        let copier = Arc::new(FileCopier {
+            source_vfs: self.source_vfs.clone(),
+            destination_vfs: self.destination_vfs.clone(),
+            preserve: self.preserve,
+            error_mode: self.error_mode,
+            verify: self.verify,
+            control: self.control.clone(),
+            progress_tx: self.progress_tx.clone(),
+            runtime: Handle::current(),
+            stop: AtomicBool::new(false),
+            mismatches: Mutex::new(Vec::new()),
+            tally: Mutex::new(Tally {
+                total_files: plan.files.len(),
+                total_bytes: plan.total_bytes,
+                ..Tally::default()
+            }),
+        });
+        failures.extend(self.copy_contents(plan.files, &copier).await);
+        self.finish_replacing(replaced_folders, !copier.stop.load(Ordering::SeqCst), &failures);
 
-        if path.is_file() {
-            files = 1;
-            bytes = path.metadata()?.len();
-        } else if path.is_dir() {
-            for entry in walkdir::WalkDir::new(path) {
-                let entry = entry?;
-                if entry.file_type().is_file() {
-                    files += 1;
-                    bytes += entry.metadata()?.len();
-                }
+        // Folder times last, deepest first, since filling a folder changes them
+        if !copier.stop.load(Ordering::SeqCst) {
+            for entry in plan.entries.iter().rev().filter(|entry| entry.metadata.is_dir()) {
+                preserve_attributes(
+                    self.source_vfs.as_ref(),
+                    self.destination_vfs.as_ref(),
+                    &self.preserve,
+                    entry,
+                );
             }
         }
 
-        Ok((files, bytes))
+        let mismatches = std::mem::take(&mut *copier.mismatches.lock().unwrap_or_else(|e| e.into_inner()));
+        self.outcome(failures, mismatches)
     }
 
-    fn copy_recursive<'a>(
+    /// Adds `source`, to be copied into `dest_dir`, and everything below it
+    /// to `plan`. `fresh` means `dest_dir` was created by this copy, so
+    /// nothing in it can clash. `ancestors` holds the resolved paths of the
+    /// folders being copied above it, to stop symlink loops when links are
+    /// followed.
+    fn plan_entry<'a>(
         &'a self,
         source: &'a Path,
         dest_dir: &'a Path,
-        completed_files: &'a mut usize,
-        bytes_copied: &'a mut u64,
-        total_files: usize,
-        total_bytes: u64,
+        fresh: bool,
+        plan: &'a mut Plan,
+        ancestors: &'a mut Vec<PathBuf>,
     ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send + 'a>> {
         Box::pin(async move {
-            let dest_name = source.file_name().unwrap();
+            let dest_name = match self.target_name {
+                Some(ref name) if dest_dir == self.destination => name.as_os_str(),
+                _ => source.file_name()
+                    .ok_or_else(|| anyhow::anyhow!("Invalid file name: {}", source.display()))?,
+            };
             let dest_path = dest_dir.join(dest_name);
 
-            if source.is_file() {
-                self.copy_file(source, &dest_path).await?;
-                *completed_files += 1;
-                *bytes_copied += source.metadata()?.len();
-                
-                if let Some(ref tx) = self.progress_tx {
-                    let progress = CopyProgress {
-                        current_file: source.to_path_buf(),
-                        total_files,
-                        completed_files: *completed_files,
-                        bytes_copied: *bytes_copied,
-                        total_bytes,
-                    };
-                    let _ = tx.send(progress);
+            let mut metadata = self.source_vfs.symlink_metadata(source)?;
+            if metadata.file_type == FileType::SymbolicLink && !self.preserve.symlinks {
+                metadata = match self.source_vfs.metadata(source) {
+                    Ok(metadata) => metadata,
+                    Err(e) => {
+                        log::warn!("Skipping broken link {}: {}", source.display(), e);
+                        return Ok(());
+                    }
+                };
+            }
+            // Don't follow a link at the destination either
+            let existing = if fresh {
+                None
+            } else {
+                self.destination_vfs.symlink_metadata(&dest_path).ok()
+            };
+
+            if let Some(ref control) = self.control {
+                control.checkpoint().await?;
+            }
+
+            let resolved = if metadata.is_dir() && !self.preserve.symlinks {
+                let resolved = self.source_vfs.canonicalize(source)?;
+                if ancestors.contains(&resolved) {
+                    log::warn!("Skipping {}: it links back to a folder being copied", source.display());
+                    return Ok(());
                 }
-            } else if source.is_dir() {
-                fs::create_dir_all(&dest_path)?;
-
-                for entry in fs::read_dir(source)? {
-                    let entry = entry?;
-                    let entry_path = entry.path();
-                    
-                    self.copy_recursive(
-                        &entry_path,
+                Some(resolved)
+            } else {
+                None
+            };
+
+            // Folders are merged into existing folders; anything else clashing is a conflict
+            let target = match existing {
+                Some(ref existing) if metadata.is_dir() && existing.is_dir() => Some(dest_path.clone()),
+                Some(_) => {
+                    self.conflicts.resolve(
+                        self.source_vfs.as_ref(),
+                        source,
+                        self.destination_vfs.as_ref(),
                         &dest_path,
-                        completed_files,
-                        bytes_copied,
-                        total_files,
-                        total_bytes,
-                    ).await?;
+                    ).await?
                 }
+                None => Some(dest_path.clone()),
+            };
+            let Some(target) = target else {
+                return Ok(());
+            };
+
+            let merged = existing.is_some() && target == dest_path;
+            let replaces = merged && !(metadata.is_dir() && existing.as_ref().is_some_and(VfsMetadata::is_dir));
+
+            if dest_dir == self.destination && !merged {
+                plan.created.push((source.to_path_buf(), target.clone()));
             }
 
+            let entry = PlannedEntry {
+                source: source.to_path_buf(),
+                target,
+                metadata,
+                replaces,
+            };
+
+            if entry.metadata.is_file() {
+                plan.total_bytes += entry.metadata.len;
+                plan.files.push(entry);
+                return Ok(());
+            }
+            if !entry.metadata.is_dir() {
+                plan.entries.push(entry);
+                return Ok(());
+            }
+
+            let target = entry.target.clone();
+            plan.entries.push(entry);
+
+            ancestors.extend(resolved);
+            for entry_path in self.source_vfs.read_dir(source)? {
+                self.plan_entry(&entry_path, &target, !merged, plan, ancestors).await?;
+            }
+            if !self.preserve.symlinks {
+                ancestors.pop();
+            }
             Ok(())
         })
     }
 
-    async fn copy_file(&self, source: &Path, dest: &Path) -> Result<()> {
-        if dest.exists() && !self.overwrite {
-            return Err(anyhow::anyhow!("Destination file already exists: {}", dest.display()));
+    /// Creates a planned folder, link or special file. A folder replacing
+    /// something is returned with what it replaced, which can only go once
+    /// the folder has been filled.
+    fn create_entry(&self, entry: &PlannedEntry) -> Result<Option<SetAside>> {
+        let vfs = self.destination_vfs.as_ref();
+        let replaced = set_aside(vfs, entry)?;
+
+        match self.write_entry(entry) {
+            Ok(true) if entry.metadata.is_dir() => Ok(replaced),
+            Ok(true) => {
+                if let Some(replaced) = replaced {
+                    replaced.discard(vfs);
+                }
+                Ok(None)
+            }
+            // Nothing was written, so the target is free again
+            Ok(false) => {
+                if let Some(replaced) = replaced {
+                    replaced.restore(vfs);
+                }
+                Ok(None)
+            }
+            Err(e) => {
+                if let Some(replaced) = replaced {
+                    replaced.restore(vfs);
+                }
+                Err(e)
+            }
         }
+    }
 
-        // Ensure parent directory exists
-        if let Some(parent) = dest.parent() {
-            fs::create_dir_all(parent)?;
+    /// Writes a planned folder, link or special file. Returns whether
+    /// anything was written, as special files that can't be copied are skipped.
+    fn write_entry(&self, entry: &PlannedEntry) -> Result<bool> {
+        match entry.metadata.file_type {
+            FileType::Directory => {
+                self.destination_vfs.create_dir_all(&entry.target)?;
+                // Folder attributes are applied once their contents are in
+                return Ok(true);
+            }
+            FileType::SymbolicLink => {
+                let link = self.source_vfs.read_link(&entry.source)?;
+                self.destination_vfs.symlink(&link, &entry.target)?;
+            }
+            FileType::Other => {
+                let copied = match (self.source_vfs.local_path(&entry.source), self.destination_vfs.local_path(&entry.target)) {
+                    (Some(local_source), Some(local_target)) => {
+                        attributes::copy_special_file(&local_source, &local_target)
+                    }
+                    _ => Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "not on the local disk")),
+                };
+                if let Err(e) = copied {
+                    log::warn!("Skipping special file {}: {}", entry.source.display(), e);
+                    return Ok(false);
+                }
+            }
+            FileType::RegularFile => unreachable!("files are copied by the workers"),
+        }
+
+        preserve_attributes(self.source_vfs.as_ref(), self.destination_vfs.as_ref(), &self.preserve, entry);
+        Ok(true)
+    }
+
+    /// Deletes what folders replaced once they were filled. A folder left
+    /// incomplete by a failure or a stop is removed and what it replaced goes back.
+    fn finish_replacing(&self, replaced: Vec<(&PlannedEntry, SetAside)>, complete: bool, failures: &[(PathBuf, anyhow::Error)]) {
+        let vfs = self.destination_vfs.as_ref();
+        for (entry, replaced) in replaced {
+            if complete && !failures.iter().any(|(source, _)| source.starts_with(&entry.source)) {
+                replaced.discard(vfs);
+                continue;
+            }
+            if let Err(e) = vfs.remove_dir_all(&entry.target) {
+                log::warn!("Failed to remove partial copy {}: {}", entry.target.display(), e);
+            }
+            replaced.restore(vfs);
+        }
+    }
+
+    /// Copies `files` on up to `self.workers` blocking threads and returns
+    /// what failed.
+    async fn copy_contents(&self, files: Vec<PlannedEntry>, copier: &Arc<FileCopier>) -> Vec<(PathBuf, anyhow::Error)> {
+        let permits = Arc::new(Semaphore::new(self.workers));
+        let mut tasks = JoinSet::new();
+        let mut failures = Vec::new();
+
+        for entry in files {
+            let Ok(permit) = permits.clone().acquire_owned().await else {
+                break;
+            };
+            // Pick up finished files so a failure stops the queue early
+            while let Some(done) = tasks.try_join_next() {
+                copier.collect(done, &mut failures);
+            }
+            if copier.stop.load(Ordering::SeqCst) {
+                break;
+            }
+
+            let copier = copier.clone();
+            tasks.spawn_blocking(move || {
+                let _permit = permit;
+                let result = copier.copy_file(&entry);
+                (entry.source, result)
+            });
+        }
+
+        // Let running files finish or clean up before returning
+        while let Some(done) = tasks.join_next().await {
+            copier.collect(done, &mut failures);
+        }
+        failures
+    }
+
+    /// Turns what failed and what didn't verify into the copy's result.
+    fn outcome(&self, failures: Vec<(PathBuf, anyhow::Error)>, mismatches: Vec<ChecksumMismatch>) -> Result<()> {
+        let mut failures: Vec<_> = failures.into_iter().filter(|(_, e)| !e.is::<Stopped>()).collect();
+        if failures.iter().any(|(_, e)| e.is::<Cancelled>()) {
+            return Err(Cancelled.into());
+        }
+        if self.error_mode == CopyErrorMode::StopOnFirst {
+            failures.truncate(1);
+            if mismatches.is_empty() {
+                return match failures.pop() {
+                    Some((_, e)) => Err(e),
+                    None => Ok(()),
+                };
+            }
         }
 
-        fs::copy(source, dest)?;
+        if failures.is_empty() && mismatches.is_empty() {
+            return Ok(());
+        }
+        Err(CopyErrors {
+            failures: failures.into_iter().map(|(path, e)| (path, format!("{:#}", e))).collect(),
+            mismatches,
+        }.into())
+    }
+}
+
+/// Copies file contents for the workers of one [`CopyOperation`].
+struct FileCopier {
+    source_vfs: Arc<dyn Vfs>,
+    destination_vfs: Arc<dyn Vfs>,
+    preserve: PreserveOptions,
+    error_mode: CopyErrorMode,
+    verify: Option<HashAlgorithm>,
+    control: Option<JobControl>,
+    progress_tx: Option<mpsc::UnboundedSender<CopyProgress>>,
+    /// For waiting on `control` from the blocking workers.
+    runtime: Handle,
+    /// Set once the remaining files should not be copied.
+    stop: AtomicBool,
+    /// Copies that did not verify, reported once everything is copied.
+    mismatches: Mutex<Vec<ChecksumMismatch>>,
+    tally: Mutex<Tally>,
+}
+
+impl FileCopier {
+    /// Copies one file. Whatever it replaces is set aside until the copy is
+    /// written (and verified, if asked), and goes back if the copy fails or
+    /// doesn't verify. A copy that doesn't verify is recorded, not failed.
+    fn copy_file(&self, entry: &PlannedEntry) -> Result<()> {
+        self.check()?;
+        let vfs = self.destination_vfs.as_ref();
+        let replaced = set_aside(vfs, entry)?;
+        let result = self.write_file(entry);
+        if let Some(replaced) = replaced {
+            match result {
+                Ok(None) => replaced.discard(vfs),
+                Ok(Some(_)) => {
+                    // Keep what was there rather than a bad copy of it
+                    self.remove_copy(entry);
+                    replaced.restore(vfs);
+                }
+                Err(_) => replaced.restore(vfs),
+            }
+        }
+        if let Some(mismatch) = result? {
+            self.mismatches.lock().unwrap_or_else(|e| e.into_inner()).push(mismatch);
+        }
+        Ok(())
+    }
+
+    /// Writes and, if asked, verifies the copy of one file, returning how it
+    /// differs from its source. A failed copy is removed.
+    fn write_file(&self, entry: &PlannedEntry) -> Result<Option<ChecksumMismatch>> {
+        let mut file_bytes = 0;
+        let watched = self.control.is_some() || self.progress_tx.is_some();
+        let result = match (self.source_vfs.local_path(&entry.source), self.destination_vfs.local_path(&entry.target)) {
+            // Both ends on the local disk: clone or copy in the kernel where possible
+            (Some(local_source), Some(local_dest)) => {
+                local_copy::copy_contents(&local_source, &local_dest, &mut |bytes| {
+                    self.advance(entry, &mut file_bytes, bytes);
+                    self.check()
+                }).map(|_| ())
+            }
+            // Small files, or nobody watching or steering: copy in one go
+            _ if entry.metadata.len <= CHUNK_SIZE as u64 || !watched => self.copy_whole(entry, &mut file_bytes),
+            // Copy in chunks to report progress within the file and so the
+            // copy can be paused or cancelled part way through
+            _ => self.copy_chunked(entry, &mut file_bytes),
+        };
+        if let Err(e) = result {
+            self.remove_copy(entry);
+            return Err(e);
+        }
+
+        {
+            let mut tally = self.tally.lock().unwrap_or_else(|e| e.into_inner());
+            tally.completed_files += 1;
+            if tally.report_due() || tally.completed_files == tally.total_files {
+                self.report(entry, file_bytes, &mut tally);
+            }
+        }
+
+        preserve_attributes(self.source_vfs.as_ref(), self.destination_vfs.as_ref(), &self.preserve, entry);
+
+        match self.verify {
+            Some(algorithm) => self.verify(entry, algorithm),
+            None => Ok(None),
+        }
+    }
+
+    fn remove_copy(&self, entry: &PlannedEntry) {
+        match self.destination_vfs.remove_file(&entry.target) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                log::warn!("Failed to remove partial copy {}: {}", entry.target.display(), e);
+            }
+            _ => {}
+        }
+    }
+
+    /// Hashes the source and the copy and returns the mismatch if they differ.
+    fn verify(&self, entry: &PlannedEntry, algorithm: HashAlgorithm) -> Result<Option<ChecksumMismatch>> {
+        let expected = checksum::hash_file(self.source_vfs.as_ref(), &entry.source, algorithm, &mut |_| self.check())?;
+        let actual = checksum::hash_file(self.destination_vfs.as_ref(), &entry.target, algorithm, &mut |_| self.check())?;
+        if expected != actual {
+            return Ok(Some(ChecksumMismatch {
+                source: entry.source.clone(),
+                copy: entry.target.clone(),
+                algorithm,
+            }));
+        }
+        Ok(None)
+    }
+
+    fn copy_whole(&self, entry: &PlannedEntry, file_bytes: &mut u64) -> Result<()> {
+        let mut reader = self.source_vfs.open_read(&entry.source)?;
+        let mut writer = self.destination_vfs.open_write(&entry.target)?;
+        let copied = std::io::copy(&mut reader, &mut writer)?;
+        writer.flush()?;
+        self.advance(entry, file_bytes, copied);
+        Ok(())
+    }
+
+    fn copy_chunked(&self, entry: &PlannedEntry, file_bytes: &mut u64) -> Result<()> {
+        let mut reader = self.source_vfs.open_read(&entry.source)?;
+        let mut writer = self.destination_vfs.open_write(&entry.target)?;
+        let mut buffer = vec![0; CHUNK_SIZE];
+
+        loop {
+            self.check()?;
+
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..read])?;
+            self.advance(entry, file_bytes, read as u64);
+        }
+
+        writer.flush()?;
         Ok(())
     }
+
+    /// Waits while paused; fails once cancelled or stopped.
+    fn check(&self) -> Result<()> {
+        if let Some(ref control) = self.control {
+            self.runtime.block_on(control.checkpoint())?;
+        }
+        if self.stop.load(Ordering::SeqCst) {
+            return Err(Stopped.into());
+        }
+        Ok(())
+    }
+
+    /// Counts `bytes` more of `entry` as copied.
+    fn advance(&self, entry: &PlannedEntry, file_bytes: &mut u64, bytes: u64) {
+        *file_bytes += bytes;
+        let mut tally = self.tally.lock().unwrap_or_else(|e| e.into_inner());
+        tally.add_bytes(bytes);
+        if tally.report_due() {
+            self.report(entry, *file_bytes, &mut tally);
+        }
+    }
+
+    fn report(&self, entry: &PlannedEntry, file_bytes: u64, tally: &mut Tally) {
+        if let Some(ref tx) = self.progress_tx {
+            let progress = CopyProgress {
+                current_file: entry.source.clone(),
+                total_files: tally.total_files,
+                completed_files: tally.completed_files,
+                bytes_copied: tally.bytes_copied,
+                total_bytes: tally.total_bytes,
+                current_file_bytes: file_bytes,
+                current_file_size: entry.metadata.len,
+                bytes_per_second: tally.throughput.rate,
+                eta: tally.eta(),
+            };
+            let _ = tx.send(progress);
+            tally.last_report = Some(Instant::now());
+        }
+    }
+
+    /// Records the outcome of one worker, stopping the rest when the copy
+    /// was cancelled or should end at the first failure.
+    fn collect(&self, done: Result<(PathBuf, Result<()>), JoinError>, failures: &mut Vec<(PathBuf, anyhow::Error)>) {
+        let (source, result) = match done {
+            Ok(done) => done,
+            Err(e) => (PathBuf::new(), Err(anyhow::anyhow!("File copy worker failed: {}", e))),
+        };
+        if let Err(e) = result {
+            if self.error_mode == CopyErrorMode::StopOnFirst || e.is::<Cancelled>() {
+                self.stop.store(true, Ordering::SeqCst);
+            }
+            failures.push((source, e));
+        }
+    }
+}
+
+/// Moves whatever `entry` replaces out of its way.
+fn set_aside(vfs: &dyn Vfs, entry: &PlannedEntry) -> Result<Option<SetAside>> {
+    if !entry.replaces {
+        return Ok(None);
+    }
+    Ok(Some(SetAside::new(vfs, &entry.target)?))
+}
+
+/// Carries over what [`PreserveOptions`] asks for. Anything that can't be
+/// kept is logged rather than failing the copy.
+fn preserve_attributes(source_vfs: &dyn Vfs, destination_vfs: &dyn Vfs, preserve: &PreserveOptions, entry: &PlannedEntry) {
+    let (source, target) = (&entry.source, &entry.target);
+    if let (Some(local_source), Some(local_target)) = (source_vfs.local_path(source), destination_vfs.local_path(target)) {
+        if let Err(e) = attributes::copy_local_attributes(&local_source, &local_target, preserve) {
+            log::warn!("Failed to copy attributes of {}: {}", source.display(), e);
+        }
+    }
+
+    if preserve.timestamps && entry.metadata.file_type != FileType::SymbolicLink {
+        match destination_vfs.set_times(target, entry.metadata.accessed, entry.metadata.modified) {
+            Err(e) if e.kind() != std::io::ErrorKind::Unsupported => {
+                log::warn!("Failed to keep the times of {}: {}", target.display(), e);
+            }
+            _ => {}
+        }
+    }
 }
 
 pub async fn copy_files(
@@ -172,3 +918,311 @@ pub async fn copy_files(
 
     operation.execute().await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vfs::{MemoryFs, VfsReader, VfsWriter};
+    use std::io;
+
+    /// A [`MemoryFs`] whose files can't be read, to make copies fail.
+    struct Unreadable(MemoryFs);
+
+    impl Vfs for Unreadable {
+        fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+            self.0.read_dir(path)
+        }
+
+        fn metadata(&self, path: &Path) -> io::Result<VfsMetadata> {
+            self.0.metadata(path)
+        }
+
+        fn open_read(&self, _path: &Path) -> io::Result<Box<dyn VfsReader>> {
+            Err(io::Error::other("read failed"))
+        }
+
+        fn open_write(&self, path: &Path) -> io::Result<Box<dyn VfsWriter>> {
+            self.0.open_write(path)
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            self.0.rename(from, to)
+        }
+
+        fn remove_file(&self, path: &Path) -> io::Result<()> {
+            self.0.remove_file(path)
+        }
+
+        fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+            self.0.remove_dir_all(path)
+        }
+
+        fn create_dir(&self, path: &Path) -> io::Result<()> {
+            self.0.create_dir(path)
+        }
+
+        fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+            self.0.create_dir_all(path)
+        }
+    }
+
+    /// A [`MemoryFs`] that garbles what is written to files named `bad*`.
+    struct Corrupting(MemoryFs);
+
+    impl Vfs for Corrupting {
+        fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+            self.0.read_dir(path)
+        }
+
+        fn metadata(&self, path: &Path) -> io::Result<VfsMetadata> {
+            self.0.metadata(path)
+        }
+
+        fn open_read(&self, path: &Path) -> io::Result<Box<dyn VfsReader>> {
+            self.0.open_read(path)
+        }
+
+        fn open_write(&self, path: &Path) -> io::Result<Box<dyn VfsWriter>> {
+            let mut writer = self.0.open_write(path)?;
+            if path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("bad")) {
+                writer.write_all(b"garbage")?;
+            }
+            Ok(writer)
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            self.0.rename(from, to)
+        }
+
+        fn remove_file(&self, path: &Path) -> io::Result<()> {
+            self.0.remove_file(path)
+        }
+
+        fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+            self.0.remove_dir_all(path)
+        }
+
+        fn create_dir(&self, path: &Path) -> io::Result<()> {
+            self.0.create_dir(path)
+        }
+
+        fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+            self.0.create_dir_all(path)
+        }
+    }
+
+    fn copy(vfs: &MemoryFs, source_vfs: Arc<dyn Vfs>, sources: &[&str], policy: ConflictPolicy) -> CopyOperation {
+        CopyOperation::new(sources.iter().map(PathBuf::from).collect(), PathBuf::from("/dst"))
+            .with_source_vfs(source_vfs)
+            .with_destination_vfs(Arc::new(vfs.clone()))
+            .with_conflict_policy(policy)
+    }
+
+    fn names(vfs: &MemoryFs, path: &str) -> Vec<String> {
+        let mut names: Vec<String> = vfs.read_dir(Path::new(path)).unwrap().iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn content(vfs: &MemoryFs, path: &str) -> Vec<u8> {
+        crate::core::vfs::read(vfs, Path::new(path)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn copies_folders_and_files() {
+        let vfs = MemoryFs::new()
+            .with_file("/src/a.txt", "a")
+            .with_file("/src/folder/b.txt", "b")
+            .with_dir("/src/folder/empty")
+            .with_dir("/dst");
+        let operation = copy(&vfs, Arc::new(vfs.clone()), &["/src/a.txt", "/src/folder"], ConflictPolicy::Ask);
+        operation.execute().await.unwrap();
+
+        assert_eq!(names(&vfs, "/dst"), ["a.txt", "folder"]);
+        assert_eq!(names(&vfs, "/dst/folder"), ["b.txt", "empty"]);
+        assert_eq!(content(&vfs, "/dst/folder/b.txt"), b"b");
+        assert_eq!(operation.created().len(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn copies_many_small_files_on_workers() {
+        let mut vfs = MemoryFs::new().with_dir("/dst");
+        for folder in 0..10 {
+            for file in 0..30 {
+                vfs = vfs.with_file(format!("/src/{folder}/{file}.txt"), format!("{folder}-{file}"));
+            }
+        }
+        let total_bytes: u64 = (0..10).flat_map(|folder| (0..30).map(move |file| format!("{folder}-{file}").len() as u64)).sum();
+
+        let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+        copy(&vfs, Arc::new(vfs.clone()), &["/src"], ConflictPolicy::Ask)
+            .with_workers(8)
+            .with_progress_callback(progress_tx)
+            .execute()
+            .await
+            .unwrap();
+
+        for folder in 0..10 {
+            assert_eq!(names(&vfs, &format!("/dst/src/{folder}")).len(), 30);
+            assert_eq!(content(&vfs, &format!("/dst/src/{folder}/29.txt")), format!("{folder}-29").as_bytes());
+        }
+        let mut last = None;
+        while let Ok(progress) = progress_rx.try_recv() {
+            last = Some(progress);
+        }
+        let last = last.unwrap();
+        assert_eq!((last.completed_files, last.total_files), (300, 300));
+        assert_eq!((last.bytes_copied, last.total_bytes), (total_bytes, total_bytes));
+    }
+
+    #[tokio::test]
+    async fn collect_all_reports_every_failure() {
+        let vfs = MemoryFs::new()
+            .with_file("/src/folder/a.txt", "a")
+            .with_file("/src/folder/b.txt", "b")
+            .with_file("/src/c.txt", "c")
+            .with_dir("/dst");
+        let error = copy(&vfs, Arc::new(Unreadable(vfs.clone())), &["/src/folder", "/src/c.txt"], ConflictPolicy::Ask)
+            .with_error_mode(CopyErrorMode::CollectAll)
+            .execute()
+            .await
+            .unwrap_err();
+
+        let errors = error.downcast_ref::<CopyErrors>().unwrap();
+        assert_eq!(errors.failures.len(), 3);
+        // The folder is still made; no half-written files are left behind
+        assert_eq!(names(&vfs, "/dst"), ["folder"]);
+        assert!(names(&vfs, "/dst/folder").is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn verify_reports_every_mismatch_at_the_end() {
+        for algorithm in HashAlgorithm::ALL {
+            let vfs = MemoryFs::new()
+                .with_file("/src/bad1.txt", "one")
+                .with_file("/src/good.txt", "good")
+                .with_file("/src/bad2.txt", "two")
+                .with_file("/src2/bad3.txt", "new")
+                .with_file("/dst/bad3.txt", "old");
+            let sources = ["/src/bad1.txt", "/src/good.txt", "/src/bad2.txt", "/src2/bad3.txt"];
+            let error = copy(&vfs, Arc::new(vfs.clone()), &sources, ConflictPolicy::Overwrite)
+                .with_destination_vfs(Arc::new(Corrupting(vfs.clone())))
+                .with_verify(algorithm)
+                .execute()
+                .await
+                .unwrap_err();
+
+            // The first mismatch doesn't stop the copy, and isn't an I/O failure
+            let errors = error.downcast_ref::<CopyErrors>().unwrap();
+            assert!(errors.failures.is_empty());
+            let mut mismatched: Vec<_> = errors.mismatches.iter().map(|mismatch| mismatch.copy.clone()).collect();
+            mismatched.sort();
+            assert_eq!(mismatched, [PathBuf::from("/dst/bad1.txt"), PathBuf::from("/dst/bad2.txt"), PathBuf::from("/dst/bad3.txt")]);
+            assert!(errors.mismatches.iter().all(|mismatch| mismatch.algorithm == algorithm));
+
+            assert_eq!(content(&vfs, "/dst/good.txt"), b"good");
+            // Bad copies stay for inspection, but don't replace what was there
+            assert_eq!(content(&vfs, "/dst/bad1.txt"), b"garbageone");
+            assert_eq!(content(&vfs, "/dst/bad3.txt"), b"old");
+        }
+    }
+
+    #[tokio::test]
+    async fn merges_into_existing_folders() {
+        let vfs = MemoryFs::new()
+            .with_file("/src/folder/new.txt", "new")
+            .with_file("/dst/folder/kept.txt", "kept");
+        let operation = copy(&vfs, Arc::new(vfs.clone()), &["/src/folder"], ConflictPolicy::Ask);
+        operation.execute().await.unwrap();
+
+        assert_eq!(names(&vfs, "/dst/folder"), ["kept.txt", "new.txt"]);
+        assert!(operation.created().is_empty());
+    }
+
+    #[tokio::test]
+    async fn overwrite_replaces_file_without_leftovers() {
+        let vfs = MemoryFs::new()
+            .with_file("/src/a.txt", "new")
+            .with_file("/dst/a.txt", "old");
+        copy(&vfs, Arc::new(vfs.clone()), &["/src/a.txt"], ConflictPolicy::Overwrite).execute().await.unwrap();
+
+        assert_eq!(names(&vfs, "/dst"), ["a.txt"]);
+        assert_eq!(content(&vfs, "/dst/a.txt"), b"new");
+    }
+
+    #[tokio::test]
+    async fn overwrite_replaces_folder_with_file() {
+        let vfs = MemoryFs::new()
+            .with_file("/src/a", "file")
+            .with_file("/dst/a/inside.txt", "old");
+        copy(&vfs, Arc::new(vfs.clone()), &["/src/a"], ConflictPolicy::Overwrite).execute().await.unwrap();
+
+        assert_eq!(names(&vfs, "/dst"), ["a"]);
+        assert_eq!(content(&vfs, "/dst/a"), b"file");
+    }
+
+    #[tokio::test]
+    async fn failed_overwrite_keeps_the_original_file() {
+        let vfs = MemoryFs::new()
+            .with_file("/src/a.txt", "new")
+            .with_file("/dst/a.txt", "old");
+        let result = copy(&vfs, Arc::new(Unreadable(vfs.clone())), &["/src/a.txt"], ConflictPolicy::Overwrite)
+            .execute()
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(names(&vfs, "/dst"), ["a.txt"]);
+        assert_eq!(content(&vfs, "/dst/a.txt"), b"old");
+    }
+
+    #[tokio::test]
+    async fn failed_overwrite_of_file_with_folder_puts_the_file_back() {
+        let vfs = MemoryFs::new()
+            .with_file("/src/a/inside.txt", "new")
+            .with_file("/dst/a", "old");
+        let result = copy(&vfs, Arc::new(Unreadable(vfs.clone())), &["/src/a"], ConflictPolicy::Overwrite)
+            .execute()
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(names(&vfs, "/dst"), ["a"]);
+        assert_eq!(content(&vfs, "/dst/a"), b"old");
+    }
+
+    #[tokio::test]
+    async fn skip_leaves_existing_files_alone() {
+        let vfs = MemoryFs::new()
+            .with_file("/src/a.txt", "new")
+            .with_file("/dst/a.txt", "old");
+        let operation = copy(&vfs, Arc::new(vfs.clone()), &["/src/a.txt"], ConflictPolicy::Skip);
+        operation.execute().await.unwrap();
+
+        assert_eq!(content(&vfs, "/dst/a.txt"), b"old");
+        assert!(operation.created().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn keeps_local_permissions_only_when_asked() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("script.sh");
+        std::fs::write(&source, "#!/bin/sh").unwrap();
+        std::fs::set_permissions(&source, std::fs::Permissions::from_mode(0o751)).unwrap();
+
+        for permissions in [true, false] {
+            let destination = dir.path().join(format!("copy-{permissions}"));
+            CopyOperation::new(vec![source.clone()], destination.clone())
+                .with_preserve(PreserveOptions { permissions, ..PreserveOptions::default() })
+                .execute()
+                .await
+                .unwrap();
+
+            let mode = std::fs::metadata(destination.join("script.sh")).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode == 0o751, permissions);
+        }
+    }
+}
@@ -1,24 +1,34 @@
 use std::path::{Path, PathBuf};
 use anyhow::Result;
 
+use crate::core::vfs::{self, LocalFs, Vfs};
+
 pub async fn create_folder(parent: &Path, name: &str) -> Result<PathBuf> {
+    create_folder_in(&LocalFs, parent, name).await
+}
+
+pub async fn create_folder_in<V: Vfs + ?Sized>(vfs: &V, parent: &Path, name: &str) -> Result<PathBuf> {
     let new_path = parent.join(name);
     
-    if new_path.exists() {
+    if vfs.exists(&new_path) {
         return Err(anyhow::anyhow!("A folder with that name already exists"));
     }
     
-    std::fs::create_dir(&new_path)?;
+    vfs.create_dir(&new_path)?;
     Ok(new_path)
 }
 
 pub async fn create_file(parent: &Path, name: &str) -> Result<PathBuf> {
+    create_file_in(&LocalFs, parent, name).await
+}
+
+pub async fn create_file_in<V: Vfs + ?Sized>(vfs: &V, parent: &Path, name: &str) -> Result<PathBuf> {
     let new_path = parent.join(name);
     
-    if new_path.exists() {
+    if vfs.exists(&new_path) {
         return Err(anyhow::anyhow!("A file with that name already exists"));
     }
     
-    std::fs::write(&new_path, "")?;
+    vfs::write(vfs, &new_path, b"")?;
     Ok(new_path)
 }
@@ -0,0 +1,152 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+
+use crate::core::duplicates::DuplicateFile;
+use crate::core::vfs::LocalFs;
+use crate::operations::checksum::{self, Checksum, HashAlgorithm};
+
+/// Outcome of replacing duplicates with hard links.
+#[derive(Debug, Default)]
+pub struct LinkReport {
+    pub linked: usize,
+    pub bytes_freed: u64,
+    /// Duplicates left alone, with the reason.
+    pub failures: Vec<(PathBuf, String)>,
+}
+
+/// Replaces `duplicate` with a hard link to `original`, both on the local
+/// disk. The link is made under a temporary name and renamed over the
+/// duplicate, so a failure leaves the duplicate as it was. Fails if the files
+/// are on different drives, or either was modified since it was compared or
+/// no longer hashes the same as the other.
+pub fn replace_with_hard_link(original: &DuplicateFile, duplicate: &DuplicateFile) -> Result<u64> {
+    let original_metadata = fs::metadata(&original.path)?;
+    let duplicate_metadata = fs::symlink_metadata(&duplicate.path)?;
+    if !duplicate_metadata.is_file() || duplicate_metadata.len() != original_metadata.len() {
+        return Err(anyhow::anyhow!("{} has changed since it was compared", duplicate.path.display()));
+    }
+    for (file, metadata) in [(original, &original_metadata), (duplicate, &duplicate_metadata)] {
+        if modified(metadata) != file.modified {
+            return Err(anyhow::anyhow!("{} has changed since it was compared", file.path.display()));
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if original_metadata.dev() != duplicate_metadata.dev() {
+            return Err(anyhow::anyhow!("Hard links can't cross drives"));
+        }
+        if original_metadata.ino() == duplicate_metadata.ino() {
+            // Already the same file
+            return Ok(0);
+        }
+    }
+
+    // Same size and time don't rule out edits, so compare the contents once more
+    if hash(&original.path)? != hash(&duplicate.path)? {
+        return Err(anyhow::anyhow!("{} no longer matches {}", duplicate.path.display(), original.path.display()));
+    }
+
+    let (Some(parent), Some(name)) = (duplicate.path.parent(), duplicate.path.file_name()) else {
+        return Err(anyhow::anyhow!("Invalid file name: {}", duplicate.path.display()));
+    };
+    let mut temp_name = name.to_os_string();
+    temp_name.push(format!(".chex-link-{}", uuid::Uuid::new_v4().simple()));
+    let temp = parent.join(temp_name);
+
+    fs::hard_link(&original.path, &temp)?;
+    if let Err(e) = fs::rename(&temp, &duplicate.path) {
+        let _ = fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    Ok(duplicate_metadata.len())
+}
+
+/// Replaces each duplicate with a hard link to its original, as
+/// `(original, duplicate)` pairs, carrying on past failures.
+pub fn link_duplicates(pairs: &[(DuplicateFile, DuplicateFile)]) -> LinkReport {
+    let mut report = LinkReport::default();
+    for (original, duplicate) in pairs {
+        match replace_with_hard_link(original, duplicate) {
+            Ok(freed) => {
+                report.linked += 1;
+                report.bytes_freed += freed;
+            }
+            Err(e) => {
+                log::warn!("Failed to link {}: {}", duplicate.path.display(), e);
+                report.failures.push((duplicate.path.clone(), e.to_string()));
+            }
+        }
+    }
+    report
+}
+
+/// When the file was last modified, as [`DuplicateFile::modified`] records it.
+fn modified(metadata: &fs::Metadata) -> chrono::DateTime<chrono::Utc> {
+    metadata.modified().map(chrono::DateTime::from).unwrap_or_default()
+}
+
+fn hash(path: &Path) -> Result<Checksum> {
+    checksum::hash_file(&LocalFs, path, HashAlgorithm::Blake3, &mut |_| Ok(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    fn compared(path: PathBuf) -> DuplicateFile {
+        let modified = modified(&fs::metadata(&path).unwrap());
+        DuplicateFile { path, modified }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_unchanged_duplicates() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), "same").unwrap();
+        fs::write(dir.path().join("b"), "same").unwrap();
+        let (a, b) = (compared(dir.path().join("a")), compared(dir.path().join("b")));
+
+        let report = link_duplicates(&[(a.clone(), b.clone())]);
+        assert_eq!((report.linked, report.bytes_freed), (1, 4));
+        assert_eq!(fs::metadata(&a.path).unwrap().ino(), fs::metadata(&b.path).unwrap().ino());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn refuses_files_modified_since_compared() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), "same").unwrap();
+        fs::write(dir.path().join("b"), "same").unwrap();
+        let (a, b) = (compared(dir.path().join("a")), compared(dir.path().join("b")));
+
+        let file = fs::File::options().write(true).open(&b.path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+
+        assert!(replace_with_hard_link(&a, &b).is_err());
+        assert_eq!(fs::read(&b.path).unwrap(), b"same");
+    }
+
+    #[test]
+    fn refuses_files_edited_without_a_new_time() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), "same").unwrap();
+        fs::write(dir.path().join("b"), "same").unwrap();
+        let (a, b) = (compared(dir.path().join("a")), compared(dir.path().join("b")));
+
+        // Same size, and the old time put back
+        fs::write(&b.path, "diff").unwrap();
+        let file = fs::File::options().write(true).open(&b.path).unwrap();
+        file.set_modified(b.modified.into()).unwrap();
+
+        let report = link_duplicates(&[(a, b.clone())]);
+        assert_eq!(report.linked, 0);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(fs::read(&b.path).unwrap(), b"diff");
+    }
+}
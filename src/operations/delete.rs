@@ -1,18 +1,27 @@
 use std::path::{Path, PathBuf};
 use anyhow::Result;
 
+use crate::core::vfs::{LocalFs, Vfs};
+
 pub async fn delete_files(paths: Vec<PathBuf>, use_trash: bool) -> Result<()> {
+    if !use_trash {
+        return delete_files_in(&LocalFs, paths).await;
+    }
+
+    for path in paths {
+        // Move to trash/recycle bin
+        trash::delete(&path)?;
+    }
+    Ok(())
+}
+
+/// Permanently deletes `paths` from `vfs`. Only the host filesystem has a trash.
+pub async fn delete_files_in<V: Vfs + ?Sized>(vfs: &V, paths: Vec<PathBuf>) -> Result<()> {
     for path in paths {
-        if use_trash {
-            // Move to trash/recycle bin
-            trash::delete(&path)?;
+        if vfs.symlink_metadata(&path)?.is_dir() {
+            vfs.remove_dir_all(&path)?;
         } else {
-            // Permanently delete
-            if path.is_dir() {
-                std::fs::remove_dir_all(&path)?;
-            } else {
-                std::fs::remove_file(&path)?;
-            }
+            vfs.remove_file(&path)?;
         }
     }
     Ok(())
@@ -1,62 +1,260 @@
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
 use anyhow::Result;
-use std::fs::File;
+use std::io::Write;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+
+use crate::core::vfs::{ArchiveKind, LocalFs, Vfs};
+use crate::operations::control::JobControl;
+use crate::operations::copy::CopyProgress;
 
 pub async fn extract_zip(archive_path: &Path, destination: &Path) -> Result<()> {
-    let file = File::open(archive_path)?;
-    let mut archive = zip::ZipArchive::new(file)?;
-
-    std::fs::create_dir_all(destination)?;
-
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
-        let outpath = destination.join(file.mangled_name());
-
-        if file.name().ends_with('/') {
-            // Directory
-            std::fs::create_dir_all(&outpath)?;
-        } else {
-            // File
-            if let Some(parent) = outpath.parent() {
-                std::fs::create_dir_all(parent)?;
+    ExtractOperation::new(archive_path.to_path_buf(), destination.to_path_buf())
+        .with_kind(ArchiveKind::Zip)
+        .execute()
+        .await
+}
+
+pub async fn extract_tar(archive_path: &Path, destination: &Path) -> Result<()> {
+    ExtractOperation::new(archive_path.to_path_buf(), destination.to_path_buf())
+        .with_kind(ArchiveKind::Tar)
+        .execute()
+        .await
+}
+
+pub async fn extract_archive(archive_path: &Path, destination: &Path) -> Result<()> {
+    ExtractOperation::new(archive_path.to_path_buf(), destination.to_path_buf())
+        .execute()
+        .await
+}
+
+/// Unpacks a ZIP or TAR archive into a folder, one entry at a time.
+///
+/// The archive is read on a blocking thread. Before each entry the
+/// extraction reports progress and waits while its [`JobControl`] is paused,
+/// stopping there once cancelled.
+#[derive(Clone)]
+pub struct ExtractOperation {
+    archive: PathBuf,
+    destination: PathBuf,
+    kind: Option<ArchiveKind>,
+    vfs: Arc<dyn Vfs>,
+    progress_tx: Option<mpsc::UnboundedSender<CopyProgress>>,
+    control: Option<JobControl>,
+}
+
+impl ExtractOperation {
+    pub fn new(archive: PathBuf, destination: PathBuf) -> Self {
+        Self {
+            kind: ArchiveKind::from_path(&archive),
+            archive,
+            destination,
+            vfs: Arc::new(LocalFs),
+            progress_tx: None,
+            control: None,
+        }
+    }
+
+    /// Reads the archive as `kind`, whatever its extension.
+    pub fn with_kind(mut self, kind: ArchiveKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Reads the archive from `vfs` and extracts into it, instead of the local disk.
+    pub fn with_vfs(mut self, vfs: Arc<dyn Vfs>) -> Self {
+        self.vfs = vfs;
+        self
+    }
+
+    /// Reports the entries extracted so far, as files.
+    pub fn with_progress_callback(mut self, tx: mpsc::UnboundedSender<CopyProgress>) -> Self {
+        self.progress_tx = Some(tx);
+        self
+    }
+
+    /// Lets `control` pause or cancel the extraction between entries.
+    pub fn with_control(mut self, control: JobControl) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    pub async fn execute(&self) -> Result<()> {
+        let Some(kind) = self.kind else {
+            return Err(anyhow::anyhow!("Unsupported archive format: {}", self.archive.display()));
+        };
+
+        let operation = self.clone();
+        let runtime = Handle::current();
+        tokio::task::spawn_blocking(move || match kind {
+            ArchiveKind::Zip => operation.extract_zip(&runtime),
+            ArchiveKind::Tar => operation.extract_tar(&runtime),
+        }).await?
+    }
+
+    fn extract_zip(&self, runtime: &Handle) -> Result<()> {
+        let vfs = self.vfs.as_ref();
+        let file = vfs.open_read(&self.archive)?;
+        let mut archive = zip::ZipArchive::new(file)?;
+
+        vfs.create_dir_all(&self.destination)?;
+
+        let total = archive.len();
+        for i in 0..total {
+            let mut file = archive.by_index(i)?;
+            let outpath = self.destination.join(file.mangled_name());
+            self.step(runtime, &outpath, i, total)?;
+
+            if file.name().ends_with('/') {
+                // Directory
+                vfs.create_dir_all(&outpath)?;
+            } else {
+                // File
+                if let Some(parent) = outpath.parent() {
+                    vfs.create_dir_all(parent)?;
+                }
+
+                let mut outfile = vfs.open_write(&outpath)?;
+                std::io::copy(&mut file, &mut outfile)?;
+                outfile.flush()?;
+            }
+
+            // Set permissions on Unix-like systems
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                if let (Some(mode), Some(local_path)) = (file.unix_mode(), vfs.local_path(&outpath)) {
+                    std::fs::set_permissions(&local_path, std::fs::Permissions::from_mode(mode))?;
+                }
             }
-            
-            let mut outfile = File::create(&outpath)?;
-            std::io::copy(&mut file, &mut outfile)?;
         }
 
-        // Set permissions on Unix-like systems
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            if let Some(mode) = file.unix_mode() {
-                std::fs::set_permissions(&outpath, std::fs::Permissions::from_mode(mode))?;
+        self.report(&self.destination, total, total);
+        Ok(())
+    }
+
+    fn extract_tar(&self, runtime: &Handle) -> Result<()> {
+        let vfs = self.vfs.as_ref();
+        // Only the headers are read to count the entries; the data is seeked over
+        let total = tar::Archive::new(vfs.open_read(&self.archive)?).entries_with_seek()?.count();
+        let mut archive = tar::Archive::new(vfs.open_read(&self.archive)?);
+
+        vfs.create_dir_all(&self.destination)?;
+
+        // On the host filesystem tar can restore links, modes and times itself
+        let local_destination = vfs.local_path(&self.destination);
+
+        for (index, entry) in archive.entries()?.enumerate() {
+            let mut entry = entry?;
+            let relative_path = entry.path()?.into_owned();
+            let outpath = self.destination.join(&relative_path);
+            self.step(runtime, &outpath, index, total)?;
+
+            if let Some(ref local_destination) = local_destination {
+                // Entries that would land outside the destination are refused
+                if !entry.unpack_in(local_destination)? {
+                    log::warn!("Skipping unsafe archive entry: {}", relative_path.display());
+                }
+                continue;
             }
+
+            // Never write outside the destination
+            if relative_path.components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir)) {
+                log::warn!("Skipping unsafe archive entry: {}", relative_path.display());
+                continue;
+            }
+
+            let entry_type = entry.header().entry_type();
+
+            if entry_type.is_dir() {
+                vfs.create_dir_all(&outpath)?;
+            } else if entry_type.is_file() {
+                if let Some(parent) = outpath.parent() {
+                    vfs.create_dir_all(parent)?;
+                }
+                let mut outfile = vfs.open_write(&outpath)?;
+                std::io::copy(&mut entry, &mut outfile)?;
+                outfile.flush()?;
+            }
+            // Links and special files need a real filesystem and are skipped
         }
+
+        self.report(&self.destination, total, total);
+        Ok(())
     }
 
-    Ok(())
+    /// Waits while paused, failing once cancelled, then reports `current`
+    /// as the next of `total` entries.
+    fn step(&self, runtime: &Handle, current: &Path, completed: usize, total: usize) -> Result<()> {
+        if let Some(ref control) = self.control {
+            runtime.block_on(control.checkpoint())?;
+        }
+        self.report(current, completed, total);
+        Ok(())
+    }
+
+    fn report(&self, current_file: &Path, completed_files: usize, total_files: usize) {
+        if let Some(ref tx) = self.progress_tx {
+            let _ = tx.send(CopyProgress {
+                current_file: current_file.to_path_buf(),
+                total_files,
+                completed_files,
+                ..CopyProgress::default()
+            });
+        }
+    }
 }
 
-pub async fn extract_tar(archive_path: &Path, destination: &Path) -> Result<()> {
-    let file = File::open(archive_path)?;
-    let mut archive = tar::Archive::new(file);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vfs::{self, MemoryFs};
+    use crate::operations::control::Cancelled;
 
-    std::fs::create_dir_all(destination)?;
-    archive.unpack(destination)?;
+    fn zip_archive() -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            writer.start_file(name, zip::write::SimpleFileOptions::default()).unwrap();
+            writer.write_all(name.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
 
-    Ok(())
-}
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reports_each_extracted_entry() {
+        let vfs = Arc::new(MemoryFs::new().with_file("/a.zip", zip_archive()));
+        let (tx, mut rx) = mpsc::unbounded_channel();
 
-pub async fn extract_archive(archive_path: &Path, destination: &Path) -> Result<()> {
-    let extension = archive_path.extension()
-        .and_then(|ext| ext.to_str())
-        .unwrap_or("")
-        .to_lowercase();
-
-    match extension.as_str() {
-        "zip" => extract_zip(archive_path, destination).await,
-        "tar" => extract_tar(archive_path, destination).await,
-        _ => Err(anyhow::anyhow!("Unsupported archive format: {}", extension)),
+        ExtractOperation::new(PathBuf::from("/a.zip"), PathBuf::from("/out"))
+            .with_vfs(vfs.clone())
+            .with_progress_callback(tx)
+            .execute()
+            .await
+            .unwrap();
+
+        assert_eq!(vfs::read(vfs.as_ref(), Path::new("/out/c.txt")).unwrap(), b"c.txt");
+        let mut reported = Vec::new();
+        while let Ok(progress) = rx.try_recv() {
+            reported.push((progress.completed_files, progress.total_files));
+        }
+        assert_eq!(reported, [(0, 3), (1, 3), (2, 3), (3, 3)]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stops_extracting_once_cancelled() {
+        let vfs = Arc::new(MemoryFs::new().with_file("/a.zip", zip_archive()));
+        let control = JobControl::new();
+        control.cancel();
+
+        let error = ExtractOperation::new(PathBuf::from("/a.zip"), PathBuf::from("/out"))
+            .with_vfs(vfs.clone())
+            .with_control(control)
+            .execute()
+            .await
+            .unwrap_err();
+
+        assert!(error.is::<Cancelled>());
+        assert!(!vfs.exists(Path::new("/out/a.txt")));
     }
 }
@@ -0,0 +1,642 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use anyhow::Result;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+
+use crate::core::vfs::Vfs;
+use crate::operations::checksum::HashAlgorithm;
+use crate::operations::conflict::{ConflictHandler, ConflictPolicy, ConflictRequest, SetAside};
+use crate::operations::control::{Cancelled, JobControl};
+use crate::operations::copy::{self, CopyErrorMode, CopyOperation, CopyProgress};
+use crate::operations::journal::{self, JournalAction, OperationJournal};
+use crate::operations::extract::ExtractOperation;
+use crate::operations::rename::{self, MoveOperation};
+use crate::operations::{create, delete};
+
+pub type JobId = u64;
+
+#[derive(Debug, Clone)]
+pub enum JobKind {
+    Copy { sources: Vec<PathBuf>, destination: PathBuf },
+    Move { sources: Vec<PathBuf>, destination: PathBuf },
+    /// Symbolic links in `destination` to each of `sources`.
+    Link { sources: Vec<PathBuf>, destination: PathBuf },
+    /// Local items go to the trash; items on other filesystems are removed for good.
+    Delete { paths: Vec<PathBuf> },
+    Extract { archive: PathBuf, destination: PathBuf },
+    /// Gives `path` the new file name `name` within its folder.
+    Rename { path: PathBuf, name: String },
+    CreateFolder { parent: PathBuf, name: String },
+    CreateFile { parent: PathBuf, name: String },
+}
+
+impl JobKind {
+    pub fn description(&self) -> String {
+        let count = |paths: &[PathBuf]| match paths {
+            [path] => path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.display().to_string()),
+            _ => format!("{} items", paths.len()),
+        };
+
+        match self {
+            JobKind::Copy { sources, destination } => {
+                format!("Copy {} to {}", count(sources), destination.display())
+            }
+            JobKind::Move { sources, destination } => {
+                format!("Move {} to {}", count(sources), destination.display())
+            }
+            JobKind::Link { sources, destination } => {
+                format!("Link {} in {}", count(sources), destination.display())
+            }
+            JobKind::Delete { paths } => format!("Delete {}", count(paths)),
+            JobKind::Extract { archive, destination } => {
+                format!("Extract {} to {}", count(std::slice::from_ref(archive)), destination.display())
+            }
+            JobKind::Rename { path, name } => {
+                format!("Rename {} to {}", count(std::slice::from_ref(path)), name)
+            }
+            JobKind::CreateFolder { parent, name } => format!("New folder {} in {}", name, parent.display()),
+            JobKind::CreateFile { parent, name } => format!("New file {} in {}", name, parent.display()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobState {
+    Queued,
+    Running,
+    Paused,
+    Completed,
+    Failed(String),
+    Cancelled,
+}
+
+impl JobState {
+    /// Whether the job has started and not yet finished.
+    pub fn is_active(&self) -> bool {
+        matches!(self, JobState::Running | JobState::Paused)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobState::Completed | JobState::Failed(_) | JobState::Cancelled)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: JobId,
+    pub kind: JobKind,
+    pub state: JobState,
+    pub progress: Option<CopyProgress>,
+    control: JobControl,
+}
+
+#[derive(Debug)]
+struct Queue {
+    jobs: Vec<Job>,
+    next_id: JobId,
+    max_parallel: usize,
+    /// Applied to copy and move jobs as they start.
+    conflict_policy: ConflictPolicy,
+    /// Files each copy job copies at the same time.
+    copy_workers: usize,
+    copy_error_mode: CopyErrorMode,
+    /// Checksum copy and move jobs compare their copies with, if any.
+    verify: Option<HashAlgorithm>,
+    /// Set when a job finishes, cleared by [`JobManager::take_finished`].
+    finished: bool,
+}
+
+/// Queue of file operations, from copies and moves to renames, run in the
+/// background.
+///
+/// Jobs start in queue order, at most `max_parallel` at a time (one by default,
+/// so transfers to the same disk don't compete), and can be paused, resumed,
+/// cancelled or reordered while waiting. Clones share the same queue.
+#[derive(Clone)]
+pub struct JobManager {
+    queue: Arc<Mutex<Queue>>,
+    runtime: Handle,
+    vfs: Arc<dyn Vfs>,
+    journal: Option<Arc<tokio::sync::Mutex<OperationJournal>>>,
+    conflict_resolver: Option<mpsc::UnboundedSender<ConflictRequest>>,
+}
+
+impl JobManager {
+    /// Creates a manager that runs jobs on `runtime` against `vfs`.
+    pub fn new(runtime: Handle, vfs: Arc<dyn Vfs>) -> Self {
+        Self {
+            queue: Arc::new(Mutex::new(Queue {
+                jobs: Vec::new(),
+                next_id: 1,
+                max_parallel: 1,
+                conflict_policy: ConflictPolicy::Ask,
+                copy_workers: copy::DEFAULT_WORKERS,
+                copy_error_mode: CopyErrorMode::default(),
+                verify: None,
+                finished: false,
+            })),
+            runtime,
+            vfs,
+            journal: None,
+            conflict_resolver: None,
+        }
+    }
+
+    /// Records completed jobs in `journal` so they can be undone.
+    pub fn with_journal(mut self, journal: Arc<tokio::sync::Mutex<OperationJournal>>) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Sends conflicts from copy and move jobs to `resolver` to be answered.
+    pub fn with_conflict_resolver(mut self, resolver: mpsc::UnboundedSender<ConflictRequest>) -> Self {
+        self.conflict_resolver = Some(resolver);
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn enqueue(&self, kind: JobKind) -> JobId {
+        let id = {
+            let mut queue = self.lock();
+            let id = queue.next_id;
+            queue.next_id += 1;
+            queue.jobs.push(Job {
+                id,
+                kind,
+                state: JobState::Queued,
+                progress: None,
+                control: JobControl::new(),
+            });
+            id
+        };
+
+        self.schedule();
+        id
+    }
+
+    /// A snapshot of every job, in queue order.
+    pub fn jobs(&self) -> Vec<Job> {
+        self.lock().jobs.clone()
+    }
+
+    pub fn max_parallel(&self) -> usize {
+        self.lock().max_parallel
+    }
+
+    pub fn set_max_parallel(&self, max_parallel: usize) {
+        self.lock().max_parallel = max_parallel.max(1);
+        self.schedule();
+    }
+
+    pub fn conflict_policy(&self) -> ConflictPolicy {
+        self.lock().conflict_policy
+    }
+
+    /// Sets how copy and move jobs started from now on handle existing files.
+    pub fn set_conflict_policy(&self, policy: ConflictPolicy) {
+        self.lock().conflict_policy = policy;
+    }
+
+    pub fn copy_workers(&self) -> usize {
+        self.lock().copy_workers
+    }
+
+    /// Sets how many files copy jobs started from now on copy at the same time.
+    pub fn set_copy_workers(&self, workers: usize) {
+        self.lock().copy_workers = workers.max(1);
+    }
+
+    pub fn copy_error_mode(&self) -> CopyErrorMode {
+        self.lock().copy_error_mode
+    }
+
+    /// Sets whether copy jobs started from now on stop at the first failure.
+    pub fn set_copy_error_mode(&self, mode: CopyErrorMode) {
+        self.lock().copy_error_mode = mode;
+    }
+
+    pub fn verify(&self) -> Option<HashAlgorithm> {
+        self.lock().verify
+    }
+
+    /// Sets whether copy and move jobs started from now on verify their
+    /// copies with checksums, and which.
+    pub fn set_verify(&self, algorithm: Option<HashAlgorithm>) {
+        self.lock().verify = algorithm;
+    }
+
+    /// Returns whether any job finished since the last call.
+    pub fn take_finished(&self) -> bool {
+        std::mem::take(&mut self.lock().finished)
+    }
+
+    pub fn pause(&self, id: JobId) {
+        self.update(id, |job| {
+            if job.state == JobState::Running {
+                job.control.pause();
+                job.state = JobState::Paused;
+            }
+        });
+    }
+
+    pub fn resume(&self, id: JobId) {
+        self.update(id, |job| {
+            if job.state == JobState::Paused {
+                job.control.resume();
+                job.state = JobState::Running;
+            }
+        });
+    }
+
+    pub fn cancel(&self, id: JobId) {
+        self.update(id, |job| {
+            job.control.cancel();
+            if job.state == JobState::Queued {
+                job.state = JobState::Cancelled;
+            }
+        });
+    }
+
+    /// Starts a queued job straight away, alongside whatever is already running.
+    pub fn start_now(&self, id: JobId) {
+        let start = self.lock().jobs.iter().any(|job| job.id == id && job.state == JobState::Queued);
+        if start {
+            self.start(id);
+        }
+    }
+
+    /// Moves a job one place earlier in the queue.
+    pub fn move_up(&self, id: JobId) {
+        let mut queue = self.lock();
+        if let Some(index) = queue.jobs.iter().position(|job| job.id == id) {
+            if index > 0 {
+                queue.jobs.swap(index, index - 1);
+            }
+        }
+    }
+
+    /// Moves a job one place later in the queue.
+    pub fn move_down(&self, id: JobId) {
+        let mut queue = self.lock();
+        if let Some(index) = queue.jobs.iter().position(|job| job.id == id) {
+            if index + 1 < queue.jobs.len() {
+                queue.jobs.swap(index, index + 1);
+            }
+        }
+    }
+
+    /// Removes completed, failed and cancelled jobs from the list.
+    pub fn clear_finished(&self) {
+        self.lock().jobs.retain(|job| !job.state.is_finished());
+    }
+
+    fn update(&self, id: JobId, f: impl FnOnce(&mut Job)) {
+        if let Some(job) = self.lock().jobs.iter_mut().find(|job| job.id == id) {
+            f(job);
+        }
+    }
+
+    /// Starts queued jobs until `max_parallel` are running.
+    fn schedule(&self) {
+        loop {
+            let next = {
+                let queue = self.lock();
+                let active = queue.jobs.iter().filter(|job| job.state.is_active()).count();
+                if active >= queue.max_parallel {
+                    return;
+                }
+                queue.jobs.iter().find(|job| job.state == JobState::Queued).map(|job| job.id)
+            };
+
+            match next {
+                Some(id) => self.start(id),
+                None => return,
+            }
+        }
+    }
+
+    fn start(&self, id: JobId) {
+        let Some((kind, control)) = ({
+            let mut queue = self.lock();
+            queue.jobs.iter_mut().find(|job| job.id == id).map(|job| {
+                job.state = JobState::Running;
+                (job.kind.clone(), job.control.clone())
+            })
+        }) else {
+            return;
+        };
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<CopyProgress>();
+        let manager = self.clone();
+        self.runtime.spawn(async move {
+            while let Some(progress) = rx.recv().await {
+                manager.update(id, |job| job.progress = Some(progress));
+            }
+        });
+
+        let manager = self.clone();
+        self.runtime.spawn(async move {
+            let result = manager.run(kind, control, tx).await;
+
+            let state = match result {
+                Ok(()) => JobState::Completed,
+                Err(e) if e.is::<Cancelled>() => JobState::Cancelled,
+                Err(e) => {
+                    log::error!("Job {} failed: {}", id, e);
+                    JobState::Failed(e.to_string())
+                }
+            };
+
+            manager.update(id, |job| job.state = state);
+            manager.lock().finished = true;
+            manager.schedule();
+        });
+    }
+
+    async fn run(
+        &self,
+        kind: JobKind,
+        control: JobControl,
+        progress_tx: mpsc::UnboundedSender<CopyProgress>,
+    ) -> Result<()> {
+        let vfs = self.vfs.clone();
+        let mut conflicts = ConflictHandler::new(self.conflict_policy());
+        conflicts.set_resolver(self.conflict_resolver.clone());
+        // Progress of jobs that go item by item, without byte counts
+        let report = |current_file: &PathBuf, completed_files: usize, total_files: usize| {
+            let _ = progress_tx.send(CopyProgress {
+                current_file: current_file.clone(),
+                total_files,
+                completed_files,
+                ..CopyProgress::default()
+            });
+        };
+
+        match kind {
+            JobKind::Copy { sources, destination } => {
+                let mut operation = CopyOperation::new(sources, destination)
+                    .with_source_vfs(vfs.clone())
+                    .with_destination_vfs(vfs)
+                    .with_conflict_policy(conflicts.policy())
+                    .with_workers(self.copy_workers())
+                    .with_error_mode(self.copy_error_mode())
+                    .with_progress_callback(progress_tx.clone())
+                    .with_control(control);
+                if let Some(ref resolver) = self.conflict_resolver {
+                    operation = operation.with_conflict_resolver(resolver.clone());
+                }
+                if let Some(algorithm) = self.verify() {
+                    operation = operation.with_verify(algorithm);
+                }
+                let action = journal::copy_recorded(operation).await?;
+                self.record(action).await;
+            }
+            JobKind::Move { sources, destination } => {
+                let mut moves = Vec::new();
+                let result = async {
+                    for (index, source) in sources.iter().enumerate() {
+                        control.checkpoint().await?;
+                        report(source, index, sources.len());
+                        moves.extend(self.move_one(&conflicts, &control, &progress_tx, source, &destination).await?);
+                        report(source, index + 1, sources.len());
+                    }
+                    anyhow::Ok(())
+                }.await;
+
+                // Whatever was moved before a failure can still be undone
+                if !moves.is_empty() {
+                    self.record(JournalAction::Move { moves }).await;
+                }
+                result?;
+            }
+            JobKind::Link { sources, destination } => {
+                let mut links = Vec::new();
+                let result = async {
+                    for (index, source) in sources.iter().enumerate() {
+                        control.checkpoint().await?;
+                        report(source, index, sources.len());
+                        links.extend(self.link_one(&conflicts, source, &destination).await?);
+                        report(source, index + 1, sources.len());
+                    }
+                    anyhow::Ok(())
+                }.await;
+
+                if !links.is_empty() {
+                    self.record(JournalAction::Link { links }).await;
+                }
+                result?;
+            }
+            JobKind::Delete { paths } => {
+                let mut trashed = Vec::new();
+                let result = async {
+                    for (index, path) in paths.iter().enumerate() {
+                        control.checkpoint().await?;
+                        report(path, index, paths.len());
+                        trashed.extend(self.delete_one(path).await?);
+                        report(path, index + 1, paths.len());
+                    }
+                    anyhow::Ok(())
+                }.await;
+
+                if !trashed.is_empty() {
+                    self.record(JournalAction::Trash { paths: trashed }).await;
+                }
+                result?;
+            }
+            JobKind::Extract { archive, destination } => {
+                ExtractOperation::new(archive, destination)
+                    .with_vfs(vfs)
+                    .with_progress_callback(progress_tx.clone())
+                    .with_control(control)
+                    .execute()
+                    .await?;
+            }
+            JobKind::Rename { path, name } => {
+                let to = rename::rename_file_in(vfs.as_ref(), &path, &name).await?;
+                self.record(JournalAction::Rename { from: path, to }).await;
+            }
+            JobKind::CreateFolder { parent, name } => {
+                let path = create::create_folder_in(vfs.as_ref(), &parent, &name).await?;
+                self.record(JournalAction::CreateFolder { path }).await;
+            }
+            JobKind::CreateFile { parent, name } => {
+                let path = create::create_file_in(vfs.as_ref(), &parent, &name).await?;
+                self.record(JournalAction::CreateFile { path }).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves `source` into `destination`, returning the `(source, target)`
+    /// pair it was moved as, or `None` if it was skipped.
+    async fn move_one(
+        &self,
+        conflicts: &ConflictHandler,
+        control: &JobControl,
+        progress_tx: &mpsc::UnboundedSender<CopyProgress>,
+        source: &Path,
+        destination: &Path,
+    ) -> Result<Option<(PathBuf, PathBuf)>> {
+        let Some(name) = source.file_name() else {
+            return Ok(None);
+        };
+        let target = destination.join(name);
+        if target == source {
+            return Ok(None);
+        }
+        let Some((target, replaced)) = Self::move_target(self.vfs.as_ref(), conflicts, source, target).await? else {
+            return Ok(None);
+        };
+
+        let mut operation = MoveOperation::new(source.to_path_buf(), target.clone())
+            .with_vfs(self.vfs.clone())
+            .with_progress_callback(progress_tx.clone())
+            .with_control(control.clone());
+        if let Some(algorithm) = self.verify() {
+            operation = operation.with_verify(algorithm);
+        }
+        let moved = operation.execute().await;
+        Self::finish_replacing(self.vfs.as_ref(), replaced, moved.is_ok());
+        moved?;
+        Ok(Some((source.to_path_buf(), target)))
+    }
+
+    /// Links to `source` from `destination`, returning the `(target, link)`
+    /// pair, or `None` if it was skipped.
+    async fn link_one(
+        &self,
+        conflicts: &ConflictHandler,
+        source: &Path,
+        destination: &Path,
+    ) -> Result<Option<(PathBuf, PathBuf)>> {
+        let Some(name) = source.file_name() else {
+            return Ok(None);
+        };
+        let vfs = self.vfs.as_ref();
+        let Some((link, replaced)) = Self::move_target(vfs, conflicts, source, destination.join(name)).await? else {
+            return Ok(None);
+        };
+
+        let linked = vfs.symlink(source, &link);
+        Self::finish_replacing(vfs, replaced, linked.is_ok());
+        linked?;
+        Ok(Some((source.to_path_buf(), link)))
+    }
+
+    /// Deletes `path`, returning where it was on the local disk if it went
+    /// to the trash.
+    async fn delete_one(&self, path: &Path) -> Result<Option<PathBuf>> {
+        match self.vfs.local_path(path) {
+            Some(local_path) => {
+                delete::delete_files(vec![local_path.clone()], true).await?;
+                Ok(Some(local_path))
+            }
+            None => {
+                delete::delete_files_in(self.vfs.as_ref(), vec![path.to_path_buf()]).await?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Resolves a clash at `target` for a move or link. An existing file to
+    /// be replaced is set aside and returned with the path to use, to be
+    /// dropped through [`Self::finish_replacing`]. `None` skips the item.
+    async fn move_target(
+        vfs: &dyn Vfs,
+        conflicts: &ConflictHandler,
+        source: &Path,
+        target: PathBuf,
+    ) -> Result<Option<(PathBuf, Option<SetAside>)>> {
+        let Ok(existing) = vfs.metadata(&target) else {
+            return Ok(Some((target, None)));
+        };
+
+        let Some(resolved) = conflicts.resolve(vfs, source, vfs, &target).await? else {
+            return Ok(None);
+        };
+        if resolved != target {
+            return Ok(Some((resolved, None)));
+        }
+        if existing.is_dir() {
+            return Err(anyhow::anyhow!("Cannot replace folder {}", target.display()));
+        }
+        let replaced = SetAside::new(vfs, &target)?;
+        Ok(Some((resolved, Some(replaced))))
+    }
+
+    /// Deletes what a move or link replaced once it is `done`, or puts it back.
+    fn finish_replacing(vfs: &dyn Vfs, replaced: Option<SetAside>, done: bool) {
+        match replaced {
+            Some(replaced) if done => replaced.discard(vfs),
+            Some(replaced) => replaced.restore(vfs),
+            None => {}
+        }
+    }
+
+    async fn record(&self, action: JournalAction) {
+        if let Some(ref journal) = self.journal {
+            journal.lock().await.record(action);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vfs::MemoryFs;
+    use std::time::Duration;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn item_jobs_report_files_not_bytes() {
+        let vfs = MemoryFs::new().with_file("/a.txt", "aaaa").with_file("/b.txt", "bbbb");
+        let jobs = JobManager::new(Handle::current(), Arc::new(vfs.clone()));
+        jobs.enqueue(JobKind::Delete { paths: vec![PathBuf::from("/a.txt"), PathBuf::from("/b.txt")] });
+
+        let job = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                // Progress arrives apart from the state, so wait for the last report too
+                let job = jobs.jobs().remove(0);
+                if job.state.is_finished() && job.progress.as_ref().is_some_and(|p| p.completed_files == 2) {
+                    return job;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await.unwrap();
+
+        assert_eq!(job.state, JobState::Completed);
+        assert!(!vfs.exists(Path::new("/a.txt")) && !vfs.exists(Path::new("/b.txt")));
+        let progress = job.progress.unwrap();
+        assert_eq!((progress.completed_files, progress.total_files), (2, 2));
+        assert_eq!((progress.bytes_copied, progress.total_bytes), (0, 0));
+        assert_eq!(progress.fraction(), 1.0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn renames_and_new_items_are_journaled() {
+        let vfs = MemoryFs::new().with_file("/dir/a.txt", "a");
+        let journal = Arc::new(tokio::sync::Mutex::new(OperationJournal::new()));
+        let jobs = JobManager::new(Handle::current(), Arc::new(vfs.clone())).with_journal(journal.clone());
+        jobs.enqueue(JobKind::Rename { path: PathBuf::from("/dir/a.txt"), name: String::from("b.txt") });
+        jobs.enqueue(JobKind::CreateFolder { parent: PathBuf::from("/dir"), name: String::from("sub") });
+        jobs.enqueue(JobKind::CreateFile { parent: PathBuf::from("/dir"), name: String::from("new.txt") });
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !jobs.jobs().iter().all(|job| job.state == JobState::Completed) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await.unwrap();
+
+        assert!(vfs.exists(Path::new("/dir/b.txt")) && vfs.is_dir(Path::new("/dir/sub")) && vfs.exists(Path::new("/dir/new.txt")));
+        let descriptions: Vec<_> = journal.lock().await.undo_entries().iter()
+            .map(|entry| entry.action.description())
+            .collect();
+        assert_eq!(descriptions, ["Rename a.txt to b.txt", "New folder sub", "New file new.txt"]);
+    }
+}
@@ -0,0 +1,444 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+use crate::core::file_item::FileType;
+use crate::core::vfs::{self, Vfs};
+use crate::operations::conflict::ConflictPolicy;
+use crate::operations::copy::CopyOperation;
+use crate::operations::delete;
+use crate::operations::rename::MoveOperation;
+
+const DEFAULT_LIMIT: usize = 100;
+
+/// A completed file operation, with enough detail to reverse it.
+#[derive(Debug, Clone)]
+pub enum JournalAction {
+    /// An item renamed within its folder.
+    Rename { from: PathBuf, to: PathBuf },
+    /// Each `(source, destination)` pair that was moved.
+    Move { moves: Vec<(PathBuf, PathBuf)> },
+    /// Each `(target, link)` pair: a symbolic link created to `target`.
+    Link { links: Vec<(PathBuf, PathBuf)> },
+    /// A new, empty folder.
+    CreateFolder { path: PathBuf },
+    /// A new, empty file.
+    CreateFile { path: PathBuf },
+    /// `copies` holds the top-level paths the copy created under `destination`.
+    Copy { sources: Vec<PathBuf>, destination: PathBuf, copies: Vec<PathBuf> },
+    /// Items moved to the trash, by their original path.
+    Trash { paths: Vec<PathBuf> },
+}
+
+impl JournalAction {
+    pub fn description(&self) -> String {
+        match self {
+            JournalAction::Rename { from, to } => {
+                format!("Rename {} to {}", file_name(from), file_name(to))
+            }
+            JournalAction::Move { moves } => match moves.as_slice() {
+                [(source, destination)] => format!(
+                    "Move {} to {}",
+                    file_name(source),
+                    destination.parent().unwrap_or(destination).display()
+                ),
+                _ => format!("Move {} items", moves.len()),
+            },
+            JournalAction::Link { links } => match links.as_slice() {
+                [(_, link)] => format!(
+                    "Link {} in {}",
+                    file_name(link),
+                    link.parent().unwrap_or(link).display()
+                ),
+                _ => format!("Link {} items", links.len()),
+            },
+            JournalAction::CreateFolder { path } => format!("New folder {}", file_name(path)),
+            JournalAction::CreateFile { path } => format!("New file {}", file_name(path)),
+            JournalAction::Copy { copies, destination, .. } => match copies.as_slice() {
+                [copy] => format!("Copy {} to {}", file_name(copy), destination.display()),
+                _ => format!("Copy {} items to {}", copies.len(), destination.display()),
+            },
+            JournalAction::Trash { paths } => match paths.as_slice() {
+                [path] => format!("Delete {}", file_name(path)),
+                _ => format!("Delete {} items", paths.len()),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JournalEntry {
+    pub action: JournalAction,
+    pub completed_at: DateTime<Utc>,
+}
+
+/// Undo/redo history of the file operations performed in the explorer.
+///
+/// Operations are recorded once they have completed; undoing one performs the
+/// reverse operation through the same [`Vfs`] and moves it to the redo stack.
+/// Recording a new operation discards anything that could be redone.
+#[derive(Debug)]
+pub struct OperationJournal {
+    undo_stack: Vec<JournalEntry>,
+    redo_stack: Vec<JournalEntry>,
+    limit: usize,
+}
+
+impl OperationJournal {
+    pub fn new() -> Self {
+        Self::with_limit(DEFAULT_LIMIT)
+    }
+
+    /// Creates a journal that keeps at most `limit` undoable operations.
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            limit,
+        }
+    }
+
+    pub fn record(&mut self, action: JournalAction) {
+        self.redo_stack.clear();
+        self.undo_stack.push(JournalEntry {
+            action,
+            completed_at: Utc::now(),
+        });
+
+        if self.undo_stack.len() > self.limit {
+            let excess = self.undo_stack.len() - self.limit;
+            self.undo_stack.drain(..excess);
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Operations that can be undone, oldest first.
+    pub fn undo_entries(&self) -> &[JournalEntry] {
+        &self.undo_stack
+    }
+
+    /// Operations that can be redone, most recently undone last.
+    pub fn redo_entries(&self) -> &[JournalEntry] {
+        &self.redo_stack
+    }
+
+    /// Reverses the most recent operation. Returns its description, or `None`
+    /// if there is nothing to undo. A failed undo stays on the undo stack.
+    pub async fn undo(&mut self, vfs: Arc<dyn Vfs>) -> Result<Option<String>> {
+        let Some(entry) = self.undo_stack.pop() else {
+            return Ok(None);
+        };
+
+        if let Err(e) = revert(vfs, &entry.action).await {
+            self.undo_stack.push(entry);
+            return Err(e);
+        }
+
+        let description = entry.action.description();
+        self.redo_stack.push(entry);
+        Ok(Some(description))
+    }
+
+    /// Performs the most recently undone operation again.
+    pub async fn redo(&mut self, vfs: Arc<dyn Vfs>) -> Result<Option<String>> {
+        let Some(entry) = self.redo_stack.pop() else {
+            return Ok(None);
+        };
+
+        let action = match reapply(vfs, &entry.action).await {
+            Ok(action) => action,
+            Err(e) => {
+                self.redo_stack.push(entry);
+                return Err(e);
+            }
+        };
+
+        let description = entry.action.description();
+        self.undo_stack.push(JournalEntry {
+            action,
+            completed_at: Utc::now(),
+        });
+        Ok(Some(description))
+    }
+}
+
+impl Default for OperationJournal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs `operation` and, if it succeeds, returns the journal action for it.
+///
+/// Only copies that did not exist beforehand are recorded, so undoing never
+/// removes a folder the copy was merged into or a file it skipped or replaced.
+pub async fn copy_recorded(operation: CopyOperation) -> Result<JournalAction> {
+    let destination = operation.destination().to_path_buf();
+
+    operation.execute().await?;
+
+    let (sources, copies) = operation.created().into_iter().unzip();
+    Ok(JournalAction::Copy { sources, destination, copies })
+}
+
+async fn revert(vfs: Arc<dyn Vfs>, action: &JournalAction) -> Result<()> {
+    match action {
+        JournalAction::Rename { from, to } => rename_back(vfs, to, from).await,
+        JournalAction::Move { moves } => {
+            for (source, destination) in moves.iter().rev() {
+                rename_back(vfs.clone(), destination, source).await?;
+            }
+            Ok(())
+        }
+        JournalAction::Link { links } => {
+            for (_, link) in links {
+                if vfs.symlink_metadata(link)?.file_type != FileType::SymbolicLink {
+                    return Err(anyhow::anyhow!("{} is no longer a link", link.display()));
+                }
+                vfs.remove_file(link)?;
+            }
+            Ok(())
+        }
+        // Only ever removes what is still as it was created
+        JournalAction::CreateFolder { path } => {
+            if !vfs.read_dir(path)?.is_empty() {
+                return Err(anyhow::anyhow!("{} is no longer empty", path.display()));
+            }
+            vfs.remove_dir_all(path)?;
+            Ok(())
+        }
+        JournalAction::CreateFile { path } => {
+            if vfs.metadata(path)?.len > 0 {
+                return Err(anyhow::anyhow!("{} is no longer empty", path.display()));
+            }
+            vfs.remove_file(path)?;
+            Ok(())
+        }
+        JournalAction::Copy { copies, .. } => remove_copies(vfs.as_ref(), copies).await,
+        JournalAction::Trash { paths } => restore_from_trash(paths),
+    }
+}
+
+/// Performs `action` again, returning it as it should now be recorded.
+async fn reapply(vfs: Arc<dyn Vfs>, action: &JournalAction) -> Result<JournalAction> {
+    match action {
+        JournalAction::Rename { from, to } => rename_back(vfs, from, to).await?,
+        JournalAction::Move { moves } => {
+            for (source, destination) in moves {
+                rename_back(vfs.clone(), source, destination).await?;
+            }
+        }
+        JournalAction::Link { links } => {
+            for (target, link) in links {
+                vfs.symlink(target, link)?;
+            }
+        }
+        JournalAction::CreateFolder { path } => vfs.create_dir(path)?,
+        JournalAction::CreateFile { path } => {
+            if vfs.exists(path) {
+                return Err(anyhow::anyhow!("{} already exists", path.display()));
+            }
+            vfs::write(vfs.as_ref(), path, b"")?
+        }
+        JournalAction::Copy { sources, destination, .. } => {
+            // Never replace anything that has appeared since; the copies are re-recorded
+            let operation = CopyOperation::new(sources.clone(), destination.clone())
+                .with_source_vfs(vfs.clone())
+                .with_destination_vfs(vfs)
+                .with_conflict_policy(ConflictPolicy::KeepBoth);
+            return copy_recorded(operation).await;
+        }
+        JournalAction::Trash { paths } => {
+            delete::delete_files(paths.clone(), true).await?
+        }
+    }
+
+    Ok(action.clone())
+}
+
+/// Sends the copies to the trash, so undoing a copy can be taken back too.
+/// Copies off the host filesystem have no trash and are deleted, as the
+/// Delete job does.
+async fn remove_copies(vfs: &dyn Vfs, copies: &[PathBuf]) -> Result<()> {
+    for copy in copies {
+        match vfs.local_path(copy) {
+            Some(local_path) => delete::delete_files(vec![local_path], true).await?,
+            None => delete::delete_files_in(vfs, vec![copy.clone()]).await?,
+        }
+    }
+    Ok(())
+}
+
+/// Moves `from` to `to`, refusing to replace anything already at `to`.
+async fn rename_back(vfs: Arc<dyn Vfs>, from: &Path, to: &Path) -> Result<()> {
+    if vfs.exists(to) {
+        return Err(anyhow::anyhow!("{} already exists", to.display()));
+    }
+    MoveOperation::new(from.to_path_buf(), to.to_path_buf())
+        .with_vfs(vfs)
+        .execute()
+        .await
+}
+
+/// Puts the most recently trashed item for each of `paths` back where it was.
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+))]
+fn restore_from_trash(paths: &[PathBuf]) -> Result<()> {
+    let mut trashed = trash::os_limited::list()?;
+    // Newest first, so a path deleted more than once restores its latest version
+    trashed.sort_by_key(|item| std::cmp::Reverse(item.time_deleted));
+
+    let mut items = Vec::new();
+    for path in paths {
+        let index = trashed.iter()
+            .position(|item| &item.original_path() == path)
+            .ok_or_else(|| anyhow::anyhow!("{} is no longer in the trash", path.display()))?;
+        items.push(trashed.remove(index));
+    }
+
+    trash::os_limited::restore_all(items)?;
+    Ok(())
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+)))]
+fn restore_from_trash(_paths: &[PathBuf]) -> Result<()> {
+    Err(anyhow::anyhow!("Restoring from the trash is not supported on this platform"))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vfs::{self, MemoryFs};
+
+    fn copy(vfs: &MemoryFs, sources: &[&str], destination: &str) -> CopyOperation {
+        CopyOperation::new(sources.iter().map(PathBuf::from).collect(), PathBuf::from(destination))
+            .with_source_vfs(Arc::new(vfs.clone()))
+            .with_destination_vfs(Arc::new(vfs.clone()))
+    }
+
+    #[tokio::test]
+    async fn undoes_and_redoes_copies() {
+        let vfs = MemoryFs::new()
+            .with_file("/src/a.txt", "a")
+            .with_file("/src/folder/b.txt", "b")
+            .with_file("/dst/folder/kept.txt", "kept");
+        let mut journal = OperationJournal::new();
+        journal.record(copy_recorded(copy(&vfs, &["/src/a.txt", "/src/folder"], "/dst")).await.unwrap());
+
+        // The folder merged into was there before, so it stays
+        journal.undo(Arc::new(vfs.clone())).await.unwrap();
+        assert!(!vfs.exists(Path::new("/dst/a.txt")));
+        assert!(vfs.exists(Path::new("/dst/folder/kept.txt")));
+        assert!(vfs.exists(Path::new("/dst/folder/b.txt")));
+        assert!(journal.can_redo() && !journal.can_undo());
+
+        journal.redo(Arc::new(vfs.clone())).await.unwrap();
+        assert_eq!(vfs::read(&vfs, Path::new("/dst/a.txt")).unwrap(), b"a");
+        assert!(journal.can_undo() && !journal.can_redo());
+    }
+
+    #[tokio::test]
+    async fn undoes_and_redoes_moves() {
+        let vfs = MemoryFs::new().with_file("/src/a.txt", "a").with_dir("/dst");
+        let moves = vec![(PathBuf::from("/src/a.txt"), PathBuf::from("/dst/a.txt"))];
+        vfs.rename(&moves[0].0, &moves[0].1).unwrap();
+        let mut journal = OperationJournal::new();
+        journal.record(JournalAction::Move { moves });
+
+        assert_eq!(journal.undo(Arc::new(vfs.clone())).await.unwrap().as_deref(), Some("Move a.txt to /dst"));
+        assert!(vfs.exists(Path::new("/src/a.txt")) && !vfs.exists(Path::new("/dst/a.txt")));
+
+        journal.redo(Arc::new(vfs.clone())).await.unwrap();
+        assert!(!vfs.exists(Path::new("/src/a.txt")) && vfs.exists(Path::new("/dst/a.txt")));
+    }
+
+    #[tokio::test]
+    async fn failed_undo_stays_undoable() {
+        let vfs = MemoryFs::new().with_file("/src/a.txt", "new").with_file("/dst/a.txt", "moved");
+        let mut journal = OperationJournal::new();
+        journal.record(JournalAction::Move {
+            moves: vec![(PathBuf::from("/src/a.txt"), PathBuf::from("/dst/a.txt"))],
+        });
+
+        // Something new took the old place, and is never replaced
+        assert!(journal.undo(Arc::new(vfs.clone())).await.is_err());
+        assert_eq!(vfs::read(&vfs, Path::new("/src/a.txt")).unwrap(), b"new");
+        assert!(journal.can_undo() && !journal.can_redo());
+    }
+
+    #[tokio::test]
+    async fn undoes_and_redoes_renames() {
+        let vfs = MemoryFs::new().with_file("/src/b.txt", "a");
+        let mut journal = OperationJournal::new();
+        journal.record(JournalAction::Rename { from: PathBuf::from("/src/a.txt"), to: PathBuf::from("/src/b.txt") });
+
+        assert_eq!(journal.undo(Arc::new(vfs.clone())).await.unwrap().as_deref(), Some("Rename a.txt to b.txt"));
+        assert!(vfs.exists(Path::new("/src/a.txt")) && !vfs.exists(Path::new("/src/b.txt")));
+
+        journal.redo(Arc::new(vfs.clone())).await.unwrap();
+        assert!(!vfs.exists(Path::new("/src/a.txt")) && vfs.exists(Path::new("/src/b.txt")));
+    }
+
+    #[tokio::test]
+    async fn undoes_and_redoes_created_items() {
+        let vfs = MemoryFs::new().with_dir("/dst/folder").with_file("/dst/new.txt", "");
+        let mut journal = OperationJournal::new();
+        journal.record(JournalAction::CreateFolder { path: PathBuf::from("/dst/folder") });
+        journal.record(JournalAction::CreateFile { path: PathBuf::from("/dst/new.txt") });
+
+        assert_eq!(journal.undo(Arc::new(vfs.clone())).await.unwrap().as_deref(), Some("New file new.txt"));
+        assert_eq!(journal.undo(Arc::new(vfs.clone())).await.unwrap().as_deref(), Some("New folder folder"));
+        assert!(!vfs.exists(Path::new("/dst/folder")) && !vfs.exists(Path::new("/dst/new.txt")));
+
+        journal.redo(Arc::new(vfs.clone())).await.unwrap();
+        journal.redo(Arc::new(vfs.clone())).await.unwrap();
+        assert!(vfs.is_dir(Path::new("/dst/folder")));
+        assert_eq!(vfs::read(&vfs, Path::new("/dst/new.txt")).unwrap(), b"");
+    }
+
+    #[tokio::test]
+    async fn keeps_created_items_that_were_filled_in() {
+        let vfs = MemoryFs::new().with_file("/dst/folder/a.txt", "a").with_file("/dst/new.txt", "written");
+
+        for action in [
+            JournalAction::CreateFolder { path: PathBuf::from("/dst/folder") },
+            JournalAction::CreateFile { path: PathBuf::from("/dst/new.txt") },
+        ] {
+            let mut journal = OperationJournal::new();
+            journal.record(action);
+            assert!(journal.undo(Arc::new(vfs.clone())).await.is_err());
+            assert!(journal.can_undo());
+        }
+        assert!(vfs.exists(Path::new("/dst/folder/a.txt")));
+        assert_eq!(vfs::read(&vfs, Path::new("/dst/new.txt")).unwrap(), b"written");
+    }
+
+    #[test]
+    fn keeps_only_the_newest_entries() {
+        let mut journal = OperationJournal::with_limit(2);
+        for name in ["a", "b", "c"] {
+            journal.record(JournalAction::Trash { paths: vec![PathBuf::from(name)] });
+        }
+        let descriptions: Vec<_> = journal.undo_entries().iter().map(|entry| entry.action.description()).collect();
+        assert_eq!(descriptions, ["Delete b", "Delete c"]);
+    }
+}
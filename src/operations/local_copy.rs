@@ -0,0 +1,234 @@
+use std::fs;
+use std::path::Path;
+use anyhow::Result;
+
+/// Copies the contents of `source` to `dest`, both on the local disk, the
+/// fastest way the file system allows. `progress` is called with the bytes
+/// done after each step and can fail to stop the copy. Permissions are left
+/// to the caller, to keep only when [`PreserveOptions`] asks.
+///
+/// [`PreserveOptions`]: crate::operations::attributes::PreserveOptions
+///
+/// On Linux the copy is first cloned (copy-on-write, e.g. on btrfs and XFS),
+/// then copied in the kernel with `copy_file_range`, and finally read and
+/// written here. Holes in sparse files stay holes unless the file is cloned,
+/// where they are shared anyway.
+#[cfg(target_os = "linux")]
+pub fn copy_contents(source: &Path, dest: &Path, progress: &mut dyn FnMut(u64) -> Result<()>) -> Result<u64> {
+    let reader = fs::File::open(source)?;
+    let writer = fs::OpenOptions::new().write(true).create(true).truncate(true).open(dest)?;
+
+    let len = reader.metadata()?.len();
+    if linux::clone(&reader, &writer).is_ok() {
+        progress(len)?;
+        return Ok(len);
+    }
+
+    linux::copy_sparse(&reader, &writer, len, progress)?;
+    Ok(len)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn copy_contents(source: &Path, dest: &Path, progress: &mut dyn FnMut(u64) -> Result<()>) -> Result<u64> {
+    // The standard library already clones where the platform can (macOS)
+    let copied = fs::copy(source, dest)?;
+    progress(copied)?;
+    Ok(copied)
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::fs::FileExt;
+    use std::os::unix::io::AsRawFd;
+    use anyhow::Result;
+
+    /// Bytes handed to the kernel per `copy_file_range` call.
+    const RANGE_CHUNK: usize = 8 * 1024 * 1024;
+
+    /// Bytes read and written per step when the kernel can't copy.
+    const BUFFER_SIZE: usize = 1024 * 1024;
+
+    /// Shares the blocks of `reader` with `writer` (FICLONE).
+    pub fn clone(reader: &File, writer: &File) -> io::Result<()> {
+        // SAFETY: both descriptors are open for the duration of the call
+        let result = unsafe { libc::ioctl(writer.as_raw_fd(), libc::FICLONE, reader.as_raw_fd()) };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Copies the data ranges of `reader` and leaves the holes between them.
+    pub fn copy_sparse(
+        reader: &File,
+        writer: &File,
+        len: u64,
+        progress: &mut dyn FnMut(u64) -> Result<()>,
+    ) -> Result<()> {
+        let mut position = 0;
+        let mut in_kernel = true;
+
+        while position < len {
+            let data = match seek(reader, position, libc::SEEK_DATA) {
+                Ok(data) => data,
+                // Nothing but a hole left
+                Err(e) if e.raw_os_error() == Some(libc::ENXIO) => len,
+                // No hole detection on this file system: it's all data
+                Err(e) if e.raw_os_error() == Some(libc::EINVAL) => position,
+                Err(e) => return Err(e.into()),
+            };
+            if data >= len {
+                break;
+            }
+            let hole = seek(reader, data, libc::SEEK_HOLE).unwrap_or(len).min(len);
+
+            // Skipped holes count as done
+            if data > position {
+                progress(data - position)?;
+            }
+            copy_range(reader, writer, data, hole, &mut in_kernel, progress)?;
+            position = hole;
+        }
+
+        // Leaves any trailing hole
+        writer.set_len(len)?;
+        if len > position {
+            progress(len - position)?;
+        }
+        Ok(())
+    }
+
+    /// Copies bytes `start..end`, in the kernel while `in_kernel` holds.
+    fn copy_range(
+        reader: &File,
+        writer: &File,
+        start: u64,
+        end: u64,
+        in_kernel: &mut bool,
+        progress: &mut dyn FnMut(u64) -> Result<()>,
+    ) -> Result<()> {
+        let mut offset = start;
+        let mut buffer = Vec::new();
+
+        while offset < end {
+            let wanted = ((end - offset) as usize).min(RANGE_CHUNK);
+
+            let copied = if *in_kernel {
+                let mut offset_in = offset as libc::loff_t;
+                let mut offset_out = offset as libc::loff_t;
+                // SAFETY: both descriptors are open and the offsets outlive the call
+                let result = unsafe {
+                    libc::copy_file_range(
+                        reader.as_raw_fd(),
+                        &mut offset_in,
+                        writer.as_raw_fd(),
+                        &mut offset_out,
+                        wanted,
+                        0,
+                    )
+                };
+                if result < 0 {
+                    let error = io::Error::last_os_error();
+                    match error.raw_os_error() {
+                        // Not possible between these files: do it here instead
+                        Some(libc::EXDEV | libc::ENOSYS | libc::EOPNOTSUPP | libc::EINVAL | libc::EPERM) => {
+                            *in_kernel = false;
+                            continue;
+                        }
+                        Some(libc::EINTR) => continue,
+                        _ => return Err(error.into()),
+                    }
+                }
+                result as usize
+            } else {
+                if buffer.is_empty() {
+                    buffer = vec![0; BUFFER_SIZE];
+                }
+                let wanted = wanted.min(BUFFER_SIZE);
+                let read = reader.read_at(&mut buffer[..wanted], offset)?;
+                writer.write_all_at(&buffer[..read], offset)?;
+                read
+            };
+
+            // The file got shorter while copying
+            if copied == 0 {
+                break;
+            }
+            offset += copied as u64;
+            progress(copied as u64)?;
+        }
+        Ok(())
+    }
+
+    fn seek(file: &File, offset: u64, whence: libc::c_int) -> io::Result<u64> {
+        // SAFETY: the descriptor is open for the duration of the call
+        let result = unsafe { libc::lseek(file.as_raw_fd(), offset as libc::off_t, whence) };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(result as u64)
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::{FileExt, MetadataExt};
+
+    /// Copies the way [`copy_contents`] does where cloning isn't supported,
+    /// returning the bytes reported.
+    fn copy_without_clone(source: &Path, dest: &Path) -> u64 {
+        let reader = fs::File::open(source).unwrap();
+        let writer = fs::File::create(dest).unwrap();
+        let mut reported = 0;
+        linux::copy_sparse(&reader, &writer, reader.metadata().unwrap().len(), &mut |bytes| {
+            reported += bytes;
+            Ok(())
+        }).unwrap();
+        reported
+    }
+
+    #[test]
+    fn copies_contents_without_clone() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, dest) = (dir.path().join("source"), dir.path().join("dest"));
+        // Spans several read buffers and doesn't end on a block boundary
+        let content: Vec<u8> = (0..3 * 1024 * 1024 + 17).map(|i: u32| (i % 251) as u8).collect();
+        fs::write(&source, &content).unwrap();
+
+        assert_eq!(copy_without_clone(&source, &dest), content.len() as u64);
+        assert_eq!(fs::read(&dest).unwrap(), content);
+
+        // Whichever way the kernel copies, the result is the same
+        let cloned = dir.path().join("cloned");
+        assert_eq!(copy_contents(&source, &cloned, &mut |_| Ok(())).unwrap(), content.len() as u64);
+        assert_eq!(fs::read(&cloned).unwrap(), content);
+    }
+
+    #[test]
+    fn keeps_holes_in_sparse_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, dest) = (dir.path().join("source"), dir.path().join("dest"));
+        let len = 16 * 1024 * 1024;
+        let file = fs::File::create(&source).unwrap();
+        file.set_len(len).unwrap();
+        file.write_all_at(b"middle", len / 2).unwrap();
+        drop(file);
+
+        let allocated = |path: &Path| fs::metadata(path).unwrap().blocks() * 512;
+        if allocated(&source) >= len {
+            // This file system doesn't make holes
+            return;
+        }
+
+        assert_eq!(copy_without_clone(&source, &dest), len);
+        assert_eq!(fs::metadata(&dest).unwrap().len(), len);
+        assert!(allocated(&dest) < len / 2, "the copy filled in its holes");
+
+        let mut middle = [0; 6];
+        fs::File::open(&dest).unwrap().read_exact_at(&mut middle, len / 2).unwrap();
+        assert_eq!(&middle, b"middle");
+    }
+}
@@ -1,6 +1,14 @@
+pub mod attributes;
+pub mod checksum;
+pub mod conflict;
+pub mod control;
 pub mod copy;
+pub mod dedupe;
 pub mod delete;
 pub mod rename;
 pub mod create;
 pub mod compress;
 pub mod extract;
+pub mod jobs;
+pub mod local_copy;
+pub mod journal;
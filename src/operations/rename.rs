@@ -1,21 +1,333 @@
+use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use anyhow::Result;
+use tokio::sync::mpsc;
+
+use crate::core::vfs::{self, LocalFs, Vfs};
+use crate::operations::attributes::PreserveOptions;
+use crate::operations::checksum::HashAlgorithm;
+use crate::operations::control::JobControl;
+use crate::operations::copy::{CopyOperation, CopyProgress};
 
 pub async fn rename_file(old_path: &Path, new_name: &str) -> Result<PathBuf> {
+    rename_file_in(&LocalFs, old_path, new_name).await
+}
+
+pub async fn rename_file_in<V: Vfs + ?Sized>(vfs: &V, old_path: &Path, new_name: &str) -> Result<PathBuf> {
     let parent = old_path.parent()
         .ok_or_else(|| anyhow::anyhow!("Cannot get parent directory"))?;
     
     let new_path = parent.join(new_name);
     
-    if new_path.exists() {
+    if vfs.exists(&new_path) {
         return Err(anyhow::anyhow!("A file with that name already exists"));
     }
     
-    std::fs::rename(old_path, &new_path)?;
+    vfs.rename(old_path, &new_path)?;
     Ok(new_path)
 }
 
 pub async fn move_file(source: &Path, destination: &Path) -> Result<()> {
-    std::fs::rename(source, destination)?;
-    Ok(())
+    move_file_in(Arc::new(LocalFs), source, destination).await
+}
+
+pub async fn move_file_in(vfs: Arc<dyn Vfs>, source: &Path, destination: &Path) -> Result<()> {
+    MoveOperation::new(source.to_path_buf(), destination.to_path_buf())
+        .with_vfs(vfs)
+        .execute()
+        .await
+}
+
+/// Moves one file or folder to a new path within a single [`Vfs`].
+///
+/// Tries a plain rename first. When the backend can't rename from one path
+/// to the other (such as across mounts on the local disk), the item is
+/// copied, the copy is checked against the source and only then is the
+/// source deleted. If the copy fails part way, doesn't match when verified
+/// with checksums, or the source can't be deleted, whatever was copied is
+/// removed again and the source is left untouched.
+pub struct MoveOperation {
+    source: PathBuf,
+    destination: PathBuf,
+    vfs: Arc<dyn Vfs>,
+    progress_tx: Option<mpsc::UnboundedSender<CopyProgress>>,
+    control: Option<JobControl>,
+    verify: Option<HashAlgorithm>,
+}
+
+impl MoveOperation {
+    pub fn new(source: PathBuf, destination: PathBuf) -> Self {
+        Self {
+            source,
+            destination,
+            vfs: Arc::new(LocalFs),
+            progress_tx: None,
+            control: None,
+            verify: None,
+        }
+    }
+
+    /// Moves within `vfs` instead of the local disk.
+    pub fn with_vfs(mut self, vfs: Arc<dyn Vfs>) -> Self {
+        self.vfs = vfs;
+        self
+    }
+
+    /// Reports progress while falling back to copying.
+    pub fn with_progress_callback(mut self, tx: mpsc::UnboundedSender<CopyProgress>) -> Self {
+        self.progress_tx = Some(tx);
+        self
+    }
+
+    /// Lets `control` pause or cancel the copy when falling back to copying.
+    pub fn with_control(mut self, control: JobControl) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    /// Compares checksums of every file and its copy before deleting the
+    /// source when falling back to copying.
+    pub fn with_verify(mut self, algorithm: HashAlgorithm) -> Self {
+        self.verify = Some(algorithm);
+        self
+    }
+
+    pub async fn execute(&self) -> Result<()> {
+        match self.vfs.rename(&self.source, &self.destination) {
+            Ok(()) => Ok(()),
+            Err(e) if crosses_filesystems(&e) => {
+                log::info!("Moving {} by copying: {}", self.source.display(), e);
+                self.copy_then_delete().await
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn copy_then_delete(&self) -> Result<()> {
+        if self.vfs.exists(&self.destination) {
+            return Err(anyhow::anyhow!("Destination already exists: {}", self.destination.display()));
+        }
+
+        let (Some(parent), Some(name)) = (self.destination.parent(), self.destination.file_name()) else {
+            return Err(anyhow::anyhow!("Invalid destination: {}", self.destination.display()));
+        };
+        let is_dir = self.vfs.symlink_metadata(&self.source)?.is_dir();
+
+        let mut operation = CopyOperation::new(vec![self.source.clone()], parent.to_path_buf())
+            .with_target_name(name.to_os_string())
+            .with_preserve(PreserveOptions::all())
+            .with_source_vfs(self.vfs.clone())
+            .with_destination_vfs(self.vfs.clone());
+        if let Some(ref tx) = self.progress_tx {
+            operation = operation.with_progress_callback(tx.clone());
+        }
+        if let Some(ref control) = self.control {
+            operation = operation.with_control(control.clone());
+        }
+        if let Some(algorithm) = self.verify {
+            operation = operation.with_verify(algorithm);
+        }
+
+        let copied = match operation.execute().await {
+            Ok(()) => self.verify(),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = copied {
+            self.roll_back();
+            return Err(e);
+        }
+
+        let entries = self.entries()?.len();
+        let removed = if is_dir {
+            self.vfs.remove_dir_all(&self.source)
+        } else {
+            self.vfs.remove_file(&self.source)
+        };
+        if let Err(e) = removed {
+            // Only drop the copy while the source is still whole
+            if self.entries().is_ok_and(|left| left.len() == entries) {
+                self.roll_back();
+            }
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// Pairs the source and everything below it with where its copy goes.
+    fn entries(&self) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let mut entries = vec![(self.source.clone(), self.destination.clone())];
+        if self.vfs.symlink_metadata(&self.source)?.is_dir() {
+            for (path, _) in vfs::walk(self.vfs.as_ref(), &self.source)? {
+                let relative = path.strip_prefix(&self.source)?;
+                entries.push((path.clone(), self.destination.join(relative)));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Checks that every entry of the source arrived, files with the same size.
+    fn verify(&self) -> Result<()> {
+        for (source, copy) in self.entries()? {
+            let expected = self.vfs.symlink_metadata(&source)?;
+            let actual = self.vfs.symlink_metadata(&copy)
+                .map_err(|_| anyhow::anyhow!("Copy of {} is missing", source.display()))?;
+
+            if expected.is_file() && actual.len != expected.len {
+                return Err(anyhow::anyhow!(
+                    "Copy of {} is incomplete ({} of {} bytes)",
+                    source.display(),
+                    actual.len,
+                    expected.len
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes a partial copy so a failed move leaves only the source behind.
+    fn roll_back(&self) {
+        let removed = match self.vfs.symlink_metadata(&self.destination) {
+            Ok(metadata) if metadata.is_dir() => self.vfs.remove_dir_all(&self.destination),
+            Ok(_) => self.vfs.remove_file(&self.destination),
+            Err(_) => return,
+        };
+        if let Err(e) = removed {
+            log::warn!("Failed to remove partial copy {}: {}", self.destination.display(), e);
+        }
+    }
+}
+
+/// Whether a rename failed only because it would cross filesystems.
+fn crosses_filesystems(error: &io::Error) -> bool {
+    matches!(error.kind(), io::ErrorKind::CrossesDevices | io::ErrorKind::Unsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vfs::{MemoryFs, VfsMetadata, VfsReader, VfsWriter};
+    use std::io::Write;
+
+    /// A [`MemoryFs`] that can't rename, as if every move crossed devices.
+    /// It can also garble what is written, or refuse to delete sources.
+    struct CrossDevice {
+        fs: MemoryFs,
+        corrupt: bool,
+        undeletable: bool,
+    }
+
+    impl CrossDevice {
+        fn new(fs: &MemoryFs) -> Self {
+            Self { fs: fs.clone(), corrupt: false, undeletable: false }
+        }
+
+        fn removal(&self, path: &Path) -> io::Result<()> {
+            if self.undeletable && path.starts_with("/src") {
+                return Err(io::Error::from(io::ErrorKind::PermissionDenied));
+            }
+            Ok(())
+        }
+    }
+
+    impl Vfs for CrossDevice {
+        fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+            self.fs.read_dir(path)
+        }
+
+        fn metadata(&self, path: &Path) -> io::Result<VfsMetadata> {
+            self.fs.metadata(path)
+        }
+
+        fn open_read(&self, path: &Path) -> io::Result<Box<dyn VfsReader>> {
+            self.fs.open_read(path)
+        }
+
+        fn open_write(&self, path: &Path) -> io::Result<Box<dyn VfsWriter>> {
+            let mut writer = self.fs.open_write(path)?;
+            if self.corrupt {
+                writer.write_all(b"garbage")?;
+            }
+            Ok(writer)
+        }
+
+        fn rename(&self, _from: &Path, _to: &Path) -> io::Result<()> {
+            Err(io::Error::from(io::ErrorKind::CrossesDevices))
+        }
+
+        fn remove_file(&self, path: &Path) -> io::Result<()> {
+            self.removal(path)?;
+            self.fs.remove_file(path)
+        }
+
+        fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+            self.removal(path)?;
+            self.fs.remove_dir_all(path)
+        }
+
+        fn create_dir(&self, path: &Path) -> io::Result<()> {
+            self.fs.create_dir(path)
+        }
+
+        fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+            self.fs.create_dir_all(path)
+        }
+    }
+
+    fn folder() -> MemoryFs {
+        MemoryFs::new()
+            .with_file("/src/folder/a.txt", "a")
+            .with_file("/src/folder/sub/b.txt", "b")
+            .with_dir("/dst")
+    }
+
+    fn move_folder(vfs: CrossDevice) -> MoveOperation {
+        MoveOperation::new(PathBuf::from("/src/folder"), PathBuf::from("/dst/moved"))
+            .with_vfs(Arc::new(vfs))
+    }
+
+    #[test]
+    fn only_device_errors_fall_back_to_copying() {
+        assert!(crosses_filesystems(&io::Error::from(io::ErrorKind::CrossesDevices)));
+        assert!(crosses_filesystems(&io::Error::from(io::ErrorKind::Unsupported)));
+        assert!(!crosses_filesystems(&io::Error::from(io::ErrorKind::PermissionDenied)));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn copies_then_deletes_across_devices() {
+        let fs = folder();
+        move_folder(CrossDevice::new(&fs)).execute().await.unwrap();
+
+        assert!(!fs.exists(Path::new("/src/folder")));
+        assert_eq!(vfs::read(&fs, Path::new("/dst/moved/a.txt")).unwrap(), b"a");
+        assert_eq!(vfs::read(&fs, Path::new("/dst/moved/sub/b.txt")).unwrap(), b"b");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rolls_back_copies_that_do_not_verify() {
+        for algorithm in [None, Some(HashAlgorithm::Blake3)] {
+            let fs = folder();
+            let mut operation = move_folder(CrossDevice { corrupt: true, ..CrossDevice::new(&fs) });
+            if let Some(algorithm) = algorithm {
+                operation = operation.with_verify(algorithm);
+            }
+
+            assert!(operation.execute().await.is_err());
+            assert!(!fs.exists(Path::new("/dst/moved")));
+            assert_eq!(vfs::read(&fs, Path::new("/src/folder/sub/b.txt")).unwrap(), b"b");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rolls_back_when_the_source_cannot_be_deleted() {
+        let fs = folder();
+        let error = move_folder(CrossDevice { undeletable: true, ..CrossDevice::new(&fs) })
+            .execute()
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.downcast_ref::<io::Error>().unwrap().kind(), io::ErrorKind::PermissionDenied);
+        assert!(!fs.exists(Path::new("/dst/moved")));
+        assert_eq!(vfs::read(&fs, Path::new("/src/folder/a.txt")).unwrap(), b"a");
+    }
 }
@@ -1,19 +1,177 @@
 use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-use crate::core::file_manager::FileManager;
+use crate::core::file_manager::{FileManager, NavigationState};
 use crate::core::bookmark::BookmarkManager;
+use crate::core::folder_tree::FolderTree;
+use crate::core::settings::Settings;
+use crate::operations::jobs::{JobKind, JobManager};
+use crate::operations::journal::OperationJournal;
 use crate::ui::{toolbar::Toolbar, sidebar::Sidebar, file_browser::FileBrowser, status_bar::StatusBar, tabs::TabManager};
+use crate::ui::file_browser::ViewState;
+use crate::ui::session::{PaneSession, Session};
+use crate::ui::tabs::{TabRequest, TabState};
+use crate::ui::dialogs::ConflictDialog;
+use crate::ui::disk_usage::DiskUsagePanel;
+use crate::ui::duplicates::DuplicatesPanel;
+use crate::ui::history::HistoryPanel;
+use crate::ui::transfers::TransfersPanel;
 
-pub struct ChexExplorerApp {
+/// A folder listing with its own location, history and selection, and tabs
+/// to keep others at hand. The window shows one, or two side by side in
+/// dual-pane mode.
+struct Pane {
     file_manager: Arc<Mutex<FileManager>>,
+    browser: FileBrowser,
+    tabs: TabManager,
+    /// Where the pane was as of last frame, for synchronized navigation.
+    last_path: PathBuf,
+}
+
+impl Pane {
+    /// A pane with the tabs of `session`, or with one tab at `path` if it has none.
+    fn restore(file_manager: FileManager, session: PaneSession, path: PathBuf, jobs: JobManager, runtime: &tokio::runtime::Runtime) -> Self {
+        let file_manager = Arc::new(Mutex::new(file_manager));
+        let mut pane = Self {
+            browser: FileBrowser::new(file_manager.clone(), jobs),
+            file_manager,
+            tabs: TabManager::new(),
+            last_path: path.clone(),
+        };
+
+        let mut tabs = session.tabs;
+        if tabs.is_empty() {
+            let navigation = runtime.block_on(async { pane.file_manager.lock().await.save_state().await });
+            tabs.push(TabState {
+                navigation: NavigationState::new(path, navigation.sort_by, navigation.sort_order),
+                view: pane.browser.view_state(),
+            });
+        }
+        pane.tabs.restore(tabs, session.active_tab);
+        pane.show_active_tab(runtime);
+        pane
+    }
+
+    fn tab_state(&self, runtime: &tokio::runtime::Runtime) -> TabState {
+        TabState {
+            navigation: runtime.block_on(async { self.file_manager.lock().await.save_state().await }),
+            view: self.browser.view_state(),
+        }
+    }
+
+    /// The pane's tabs, to be restored on the next start.
+    fn session(&self, runtime: &tokio::runtime::Runtime) -> PaneSession {
+        let (tabs, active_tab) = self.tabs.states(self.tab_state(runtime));
+        PaneSession { tabs, active_tab }
+    }
+
+    /// Puts back the state the active tab kept. A folder that has gone away
+    /// since gives way to the home folder.
+    fn show_active_tab(&mut self, runtime: &tokio::runtime::Runtime) {
+        let Some(state) = self.tabs.take_active_state() else {
+            return;
+        };
+        self.browser.set_view_state(state.view);
+        self.browser.set_sort(state.navigation.sort_by, state.navigation.sort_order);
+
+        let path = state.navigation.path().map(Path::to_path_buf).unwrap_or_default();
+        self.last_path = runtime.block_on(async {
+            let mut fm = self.file_manager.lock().await;
+            if let Err(e) = fm.restore_state(state.navigation).await {
+                log::warn!("Can't reopen {}: {}", path.display(), e);
+                let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/"));
+                if let Err(e) = fm.navigate_to(&home_dir).await {
+                    log::error!("Failed to navigate to {}: {}", home_dir.display(), e);
+                }
+            }
+            fm.get_current_path().await
+        });
+    }
+
+    fn switch_tab(&mut self, tab_id: uuid::Uuid, runtime: &tokio::runtime::Runtime) {
+        if self.tabs.get_active_tab().is_some_and(|tab| tab.id == tab_id) {
+            return;
+        }
+        let state = self.tab_state(runtime);
+        self.tabs.store_active_state(state);
+        self.tabs.activate_tab(tab_id);
+        self.show_active_tab(runtime);
+    }
+
+    /// Opens `path` in a new tab with a history of its own, listed the way
+    /// the current tab is.
+    fn open_tab(&mut self, path: PathBuf, runtime: &tokio::runtime::Runtime) {
+        let state = self.tab_state(runtime);
+        let navigation = NavigationState::new(path, state.navigation.sort_by, state.navigation.sort_order);
+        let view = ViewState { scroll_offset: 0.0, ..state.view };
+        self.tabs.store_active_state(state);
+        self.tabs.open_tab(TabState { navigation, view });
+        self.show_active_tab(runtime);
+    }
+
+    /// Closes the tab `tab_id`, unless it is the last one.
+    fn close_tab(&mut self, tab_id: uuid::Uuid, runtime: &tokio::runtime::Runtime) {
+        if self.tabs.len() < 2 {
+            return;
+        }
+        let was_active = self.tabs.get_active_tab().is_some_and(|tab| tab.id == tab_id);
+        self.tabs.close_tab(tab_id);
+        if was_active {
+            self.show_active_tab(runtime);
+        }
+    }
+
+    fn current_path(&self, runtime: &tokio::runtime::Runtime) -> PathBuf {
+        runtime.block_on(async { self.file_manager.lock().await.get_current_path().await })
+    }
+
+    /// Draws the tab bar and the browser.
+    fn show(&mut self, ui: &mut egui::Ui, jobs: &JobManager, runtime: &tokio::runtime::Runtime) {
+        match self.tabs.show_tabs(ui, jobs) {
+            Some(TabRequest::Activate(tab_id)) => self.switch_tab(tab_id, runtime),
+            Some(TabRequest::Close(tab_id)) => self.close_tab(tab_id, runtime),
+            Some(TabRequest::New) => self.open_tab(self.current_path(runtime), runtime),
+            None => {}
+        }
+        ui.separator();
+
+        self.browser.show(ui, runtime);
+        if let Some(path) = self.browser.take_new_tab() {
+            self.open_tab(path, runtime);
+        }
+        self.tabs.update_active_tab_path(self.current_path(runtime));
+    }
+}
+
+/// How the window lays out its panes, switched from the toolbar.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct PaneLayout {
+    pub dual_pane: bool,
+    /// The other pane follows the active one into subfolders and back up.
+    pub sync_navigation: bool,
+}
+
+pub struct ChexExplorerApp {
+    panes: Vec<Pane>,
+    /// The pane the keyboard, toolbar and status bar work on.
+    active_pane: usize,
+    layout: PaneLayout,
+    /// Tab was pressed outside a text field.
+    switch_pane: bool,
     bookmark_manager: Arc<Mutex<BookmarkManager>>,
+    journal: Arc<Mutex<OperationJournal>>,
+    jobs: JobManager,
     toolbar: Toolbar,
     sidebar: Sidebar,
-    file_browser: FileBrowser,
     status_bar: StatusBar,
-    tab_manager: TabManager,
+    history_panel: HistoryPanel,
+    transfers_panel: TransfersPanel,
+    duplicates_panel: DuplicatesPanel,
+    disk_usage_panel: DiskUsagePanel,
+    conflict_dialog: ConflictDialog,
     runtime: tokio::runtime::Runtime,
 }
 
@@ -26,31 +184,50 @@ impl ChexExplorerApp {
         Self::setup_theme(&cc.egui_ctx);
 
         let runtime = tokio::runtime::Runtime::new().unwrap();
-        let file_manager = Arc::new(Mutex::new(FileManager::new()));
+        let file_manager = FileManager::new();
         let bookmark_manager = Arc::new(Mutex::new(BookmarkManager::new()));
+        let journal = Arc::new(Mutex::new(OperationJournal::new()));
+        let (conflict_tx, conflict_rx) = tokio::sync::mpsc::unbounded_channel();
+        let jobs = JobManager::new(runtime.handle().clone(), file_manager.vfs())
+            .with_journal(journal.clone())
+            .with_conflict_resolver(conflict_tx);
+        let settings = Settings::load();
+        jobs.set_copy_workers(settings.copy_workers);
+        jobs.set_copy_error_mode(settings.copy_error_mode);
+        jobs.set_verify(settings.verify_copies);
 
-        // Initialize with home directory
-        {
-            let file_manager_clone = file_manager.clone();
-            runtime.spawn(async move {
-                let mut fm = file_manager_clone.lock().await;
-                let home_dir = dirs::home_dir().unwrap_or_else(|| std::path::PathBuf::from("/"));
-                if let Err(e) = fm.navigate_to(&home_dir).await {
-                    log::error!("Failed to navigate to home directory: {}", e);
-                }
-            });
-        }
+        // Reopen the tabs of last time, or the home directory
+        let vfs = file_manager.vfs();
+        let session = Session::load();
+        let mut pane_sessions = session.panes.into_iter();
+        let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/"));
+        let pane = Pane::restore(file_manager, pane_sessions.next().unwrap_or_default(), home_dir, jobs.clone(), &runtime);
+        let file_manager = pane.file_manager.clone();
 
-        Self {
-            file_manager: file_manager.clone(),
+        let mut app = Self {
+            panes: vec![pane],
+            active_pane: 0,
+            layout: session.layout,
+            switch_pane: false,
             bookmark_manager: bookmark_manager.clone(),
+            journal: journal.clone(),
+            jobs: jobs.clone(),
             toolbar: Toolbar::new(file_manager.clone()),
-            sidebar: Sidebar::new(bookmark_manager.clone()),
-            file_browser: FileBrowser::new(file_manager.clone()),
-            status_bar: StatusBar::new(file_manager.clone()),
-            tab_manager: TabManager::new(),
+            sidebar: Sidebar::new(bookmark_manager.clone(), jobs.clone(), FolderTree::new(vfs, runtime.handle().clone())),
+            status_bar: StatusBar::new(file_manager.clone(), jobs.clone()),
+            history_panel: HistoryPanel::new(journal),
+            duplicates_panel: DuplicatesPanel::new(file_manager.clone(), jobs.clone()),
+            disk_usage_panel: DiskUsagePanel::new(file_manager.clone()),
+            transfers_panel: TransfersPanel::new(jobs),
+            conflict_dialog: ConflictDialog::new(conflict_rx),
             runtime,
+        };
+
+        if app.layout.dual_pane {
+            app.open_second_pane(pane_sessions.next().unwrap_or_default());
         }
+        app.activate_pane(session.active_pane.min(app.panes.len() - 1));
+        app
     }
 
     fn setup_custom_fonts(ctx: &egui::Context) {
@@ -79,14 +256,36 @@ impl ChexExplorerApp {
 
 impl eframe::App for ChexExplorerApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if std::mem::take(&mut self.switch_pane) && self.layout.dual_pane {
+            self.activate_pane(1 - self.active_pane);
+        }
+
         // Handle file system watcher events
         self.handle_file_system_events();
+        self.follow_active_pane();
 
         // Top panel - Toolbar
         egui::TopBottomPanel::top("toolbar").show(ctx, |ui| {
-            self.toolbar.show(ui, &self.runtime);
+            ui.horizontal(|ui| {
+                self.toolbar.show(
+                    ui,
+                    &self.runtime,
+                    &mut self.history_panel.open,
+                    &mut self.transfers_panel.open,
+                    &mut self.duplicates_panel.open,
+                    &mut self.disk_usage_panel.open,
+                );
+                Toolbar::show_layout(ui, &mut self.layout);
+            });
         });
 
+        if self.layout.dual_pane && self.panes.len() < 2 {
+            self.open_second_pane(PaneSession::default());
+        }
+        if !self.layout.dual_pane && self.active_pane != 0 {
+            self.activate_pane(0);
+        }
+
         // Bottom panel - Status bar
         egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
             self.status_bar.show(ui, &self.runtime);
@@ -98,22 +297,25 @@ impl eframe::App for ChexExplorerApp {
             .default_width(200.0)
             .width_range(150.0..=400.0)
             .show(ctx, |ui| {
-                self.sidebar.show(ui, &self.runtime, &self.file_manager);
+                self.sidebar.show(ui, &self.runtime, &self.panes[self.active_pane].file_manager);
             });
 
         // Central panel - Main content area
         egui::CentralPanel::default().show(ctx, |ui| {
-            // Tab bar
-            ui.horizontal(|ui| {
-                self.tab_manager.show_tabs(ui);
-            });
-            
-            ui.separator();
-
-            // File browser
-            self.file_browser.show(ui, &self.runtime);
+            // Tab bar and file browser
+            if self.layout.dual_pane {
+                self.show_panes(ui);
+            } else {
+                self.panes[0].show(ui, &self.jobs, &self.runtime);
+            }
         });
 
+        self.history_panel.show(ctx, &self.runtime, &self.panes[self.active_pane].file_manager);
+        self.transfers_panel.show(ctx);
+        self.duplicates_panel.show(ctx, &self.runtime);
+        self.disk_usage_panel.show(ctx, &self.runtime);
+        self.conflict_dialog.show(ctx);
+
         // Handle keyboard shortcuts
         self.handle_keyboard_shortcuts(ctx);
 
@@ -121,6 +323,35 @@ impl eframe::App for ChexExplorerApp {
         ctx.request_repaint();
     }
 
+    fn raw_input_hook(&mut self, ctx: &egui::Context, raw_input: &mut egui::RawInput) {
+        // Tab switches panes unless a text field has the keyboard. It is taken
+        // out before egui sees it, as egui would move the focus to a widget
+        if !self.layout.dual_pane || ctx.memory(|memory| memory.focused().is_some()) {
+            return;
+        }
+        let mut switch_pane = false;
+        raw_input.events.retain(|event| match event {
+            egui::Event::Key { key: egui::Key::Tab, pressed, modifiers, .. } if !modifiers.command && !modifiers.alt => {
+                switch_pane |= *pressed;
+                false
+            }
+            _ => true,
+        });
+        self.switch_pane |= switch_pane;
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        let pane_count = if self.layout.dual_pane { self.panes.len() } else { 1 };
+        let session = Session {
+            panes: self.panes[..pane_count].iter().map(|pane| pane.session(&self.runtime)).collect(),
+            active_pane: self.active_pane,
+            layout: self.layout,
+        };
+        if let Err(e) = session.save() {
+            log::error!("Failed to save session: {}", e);
+        }
+    }
+
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
         // Save application state
         if let Ok(bookmark_manager) = self.bookmark_manager.try_lock() {
@@ -132,22 +363,145 @@ impl eframe::App for ChexExplorerApp {
 }
 
 impl ChexExplorerApp {
+    /// The file manager of the active pane.
+    fn file_manager(&self) -> &Arc<Mutex<FileManager>> {
+        &self.panes[self.active_pane].file_manager
+    }
+
+    /// Makes the pane at `index` the one the keyboard and toolbar work on.
+    fn activate_pane(&mut self, index: usize) {
+        self.active_pane = index;
+        for (pane_index, pane) in self.panes.iter_mut().enumerate() {
+            pane.browser.set_active(pane_index == index);
+        }
+
+        let file_manager = self.file_manager().clone();
+        self.toolbar.set_file_manager(file_manager.clone());
+        self.status_bar.set_file_manager(file_manager.clone());
+        self.duplicates_panel.set_file_manager(file_manager.clone());
+        self.disk_usage_panel.set_file_manager(file_manager);
+    }
+
+    /// Adds the right-hand pane with the tabs of `session`, or opening where
+    /// the left one is, sharing its filesystem and clipboard.
+    fn open_second_pane(&mut self, session: PaneSession) {
+        let (file_manager, path) = self.runtime.block_on(async {
+            let first = self.panes[0].file_manager.lock().await;
+            let path = first.get_current_path().await;
+            (FileManager::with_vfs(first.vfs(), path.clone()).with_shared_clipboard(&first), path)
+        });
+        let mut pane = Pane::restore(file_manager, session, path, self.jobs.clone(), &self.runtime);
+        pane.browser.set_active(false);
+        self.panes.push(pane);
+    }
+
+    fn show_panes(&mut self, ui: &mut egui::Ui) {
+        let mut clicked = None;
+        ui.columns(self.panes.len(), |columns| {
+            for (index, (pane, ui)) in self.panes.iter_mut().zip(columns).enumerate() {
+                let stroke = if index == self.active_pane {
+                    ui.visuals().selection.stroke
+                } else {
+                    ui.visuals().widgets.noninteractive.bg_stroke
+                };
+                let response = egui::Frame::group(ui.style()).stroke(stroke).show(ui, |ui| {
+                    ui.push_id(index, |ui| {
+                        ui.strong(pane.current_path(&self.runtime).display().to_string());
+                        pane.show(ui, &self.jobs, &self.runtime);
+                    });
+                }).response;
+
+                if ui.rect_contains_pointer(response.rect) && ui.input(|i| i.pointer.any_pressed()) {
+                    clicked = Some(index);
+                }
+            }
+        });
+
+        if let Some(index) = clicked.filter(|&index| index != self.active_pane) {
+            self.activate_pane(index);
+        }
+    }
+
+    /// With synchronized navigation on, the other pane follows the active
+    /// one into subfolders and back up. Jumps elsewhere are not followed.
+    fn follow_active_pane(&mut self) {
+        let paths: Vec<PathBuf> = self.panes.iter()
+            .map(|pane| self.runtime.block_on(async { pane.file_manager.lock().await.get_current_path().await }))
+            .collect();
+        let from = self.panes[self.active_pane].last_path.clone();
+        for (pane, path) in self.panes.iter_mut().zip(&paths) {
+            pane.last_path = path.clone();
+        }
+
+        let to = &paths[self.active_pane];
+        if !self.layout.dual_pane || !self.layout.sync_navigation || self.panes.len() < 2 || from == *to {
+            return;
+        }
+        let other = 1 - self.active_pane;
+        let other_path = &paths[other];
+        let target = if let Ok(down) = to.strip_prefix(&from) {
+            Some(other_path.join(down))
+        } else if let Ok(up) = from.strip_prefix(to) {
+            other_path.ancestors().nth(up.components().count()).map(Path::to_path_buf)
+        } else {
+            None
+        };
+
+        if let Some(target) = target {
+            let file_manager = self.panes[other].file_manager.clone();
+            self.runtime.spawn(async move {
+                if let Err(e) = FileManager::navigate(&file_manager, &target).await {
+                    log::debug!("Other pane can't follow to {}: {}", target.display(), e);
+                }
+            });
+        }
+    }
+
+    /// Copies the active pane's selection, or moves it with `move_files`,
+    /// into the other pane's folder. Without a selection the focused item goes.
+    fn send_to_other_pane(&self, move_files: bool) {
+        let Some(other) = self.panes.get(1 - self.active_pane) else {
+            return;
+        };
+        let (sources, destination) = self.runtime.block_on(async {
+            let fm = self.file_manager().lock().await;
+            let mut sources = fm.selected_paths().await;
+            if sources.is_empty() {
+                sources.extend(fm.focused_item().await.map(|item| item.path));
+            }
+            (sources, other.file_manager.lock().await.get_current_path().await)
+        });
+        if sources.is_empty() || sources.iter().all(|path| path.parent() == Some(destination.as_path())) {
+            return;
+        }
+
+        self.jobs.enqueue(if move_files {
+            JobKind::Move { sources, destination }
+        } else {
+            JobKind::Copy { sources, destination }
+        });
+    }
+
     fn handle_file_system_events(&mut self) {
+        let jobs_finished = self.jobs.take_finished();
+
         // Handle file system watcher events in a non-blocking way
-        self.runtime.spawn({
-            let file_manager = self.file_manager.clone();
-            async move {
+        for pane in &self.panes {
+            let file_manager = pane.file_manager.clone();
+            self.runtime.spawn(async move {
                 let mut fm = file_manager.lock().await;
-                let events = fm.check_file_changes();
+                if let Err(e) = fm.apply_file_changes().await {
+                    log::error!("Failed to apply file system changes: {}", e);
+                }
                 
-                if !events.is_empty() {
-                    // Refresh the file list if there were changes
+                // Remote folders aren't watched, so refresh them when a job finishes
+                if jobs_finished && !fm.is_watched() {
                     if let Err(e) = fm.refresh_items().await {
-                        log::error!("Failed to refresh items after file system event: {}", e);
+                        log::error!("Failed to refresh items after a job: {}", e);
                     }
                 }
-            }
-        });
+            });
+        }
     }
 
     fn handle_keyboard_shortcuts(&mut self, ctx: &egui::Context) {
@@ -156,7 +510,7 @@ impl ChexExplorerApp {
         // Ctrl+A - Select All
         if input.modifiers.ctrl && input.key_pressed(egui::Key::A) {
             self.runtime.spawn({
-                let file_manager = self.file_manager.clone();
+                let file_manager = self.file_manager().clone();
                 async move {
                     let fm = file_manager.lock().await;
                     fm.select_all().await;
@@ -164,10 +518,33 @@ impl ChexExplorerApp {
             });
         }
 
+        // Ctrl+I - Invert Selection
+        if input.modifiers.ctrl && input.key_pressed(egui::Key::I) {
+            self.runtime.spawn({
+                let file_manager = self.file_manager().clone();
+                async move {
+                    let fm = file_manager.lock().await;
+                    fm.invert_selection().await;
+                }
+            });
+        }
+
+        // Ctrl+T / Ctrl+W - Open this folder in a new tab / Close the tab
+        if input.modifiers.ctrl && input.key_pressed(egui::Key::T) {
+            let pane = &mut self.panes[self.active_pane];
+            pane.open_tab(pane.current_path(&self.runtime), &self.runtime);
+        }
+        if input.modifiers.ctrl && input.key_pressed(egui::Key::W) {
+            let pane = &mut self.panes[self.active_pane];
+            if let Some(tab_id) = pane.tabs.get_active_tab().map(|tab| tab.id) {
+                pane.close_tab(tab_id, &self.runtime);
+            }
+        }
+
         // Escape - Clear Selection
         if input.key_pressed(egui::Key::Escape) {
             self.runtime.spawn({
-                let file_manager = self.file_manager.clone();
+                let file_manager = self.file_manager().clone();
                 async move {
                     let fm = file_manager.lock().await;
                     fm.clear_selection().await;
@@ -175,10 +552,20 @@ impl ChexExplorerApp {
             });
         }
 
-        // F5 - Refresh
-        if input.key_pressed(egui::Key::F5) {
+        // F5 / F6 - Copy / Move the selection to the other pane, in dual-pane mode
+        if self.layout.dual_pane && !ctx.wants_keyboard_input() {
+            if input.key_pressed(egui::Key::F5) {
+                self.send_to_other_pane(false);
+            }
+            if input.key_pressed(egui::Key::F6) {
+                self.send_to_other_pane(true);
+            }
+        }
+
+        // F5 - Refresh, or Ctrl+R in dual-pane mode
+        if (input.key_pressed(egui::Key::F5) && !self.layout.dual_pane) || (input.modifiers.ctrl && input.key_pressed(egui::Key::R)) {
             self.runtime.spawn({
-                let file_manager = self.file_manager.clone();
+                let file_manager = self.file_manager().clone();
                 async move {
                     let mut fm = file_manager.lock().await;
                     if let Err(e) = fm.refresh_items().await {
@@ -191,7 +578,7 @@ impl ChexExplorerApp {
         // Alt+Left - Go Back
         if input.modifiers.alt && input.key_pressed(egui::Key::ArrowLeft) {
             self.runtime.spawn({
-                let file_manager = self.file_manager.clone();
+                let file_manager = self.file_manager().clone();
                 async move {
                     let mut fm = file_manager.lock().await;
                     if let Err(e) = fm.go_back().await {
@@ -204,7 +591,7 @@ impl ChexExplorerApp {
         // Alt+Right - Go Forward
         if input.modifiers.alt && input.key_pressed(egui::Key::ArrowRight) {
             self.runtime.spawn({
-                let file_manager = self.file_manager.clone();
+                let file_manager = self.file_manager().clone();
                 async move {
                     let mut fm = file_manager.lock().await;
                     if let Err(e) = fm.go_forward().await {
@@ -217,20 +604,32 @@ impl ChexExplorerApp {
         // Alt+Up - Go Up
         if input.modifiers.alt && input.key_pressed(egui::Key::ArrowUp) {
             self.runtime.spawn({
-                let file_manager = self.file_manager.clone();
+                let file_manager = self.file_manager().clone();
                 async move {
-                    let mut fm = file_manager.lock().await;
-                    if let Err(e) = fm.go_up().await {
+                    if let Err(e) = FileManager::navigate_up(&file_manager).await {
                         log::error!("Failed to go up: {}", e);
                     }
                 }
             });
         }
 
+        // Ctrl+Z / Ctrl+Y - Undo / Redo file operations, unless a text field has focus
+        if !ctx.wants_keyboard_input() {
+            if input.modifiers.ctrl && !input.modifiers.shift && input.key_pressed(egui::Key::Z) {
+                HistoryPanel::undo(&self.runtime, &self.journal, self.file_manager());
+            }
+
+            if input.modifiers.ctrl
+                && (input.key_pressed(egui::Key::Y) || (input.modifiers.shift && input.key_pressed(egui::Key::Z)))
+            {
+                HistoryPanel::redo(&self.runtime, &self.journal, self.file_manager());
+            }
+        }
+
         // Ctrl+H - Toggle Hidden Files
         if input.modifiers.ctrl && input.key_pressed(egui::Key::H) {
             self.runtime.spawn({
-                let file_manager = self.file_manager.clone();
+                let file_manager = self.file_manager().clone();
                 async move {
                     let mut fm = file_manager.lock().await;
                     if let Err(e) = fm.toggle_show_hidden().await {
@@ -1,5 +1,4 @@
 // Context menu functionality for file operations
-use eframe::egui;
 
 pub struct ContextMenu {
     // TODO: Implement context menu functionality
@@ -1,5 +1,11 @@
 // Dialog boxes for various operations
+
 use eframe::egui;
+use std::time::SystemTime;
+use tokio::sync::mpsc;
+
+use crate::operations::conflict::{ConflictAnswer, ConflictRequest, ConflictResolution};
+use crate::utils::format::{format_file_size, format_timestamp};
 
 pub struct Dialogs {
     // TODO: Implement dialogs for rename, delete confirmation, properties, etc.
@@ -16,3 +22,120 @@ impl Default for Dialogs {
         Self::new()
     }
 }
+
+/// Asks how to handle a file that already exists at a copy or move destination,
+/// showing both files side by side. Requests are answered one at a time.
+pub struct ConflictDialog {
+    requests: mpsc::UnboundedReceiver<ConflictRequest>,
+    current: Option<ConflictRequest>,
+    apply_to_all: bool,
+}
+
+impl ConflictDialog {
+    pub fn new(requests: mpsc::UnboundedReceiver<ConflictRequest>) -> Self {
+        Self {
+            requests,
+            current: None,
+            apply_to_all: false,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        if self.current.is_none() {
+            self.current = self.requests.try_recv().ok();
+            self.apply_to_all = false;
+        }
+        let Some(ref request) = self.current else {
+            return;
+        };
+        let conflict = &request.conflict;
+
+        let mut answer = None;
+        egui::Window::new("Replace or Skip")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                let name = conflict.destination.file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_else(|| conflict.destination.display().to_string());
+                let folder = conflict.destination.parent().unwrap_or(&conflict.destination);
+                ui.label(format!("\"{}\" already exists in {}", name, folder.display()));
+                ui.add_space(6.0);
+
+                egui::Grid::new("conflict_files")
+                    .num_columns(3)
+                    .spacing([16.0, 4.0])
+                    .show(ui, |ui| {
+                        ui.label("");
+                        ui.strong("Existing");
+                        ui.strong("Incoming");
+                        ui.end_row();
+
+                        ui.label("Size");
+                        ui.label(describe_size(conflict.destination_size, conflict.is_dir));
+                        ui.label(describe_size(conflict.source_size, false));
+                        ui.end_row();
+
+                        let newer = match (conflict.source_modified, conflict.destination_modified) {
+                            (Some(source), Some(destination)) => source.cmp(&destination),
+                            _ => std::cmp::Ordering::Equal,
+                        };
+                        ui.label("Modified");
+                        ui.label(describe_modified(conflict.destination_modified, newer.is_lt()));
+                        ui.label(describe_modified(conflict.source_modified, newer.is_gt()));
+                        ui.end_row();
+                    });
+
+                ui.add_space(6.0);
+                ui.checkbox(&mut self.apply_to_all, "Do this for all remaining conflicts");
+                ui.separator();
+
+                ui.horizontal_wrapped(|ui| {
+                    if ui.button("Overwrite").clicked() {
+                        answer = Some(ConflictResolution::Overwrite);
+                    }
+                    if ui.button("Overwrite if newer").clicked() {
+                        answer = Some(ConflictResolution::OverwriteIfNewer);
+                    }
+                    if ui.button("Overwrite if size differs").clicked() {
+                        answer = Some(ConflictResolution::OverwriteIfDifferentSize);
+                    }
+                    if ui.button("Keep both").clicked() {
+                        answer = Some(ConflictResolution::KeepBoth);
+                    }
+                    if ui.button("Skip").clicked() {
+                        answer = Some(ConflictResolution::Skip);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        answer = Some(ConflictResolution::Cancel);
+                    }
+                });
+            });
+
+        if let Some(resolution) = answer {
+            if let Some(request) = self.current.take() {
+                let _ = request.reply.send(ConflictAnswer {
+                    resolution,
+                    apply_to_all: self.apply_to_all,
+                });
+            }
+        }
+    }
+}
+
+fn describe_size(size: u64, is_dir: bool) -> String {
+    if is_dir {
+        "Folder".to_string()
+    } else {
+        format_file_size(size)
+    }
+}
+
+fn describe_modified(modified: Option<SystemTime>, newer: bool) -> String {
+    match modified {
+        Some(modified) if newer => format!("{} (newer)", format_timestamp(modified.into())),
+        Some(modified) => format_timestamp(modified.into()),
+        None => "Unknown".to_string(),
+    }
+}
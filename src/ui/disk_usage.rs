@@ -0,0 +1,511 @@
+use eframe::egui;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+
+use crate::core::disk_usage::{DiskUsage, UsageNode, UsageProgress, UsageScanner};
+use crate::core::file_manager::FileManager;
+use crate::operations::control::{Cancelled, JobControl};
+use crate::utils::format::format_file_size;
+
+/// Entries listed under one folder before the rest are summed up.
+const MAX_ROWS: usize = 200;
+
+/// Entries drawn in the treemap per folder; smaller ones are left as empty space.
+const MAX_TILES: usize = 300;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortBy {
+    Size,
+    Name,
+    Files,
+}
+
+impl SortBy {
+    const ALL: [SortBy; 3] = [SortBy::Size, SortBy::Name, SortBy::Files];
+
+    fn label(self) -> &'static str {
+        match self {
+            SortBy::Size => "Size",
+            SortBy::Name => "Name",
+            SortBy::Files => "Files",
+        }
+    }
+}
+
+/// Something picked in the tree or treemap, applied once drawing is done.
+enum Action {
+    Focus(PathBuf),
+    Rescan(PathBuf),
+    Browse(PathBuf),
+}
+
+/// A scan running in the background.
+struct Scan {
+    control: JobControl,
+    progress_rx: mpsc::UnboundedReceiver<UsageProgress>,
+    progress: Option<UsageProgress>,
+    handle: JoinHandle<anyhow::Result<DiskUsage>>,
+}
+
+/// Window showing which folders take up space below a folder, as a tree with
+/// percentage bars and as a treemap.
+pub struct DiskUsagePanel {
+    file_manager: Arc<Mutex<FileManager>>,
+    pub open: bool,
+    usage: Option<DiskUsage>,
+    /// The folder being looked at, within `usage`.
+    focus: PathBuf,
+    sort: SortBy,
+    scan: Option<Scan>,
+    status: Option<String>,
+}
+
+impl DiskUsagePanel {
+    pub fn new(file_manager: Arc<Mutex<FileManager>>) -> Self {
+        Self {
+            file_manager,
+            open: false,
+            usage: None,
+            focus: PathBuf::new(),
+            sort: SortBy::Size,
+            scan: None,
+            status: None,
+        }
+    }
+
+    /// Points the panel at `file_manager`, the pane whose folder it scans
+    /// and opens folders in.
+    pub fn set_file_manager(&mut self, file_manager: Arc<Mutex<FileManager>>) {
+        self.file_manager = file_manager;
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, runtime: &tokio::runtime::Runtime) {
+        self.poll(runtime);
+
+        if !self.open {
+            return;
+        }
+
+        let mut open = self.open;
+        let mut action = None;
+        egui::Window::new("Disk Usage")
+            .open(&mut open)
+            .default_width(760.0)
+            .default_height(520.0)
+            .resizable(true)
+            .show(ctx, |ui| {
+                self.show_scan_controls(ui, runtime);
+                ui.separator();
+
+                if let Some(ref status) = self.status {
+                    ui.label(status);
+                }
+
+                let Some(focused) = self.usage.as_ref().and_then(|usage| usage.root.find(&self.focus)) else {
+                    return;
+                };
+                let root = &self.usage.as_ref().expect("focused node comes from usage").root;
+
+                Self::show_breadcrumb(ui, root, focused, &mut action);
+                ui.horizontal(|ui| {
+                    ui.strong(format!("{} in {} files", format_file_size(focused.size), focused.files));
+                    if focused.errors > 0 {
+                        ui.weak(format!("({} unreadable entries)", focused.errors));
+                    }
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        egui::ComboBox::from_id_source("disk_usage_sort")
+                            .selected_text(self.sort.label())
+                            .show_ui(ui, |ui| {
+                                for sort in SortBy::ALL {
+                                    ui.selectable_value(&mut self.sort, sort, sort.label());
+                                }
+                            });
+                        ui.label("Sort by:");
+                    });
+                });
+                ui.separator();
+
+                let sort = self.sort;
+                ui.columns(2, |columns| {
+                    egui::ScrollArea::vertical()
+                        .id_source("disk_usage_tree")
+                        .auto_shrink([false, false])
+                        .show(&mut columns[0], |ui| {
+                            Self::show_children(ui, focused, sort, &mut action);
+                        });
+                    Self::show_treemap(&mut columns[1], focused, &mut action);
+                });
+            });
+        self.open = open;
+
+        if let Some(action) = action {
+            self.apply(action, runtime);
+        }
+    }
+
+    fn show_scan_controls(&mut self, ui: &mut egui::Ui, runtime: &tokio::runtime::Runtime) {
+        ui.horizontal(|ui| {
+            let scanning = self.scan.is_some();
+
+            if ui.add_enabled(!scanning, egui::Button::new("Scan Current Folder")).clicked() {
+                let root = runtime.block_on(async { self.file_manager.lock().await.get_current_path().await });
+                self.start(root, runtime);
+            }
+            if ui.add_enabled(!scanning, egui::Button::new("Choose Folder…")).clicked() {
+                if let Some(root) = rfd::FileDialog::new().pick_folder() {
+                    self.start(root, runtime);
+                }
+            }
+            if ui.add_enabled(!scanning && self.usage.is_some(), egui::Button::new("⟳ Refresh"))
+                .on_hover_text("Read again the folders that changed since the scan")
+                .clicked()
+            {
+                self.refresh(runtime);
+            }
+        });
+
+        if let Some(ref scan) = self.scan {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                match scan.progress {
+                    Some(ref progress) => {
+                        ui.label(format!(
+                            "{} files, {} — {}",
+                            progress.entries,
+                            format_file_size(progress.bytes),
+                            progress.current_dir.display()
+                        ));
+                    }
+                    None => {
+                        ui.label("Scanning…");
+                    }
+                }
+                if ui.button("Stop").clicked() {
+                    scan.control.cancel();
+                }
+            });
+        }
+    }
+
+    fn show_breadcrumb(ui: &mut egui::Ui, root: &UsageNode, focused: &UsageNode, action: &mut Option<Action>) {
+        ui.horizontal_wrapped(|ui| {
+            let up = focused.path.parent().filter(|_| focused.path != root.path);
+            if ui.add_enabled(up.is_some(), egui::Button::new("⬆")).on_hover_text("Up").clicked() {
+                *action = up.map(|parent| Action::Focus(parent.to_path_buf()));
+            }
+
+            let mut path = root.path.clone();
+            if ui.button(&root.name).clicked() {
+                *action = Some(Action::Focus(path.clone()));
+            }
+            if let Ok(relative) = focused.path.strip_prefix(&root.path) {
+                for component in relative.components() {
+                    path.push(component);
+                    ui.label("›");
+                    if ui.button(component.as_os_str().to_string_lossy()).clicked() {
+                        *action = Some(Action::Focus(path.clone()));
+                    }
+                }
+            }
+        });
+    }
+
+    /// Lists the entries of `node`, folders expanding in place.
+    fn show_children(ui: &mut egui::Ui, node: &UsageNode, sort: SortBy, action: &mut Option<Action>) {
+        let mut children: Vec<&UsageNode> = node.children.iter().collect();
+        match sort {
+            SortBy::Size => {}
+            SortBy::Name => children.sort_by_cached_key(|child| child.name.to_lowercase()),
+            SortBy::Files => children.sort_by_key(|child| std::cmp::Reverse(child.files)),
+        }
+
+        for child in children.iter().take(MAX_ROWS) {
+            if child.is_dir {
+                let id = ui.make_persistent_id(&child.path);
+                egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, false)
+                    .show_header(ui, |ui| Self::show_row(ui, child, node.size, action))
+                    .body(|ui| Self::show_children(ui, child, sort, action));
+            } else {
+                ui.horizontal(|ui| {
+                    ui.add_space(ui.spacing().indent);
+                    Self::show_row(ui, child, node.size, action);
+                });
+            }
+        }
+
+        if children.len() > MAX_ROWS {
+            let rest = &children[MAX_ROWS..];
+            ui.weak(format!(
+                "… {} more, {}",
+                rest.len(),
+                format_file_size(rest.iter().map(|child| child.size).sum())
+            ));
+        }
+    }
+
+    fn show_row(ui: &mut egui::Ui, node: &UsageNode, parent_size: u64, action: &mut Option<Action>) {
+        let fraction = if parent_size > 0 { node.size as f32 / parent_size as f32 } else { 0.0 };
+        let icon = if node.is_dir { "📁" } else { "📄" };
+
+        let response = ui.selectable_label(false, format!("{} {}", icon, node.name));
+        if node.is_dir {
+            if response.double_clicked() {
+                *action = Some(Action::Focus(node.path.clone()));
+            }
+            response.context_menu(|ui| {
+                if ui.button("Show Here").clicked() {
+                    *action = Some(Action::Focus(node.path.clone()));
+                    ui.close_menu();
+                }
+                if ui.button("Rescan").clicked() {
+                    *action = Some(Action::Rescan(node.path.clone()));
+                    ui.close_menu();
+                }
+                if ui.button("Open in Browser").clicked() {
+                    *action = Some(Action::Browse(node.path.clone()));
+                    ui.close_menu();
+                }
+            });
+        }
+
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            if node.is_dir {
+                ui.weak(format!("{} files", node.files));
+            }
+            ui.add(
+                egui::ProgressBar::new(fraction)
+                    .desired_width(90.0)
+                    .text(format!("{:.1}%", fraction * 100.0)),
+            );
+            ui.label(format_file_size(node.size));
+        });
+    }
+
+    /// Draws the entries of `node` as nested rectangles sized by the space
+    /// they use. Clicking a folder shows it.
+    fn show_treemap(ui: &mut egui::Ui, node: &UsageNode, action: &mut Option<Action>) {
+        let size = ui.available_size().max(egui::vec2(120.0, 200.0));
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+        let pointer = response.hover_pos();
+        let mut hovered = None;
+        Self::draw_tiles(&painter, node, rect.shrink(1.0), 0, pointer, &mut hovered);
+
+        if let Some(hovered) = hovered {
+            if response.clicked() {
+                let folder = if hovered.is_dir { Some(hovered.path.as_path()) } else { hovered.path.parent() };
+                *action = folder.map(|folder| Action::Focus(folder.to_path_buf()));
+            }
+            response.on_hover_text(format!("{}\n{}", hovered.path.display(), format_file_size(hovered.size)));
+        }
+    }
+
+    fn draw_tiles<'a>(
+        painter: &egui::Painter,
+        node: &'a UsageNode,
+        rect: egui::Rect,
+        depth: usize,
+        pointer: Option<egui::Pos2>,
+        hovered: &mut Option<&'a UsageNode>,
+    ) {
+        if node.size == 0 {
+            return;
+        }
+        let scale = rect.area() / node.size as f32;
+        let tiles: Vec<&UsageNode> = node.children.iter()
+            .filter(|child| child.size > 0)
+            .take(MAX_TILES)
+            .collect();
+        let areas: Vec<f32> = tiles.iter().map(|child| child.size as f32 * scale).collect();
+
+        for (child, tile) in tiles.into_iter().zip(squarify(&areas, rect)) {
+            if tile.width() < 1.0 || tile.height() < 1.0 {
+                continue;
+            }
+            painter.rect_filled(tile.shrink(0.5), 2.0, tile_color(child, depth));
+            if pointer.is_some_and(|pointer| tile.contains(pointer)) {
+                *hovered = Some(child);
+            }
+
+            let text_color = egui::Color32::from_gray(230);
+            let font = egui::FontId::proportional(11.0);
+            let labelled = tile.width() > 48.0 && tile.height() > 16.0;
+            if labelled {
+                painter.with_clip_rect(tile.shrink(2.0)).text(
+                    tile.left_top() + egui::vec2(3.0, 2.0),
+                    egui::Align2::LEFT_TOP,
+                    &child.name,
+                    font,
+                    text_color,
+                );
+            }
+
+            // Show one level inside each folder, below its name
+            if child.is_dir && depth == 0 && labelled && tile.height() > 40.0 {
+                let inner = egui::Rect::from_min_max(tile.min + egui::vec2(3.0, 16.0), tile.max - egui::vec2(3.0, 3.0));
+                Self::draw_tiles(painter, child, inner, depth + 1, pointer, hovered);
+            }
+        }
+    }
+
+    fn start(&mut self, root: PathBuf, runtime: &tokio::runtime::Runtime) {
+        self.usage = None;
+        self.focus = root.clone();
+        self.run(runtime, move |scanner| scanner.scan(&root));
+    }
+
+    fn refresh(&mut self, runtime: &tokio::runtime::Runtime) {
+        let Some(mut usage) = self.usage.clone() else {
+            return;
+        };
+        self.run(runtime, move |scanner| {
+            scanner.refresh(&mut usage)?;
+            Ok(usage)
+        });
+    }
+
+    fn rescan(&mut self, path: PathBuf, runtime: &tokio::runtime::Runtime) {
+        let Some(mut usage) = self.usage.clone() else {
+            return;
+        };
+        self.run(runtime, move |scanner| {
+            scanner.rescan(&mut usage, &path)?;
+            Ok(usage)
+        });
+    }
+
+    /// Runs `work` on a blocking thread. The current results stay on screen
+    /// until it finishes.
+    fn run<F>(&mut self, runtime: &tokio::runtime::Runtime, work: F)
+    where
+        F: FnOnce(&mut UsageScanner) -> anyhow::Result<DiskUsage> + Send + 'static,
+    {
+        let control = JobControl::new();
+        let (progress_tx, progress_rx) = mpsc::unbounded_channel();
+        let mut scanner = UsageScanner::new()
+            .with_progress_callback(progress_tx)
+            .with_control(control.clone());
+        let handle = runtime.spawn_blocking(move || work(&mut scanner));
+
+        self.status = None;
+        self.scan = Some(Scan { control, progress_rx, progress: None, handle });
+    }
+
+    /// Picks up progress and the result of a finished scan.
+    fn poll(&mut self, runtime: &tokio::runtime::Runtime) {
+        let Some(ref mut scan) = self.scan else {
+            return;
+        };
+        while let Ok(progress) = scan.progress_rx.try_recv() {
+            scan.progress = Some(progress);
+        }
+        if !scan.handle.is_finished() {
+            return;
+        }
+
+        let scan = self.scan.take().expect("scan checked above");
+        match runtime.block_on(scan.handle) {
+            Ok(Ok(usage)) => {
+                if usage.root.find(&self.focus).is_none() {
+                    self.focus = usage.root.path.clone();
+                }
+                self.usage = Some(usage);
+            }
+            Ok(Err(e)) if e.is::<Cancelled>() => self.status = Some("Scan stopped".to_string()),
+            Ok(Err(e)) => self.status = Some(format!("Scan failed: {}", e)),
+            Err(e) => self.status = Some(format!("Scan failed: {}", e)),
+        }
+    }
+
+    fn apply(&mut self, action: Action, runtime: &tokio::runtime::Runtime) {
+        match action {
+            Action::Focus(path) => self.focus = path,
+            Action::Rescan(path) => self.rescan(path, runtime),
+            Action::Browse(path) => {
+                let file_manager = self.file_manager.clone();
+                runtime.spawn(async move {
+                    if let Err(e) = FileManager::navigate(&file_manager, &path).await {
+                        log::error!("Failed to open {}: {}", path.display(), e);
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Lays out `areas`, largest first and summing to at most the area of `rect`,
+/// as rectangles kept as close to square as possible.
+fn squarify(areas: &[f32], mut rect: egui::Rect) -> Vec<egui::Rect> {
+    let mut tiles = Vec::with_capacity(areas.len());
+    let mut start = 0;
+    while start < areas.len() {
+        let side = rect.width().min(rect.height());
+        if side <= 0.0 {
+            break;
+        }
+
+        // Grow the row while that makes its worst tile squarer
+        let mut end = start + 1;
+        let mut sum = areas[start];
+        let mut worst = worst_ratio(sum, areas[start], areas[start], side);
+        while end < areas.len() {
+            let ratio = worst_ratio(sum + areas[end], areas[start], areas[end], side);
+            if ratio > worst {
+                break;
+            }
+            sum += areas[end];
+            worst = ratio;
+            end += 1;
+        }
+
+        if rect.width() >= rect.height() {
+            let width = sum / rect.height();
+            let mut y = rect.top();
+            for &area in &areas[start..end] {
+                let height = area / width;
+                tiles.push(egui::Rect::from_min_size(egui::pos2(rect.left(), y), egui::vec2(width, height)));
+                y += height;
+            }
+            rect.min.x += width;
+        } else {
+            let height = sum / rect.width();
+            let mut x = rect.left();
+            for &area in &areas[start..end] {
+                let width = area / height;
+                tiles.push(egui::Rect::from_min_size(egui::pos2(x, rect.top()), egui::vec2(width, height)));
+                x += width;
+            }
+            rect.min.y += height;
+        }
+        start = end;
+    }
+    tiles
+}
+
+/// The most elongated aspect ratio in a row of tiles with the given total,
+/// largest and smallest areas, laid along `side`.
+fn worst_ratio(sum: f32, largest: f32, smallest: f32, side: f32) -> f32 {
+    let sum_squared = sum * sum;
+    let side_squared = side * side;
+    (side_squared * largest / sum_squared).max(sum_squared / (side_squared * smallest))
+}
+
+/// Folders are shaded by depth, files coloured by extension.
+fn tile_color(node: &UsageNode, depth: usize) -> egui::Color32 {
+    if node.is_dir {
+        let shade = 70u8.saturating_sub(depth as u8 * 15);
+        return egui::Color32::from_rgb(shade, shade + 10, shade + 30);
+    }
+
+    let extension = Path::new(&node.name).extension().map(|ext| ext.to_string_lossy().to_lowercase());
+    let mut hasher = DefaultHasher::new();
+    extension.hash(&mut hasher);
+    let hue = (hasher.finish() % 360) as f32 / 360.0;
+    egui::ecolor::Hsva::new(hue, 0.45, 0.55, 1.0).into()
+}
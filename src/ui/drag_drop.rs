@@ -0,0 +1,107 @@
+use eframe::egui;
+use std::path::{Path, PathBuf};
+
+use crate::operations::jobs::{JobKind, JobManager};
+
+/// Files being dragged within the explorer.
+#[derive(Debug, Clone)]
+pub struct DraggedFiles {
+    pub paths: Vec<PathBuf>,
+}
+
+/// What dropping files does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropAction {
+    Move,
+    Copy,
+    Link,
+}
+
+impl DropAction {
+    /// Files move by default; Ctrl (Cmd on macOS) copies them and Alt links them.
+    pub fn from_modifiers(modifiers: egui::Modifiers) -> Self {
+        if modifiers.command {
+            DropAction::Copy
+        } else if modifiers.alt {
+            DropAction::Link
+        } else {
+            DropAction::Move
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DropAction::Move => "Move",
+            DropAction::Copy => "Copy",
+            DropAction::Link => "Link",
+        }
+    }
+
+    pub fn job(self, sources: Vec<PathBuf>, destination: PathBuf) -> JobKind {
+        match self {
+            DropAction::Move => JobKind::Move { sources, destination },
+            DropAction::Copy => JobKind::Copy { sources, destination },
+            DropAction::Link => JobKind::Link { sources, destination },
+        }
+    }
+}
+
+/// Starts dragging the files `paths` gives when `response` starts being dragged.
+pub fn drag_source(response: &egui::Response, paths: impl FnOnce() -> Vec<PathBuf>) {
+    if response.drag_started() {
+        let paths = paths();
+        if !paths.is_empty() {
+            egui::DragAndDrop::set_payload(&response.ctx, DraggedFiles { paths });
+        }
+    }
+}
+
+/// Lets files dragged within the explorer be dropped on `rect`, which
+/// stands for the folder `destination`. Outlines it while files that can go
+/// there are held over it, and queues the job for them once they are dropped.
+/// Returns whether files are held over it.
+pub fn drop_target(ui: &egui::Ui, rect: egui::Rect, destination: &Path, jobs: &JobManager) -> bool {
+    if !ui.rect_contains_pointer(rect) {
+        return false;
+    }
+    let Some(dragged) = egui::DragAndDrop::payload::<DraggedFiles>(ui.ctx()) else {
+        return false;
+    };
+
+    // A folder can't go inside itself, and dropping files where they already are does nothing
+    let sources: Vec<PathBuf> = dragged.paths.iter()
+        .filter(|path| !destination.starts_with(path) && path.parent() != Some(destination))
+        .cloned()
+        .collect();
+    if sources.is_empty() {
+        return false;
+    }
+
+    let action = DropAction::from_modifiers(ui.input(|input| input.modifiers));
+    ui.painter().rect_stroke(rect, 2.0, ui.visuals().selection.stroke);
+    egui::show_tooltip_at_pointer(ui.ctx(), ui.layer_id(), egui::Id::new("drop_target"), |ui| {
+        let what = match sources.as_slice() {
+            [path] => path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.display().to_string()),
+            _ => format!("{} items", sources.len()),
+        };
+        ui.label(format!("{} {} to {}", action.label(), what, destination.display()));
+    });
+
+    if ui.input(|input| input.pointer.any_released())
+        && egui::DragAndDrop::take_payload::<DraggedFiles>(ui.ctx()).is_some()
+    {
+        jobs.enqueue(action.job(sources, destination.to_path_buf()));
+    }
+    true
+}
+
+/// Files dropped onto the window from other applications this frame.
+pub fn dropped_files(ctx: &egui::Context) -> Vec<PathBuf> {
+    ctx.input(|input| {
+        input.raw.dropped_files.iter()
+            .filter_map(|file| file.path.clone())
+            .collect()
+    })
+}
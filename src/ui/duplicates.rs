@@ -0,0 +1,347 @@
+use eframe::egui;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+
+use crate::core::duplicates::{DuplicateFile, DuplicateFinder, DuplicateGroup, DuplicateProgress, KeepRule};
+use crate::core::file_manager::FileManager;
+use crate::operations::control::{Cancelled, JobControl};
+use crate::operations::dedupe::{self, LinkReport};
+use crate::operations::jobs::{JobKind, JobManager};
+use crate::utils::format::{format_file_size, format_timestamp};
+
+/// A search running in the background.
+struct Search {
+    control: JobControl,
+    progress_rx: mpsc::UnboundedReceiver<DuplicateProgress>,
+    progress: Option<DuplicateProgress>,
+    handle: JoinHandle<anyhow::Result<Vec<DuplicateGroup>>>,
+}
+
+/// Window that finds duplicate files below a folder and removes or hard-links
+/// the extra copies.
+pub struct DuplicatesPanel {
+    file_manager: Arc<Mutex<FileManager>>,
+    jobs: JobManager,
+    pub open: bool,
+    root: Option<PathBuf>,
+    min_size_kb: u64,
+    search: Option<Search>,
+    groups: Vec<DuplicateGroup>,
+    /// Files picked for removal or linking.
+    marked: HashSet<PathBuf>,
+    /// Duplicates being replaced with hard links.
+    linking: Option<(Vec<PathBuf>, JoinHandle<LinkReport>)>,
+    status: Option<String>,
+}
+
+impl DuplicatesPanel {
+    pub fn new(file_manager: Arc<Mutex<FileManager>>, jobs: JobManager) -> Self {
+        Self {
+            file_manager,
+            jobs,
+            open: false,
+            root: None,
+            min_size_kb: 0,
+            search: None,
+            groups: Vec::new(),
+            marked: HashSet::new(),
+            linking: None,
+            status: None,
+        }
+    }
+
+    /// Points the panel at `file_manager`, the pane whose folder it searches
+    /// and opens results in.
+    pub fn set_file_manager(&mut self, file_manager: Arc<Mutex<FileManager>>) {
+        self.file_manager = file_manager;
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, runtime: &tokio::runtime::Runtime) {
+        self.poll(runtime);
+
+        if !self.open {
+            return;
+        }
+
+        let mut open = self.open;
+        egui::Window::new("Duplicate Files")
+            .open(&mut open)
+            .default_width(560.0)
+            .default_height(480.0)
+            .resizable(true)
+            .show(ctx, |ui| {
+                self.show_search_controls(ui, runtime);
+                ui.separator();
+
+                if let Some(ref status) = self.status {
+                    ui.label(status);
+                }
+
+                if self.groups.is_empty() {
+                    if self.search.is_none() && self.root.is_some() {
+                        ui.label("No duplicates found");
+                    }
+                    return;
+                }
+
+                self.show_selection_controls(ui, runtime);
+                ui.separator();
+                self.show_groups(ui);
+            });
+        self.open = open;
+    }
+
+    fn show_search_controls(&mut self, ui: &mut egui::Ui, runtime: &tokio::runtime::Runtime) {
+        ui.horizontal(|ui| {
+            ui.label("Folder:");
+            match self.root {
+                Some(ref root) => ui.monospace(root.display().to_string()),
+                None => ui.weak("none"),
+            };
+        });
+
+        ui.horizontal(|ui| {
+            let searching = self.search.is_some();
+
+            if ui.add_enabled(!searching, egui::Button::new("Search Current Folder")).clicked() {
+                let root = runtime.block_on(async { self.file_manager.lock().await.get_current_path().await });
+                self.start(root, runtime);
+            }
+            if ui.add_enabled(!searching, egui::Button::new("Choose Folder…")).clicked() {
+                if let Some(root) = rfd::FileDialog::new().pick_folder() {
+                    self.start(root, runtime);
+                }
+            }
+
+            ui.label("Min size (KB):");
+            ui.add_enabled(!searching, egui::DragValue::new(&mut self.min_size_kb).range(0..=1_048_576));
+        });
+
+        if let Some(ref search) = self.search {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                match search.progress {
+                    Some(ref progress) if progress.files_total > 0 => {
+                        ui.label(format!(
+                            "{}: {} of {} files, {} read",
+                            progress.stage.label(),
+                            progress.files_done,
+                            progress.files_total,
+                            format_file_size(progress.bytes_hashed)
+                        ));
+                    }
+                    Some(ref progress) => {
+                        ui.label(format!("{}: {} files", progress.stage.label(), progress.files_done));
+                    }
+                    None => {
+                        ui.label("Starting…");
+                    }
+                }
+                if ui.button("Stop").clicked() {
+                    search.control.cancel();
+                }
+            });
+        }
+    }
+
+    fn show_selection_controls(&mut self, ui: &mut egui::Ui, runtime: &tokio::runtime::Runtime) {
+        let extra_copies: usize = self.groups.iter().map(|group| group.files.len() - 1).sum();
+        let reclaimable: u64 = self.groups.iter().map(DuplicateGroup::reclaimable).sum();
+        ui.strong(format!(
+            "{} groups, {} extra copies, {} reclaimable",
+            self.groups.len(),
+            extra_copies,
+            format_file_size(reclaimable)
+        ));
+
+        ui.horizontal(|ui| {
+            ui.label("Select all but the:");
+            if ui.button("Newest").clicked() {
+                self.mark_extras(KeepRule::Newest);
+            }
+            if ui.button("Oldest").clicked() {
+                self.mark_extras(KeepRule::Oldest);
+            }
+            if ui.button("Clear").clicked() {
+                self.marked.clear();
+            }
+        });
+
+        let marked_size: u64 = self.groups.iter()
+            .map(|group| group.size * group.files.iter().filter(|file| self.marked.contains(&file.path)).count() as u64)
+            .sum();
+        ui.horizontal(|ui| {
+            ui.label(format!("Selected: {} files, {}", self.marked.len(), format_file_size(marked_size)));
+
+            let can_act = !self.marked.is_empty() && self.linking.is_none();
+            if ui.add_enabled(can_act, egui::Button::new("🗑 Move to Trash")).clicked() {
+                self.trash_marked();
+            }
+            if ui.add_enabled(can_act, egui::Button::new("🔗 Replace with Hard Links"))
+                .on_hover_text("Each selected file becomes a link to an unselected copy, sharing its space")
+                .clicked()
+            {
+                self.link_marked(runtime);
+            }
+        });
+    }
+
+    fn show_groups(&mut self, ui: &mut egui::Ui) {
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                for group in &self.groups {
+                    ui.label(egui::RichText::new(format!(
+                        "{} × {}, {} reclaimable",
+                        group.files.len(),
+                        format_file_size(group.size),
+                        format_file_size(group.reclaimable())
+                    )).strong());
+
+                    for file in &group.files {
+                        ui.horizontal(|ui| {
+                            let mut marked = self.marked.contains(&file.path);
+                            if ui.checkbox(&mut marked, "").changed() {
+                                if marked {
+                                    self.marked.insert(file.path.clone());
+                                } else {
+                                    self.marked.remove(&file.path);
+                                }
+                            }
+                            ui.weak(format_timestamp(file.modified));
+                            ui.label(file.path.display().to_string());
+                        });
+                    }
+                    ui.add_space(6.0);
+                }
+            });
+    }
+
+    fn start(&mut self, root: PathBuf, runtime: &tokio::runtime::Runtime) {
+        let control = JobControl::new();
+        let (progress_tx, progress_rx) = mpsc::unbounded_channel();
+        let finder = DuplicateFinder::new()
+            .with_min_size((self.min_size_kb * 1024).max(1))
+            .with_progress_callback(progress_tx)
+            .with_control(control.clone());
+
+        let search_root = root.clone();
+        let handle = runtime.spawn_blocking(move || finder.find(&search_root));
+
+        self.root = Some(root);
+        self.groups.clear();
+        self.marked.clear();
+        self.status = None;
+        self.search = Some(Search { control, progress_rx, progress: None, handle });
+    }
+
+    /// Picks up progress and the results of background work.
+    fn poll(&mut self, runtime: &tokio::runtime::Runtime) {
+        if let Some(ref mut search) = self.search {
+            while let Ok(progress) = search.progress_rx.try_recv() {
+                search.progress = Some(progress);
+            }
+            if search.handle.is_finished() {
+                let search = self.search.take().expect("search checked above");
+                match runtime.block_on(search.handle) {
+                    Ok(Ok(groups)) => self.groups = groups,
+                    Ok(Err(e)) if e.is::<Cancelled>() => self.status = Some("Search stopped".to_string()),
+                    Ok(Err(e)) => self.status = Some(format!("Search failed: {}", e)),
+                    Err(e) => self.status = Some(format!("Search failed: {}", e)),
+                }
+            }
+        }
+
+        if self.linking.as_ref().is_some_and(|(_, handle)| handle.is_finished()) {
+            let (duplicates, handle) = self.linking.take().expect("linking checked above");
+            match runtime.block_on(handle) {
+                Ok(report) => {
+                    let failed: HashSet<_> = report.failures.iter().map(|(path, _)| path.clone()).collect();
+                    let linked: Vec<_> = duplicates.into_iter().filter(|path| !failed.contains(path)).collect();
+                    self.forget(&linked);
+
+                    let mut status = format!(
+                        "Linked {} files, freeing {}",
+                        report.linked,
+                        format_file_size(report.bytes_freed)
+                    );
+                    if let Some((path, error)) = report.failures.first() {
+                        status.push_str(&format!(
+                            "; {} failed, first {}: {}",
+                            report.failures.len(),
+                            path.display(),
+                            error
+                        ));
+                    }
+                    self.status = Some(status);
+                }
+                Err(e) => self.status = Some(format!("Linking failed: {}", e)),
+            }
+        }
+    }
+
+    fn mark_extras(&mut self, rule: KeepRule) {
+        self.marked = self.groups.iter().flat_map(|group| group.extras(rule)).collect();
+    }
+
+    /// Marked files grouped with an unmarked copy to keep. Groups where every
+    /// file is marked are left out, so one copy always survives.
+    fn marked_with_keepers(&self) -> (Vec<(DuplicateFile, DuplicateFile)>, usize) {
+        let mut pairs = Vec::new();
+        let mut kept_groups = 0;
+        for group in &self.groups {
+            let Some(keeper) = group.files.iter().find(|file| !self.marked.contains(&file.path)) else {
+                kept_groups += 1;
+                continue;
+            };
+            pairs.extend(
+                group.files.iter()
+                    .filter(|file| self.marked.contains(&file.path))
+                    .map(|file| (keeper.clone(), file.clone())),
+            );
+        }
+        (pairs, kept_groups)
+    }
+
+    fn trash_marked(&mut self) {
+        let (pairs, kept_groups) = self.marked_with_keepers();
+        let paths: Vec<_> = pairs.into_iter().map(|(_, duplicate)| duplicate.path).collect();
+        if !paths.is_empty() {
+            self.jobs.enqueue(JobKind::Delete { paths: paths.clone() });
+        }
+        self.forget(&paths);
+        self.status = Some(Self::skipped_note(
+            format!("Moving {} files to the trash", paths.len()),
+            kept_groups,
+        ));
+    }
+
+    fn link_marked(&mut self, runtime: &tokio::runtime::Runtime) {
+        let (pairs, kept_groups) = self.marked_with_keepers();
+        let duplicates = pairs.iter().map(|(_, duplicate)| duplicate.path.clone()).collect();
+        let handle = runtime.spawn_blocking(move || dedupe::link_duplicates(&pairs));
+        self.linking = Some((duplicates, handle));
+        self.status = Some(Self::skipped_note("Linking…".to_string(), kept_groups));
+    }
+
+    fn skipped_note(status: String, kept_groups: usize) -> String {
+        if kept_groups == 0 {
+            status
+        } else {
+            format!("{}; {} groups had every copy selected and were left alone", status, kept_groups)
+        }
+    }
+
+    /// Drops `paths` from the results, and groups left with a single file.
+    fn forget(&mut self, paths: &[PathBuf]) {
+        let paths: HashSet<_> = paths.iter().collect();
+        self.marked.retain(|path| !paths.contains(path));
+        for group in &mut self.groups {
+            group.files.retain(|file| !paths.contains(&file.path));
+        }
+        self.groups.retain(|group| group.files.len() > 1);
+    }
+}
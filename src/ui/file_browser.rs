@@ -1,49 +1,270 @@
 use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 
+use crate::core::clipboard;
 use crate::core::file_manager::FileManager;
 use crate::core::file_item::{FileItem, FileType, SortBy, SortOrder};
+use crate::core::selection::SelectMode;
+use crate::core::vfs::{sftp, Vfs};
+use crate::operations::jobs::{JobKind, JobManager, JobState};
+use crate::ui::drag_drop;
+
+/// Height of a row in the list and details views.
+const ROW_HEIGHT: f32 = 20.0;
+
+/// Space between items in the grid view.
+const GRID_SPACING: f32 = 10.0;
+
+/// How long type-ahead find waits for the next character before starting over.
+const TYPE_AHEAD_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Where a key moves the keyboard focus.
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    By(isize),
+    First,
+    Last,
+}
+
+/// What the name typed into the name window is for.
+#[derive(Debug, Clone)]
+enum NamePrompt {
+    Rename(PathBuf),
+    NewFolder,
+    NewFile,
+}
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum ViewMode {
     List,
     Grid,
     Details,
 }
 
+/// How a browser shows its folder, kept by each tab.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ViewState {
+    pub view_mode: ViewMode,
+    pub item_size: f32,
+    /// How far down the items are scrolled.
+    pub scroll_offset: f32,
+}
+
 pub struct FileBrowser {
     file_manager: Arc<Mutex<FileManager>>,
+    jobs: JobManager,
     view_mode: ViewMode,
     item_size: f32,
     sort_by: SortBy,
     sort_order: SortOrder,
+    /// The item with the keyboard focus, as of this frame.
+    focus: Option<PathBuf>,
+    /// Item to bring into view on the next frame.
+    scroll_to: Option<usize>,
+    scroll_offset: f32,
+    viewport_height: f32,
+    grid_columns: usize,
+    /// Characters typed so far to find an item by name.
+    type_ahead: String,
+    type_ahead_at: Option<Instant>,
+    /// A text field had the keyboard last frame.
+    was_typing: bool,
+    /// A folder in the list has dragged files held over it this frame.
+    drop_on_item: bool,
+    /// Takes the keyboard; only one of the panes does in dual-pane mode.
+    active: bool,
+    /// Scroll position to go back to once the folder has been read.
+    restore_scroll: Option<f32>,
+    /// Folder picked with Open in New Tab, for the pane to open.
+    new_tab: Option<PathBuf>,
+    /// The name window, while it is open, and the name typed so far.
+    name_prompt: Option<(NamePrompt, String)>,
 }
 
 impl FileBrowser {
-    pub fn new(file_manager: Arc<Mutex<FileManager>>) -> Self {
+    pub fn new(file_manager: Arc<Mutex<FileManager>>, jobs: JobManager) -> Self {
         Self {
             file_manager,
+            jobs,
             view_mode: ViewMode::Details,
             item_size: 64.0,
             sort_by: SortBy::Name,
             sort_order: SortOrder::Ascending,
+            focus: None,
+            scroll_to: None,
+            scroll_offset: 0.0,
+            viewport_height: 0.0,
+            grid_columns: 1,
+            type_ahead: String::new(),
+            type_ahead_at: None,
+            was_typing: false,
+            drop_on_item: false,
+            active: true,
+            restore_scroll: None,
+            new_tab: None,
+            name_prompt: None,
         }
     }
 
+    pub fn view_state(&self) -> ViewState {
+        ViewState {
+            view_mode: self.view_mode,
+            item_size: self.item_size,
+            scroll_offset: self.scroll_offset,
+        }
+    }
+
+    pub fn set_view_state(&mut self, state: ViewState) {
+        self.view_mode = state.view_mode;
+        self.item_size = state.item_size;
+        self.scroll_offset = state.scroll_offset;
+        self.restore_scroll = Some(state.scroll_offset);
+        self.scroll_to = None;
+    }
+
+    /// Shows `sort_by` and `sort_order` as the sort in use, after the file
+    /// manager's was changed from elsewhere.
+    pub fn set_sort(&mut self, sort_by: SortBy, sort_order: SortOrder) {
+        self.sort_by = sort_by;
+        self.sort_order = sort_order;
+    }
+
+    /// The folder last picked with Open in New Tab.
+    pub fn take_new_tab(&mut self) -> Option<PathBuf> {
+        self.new_tab.take()
+    }
+
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
     pub fn show(&mut self, ui: &mut egui::Ui, runtime: &tokio::runtime::Runtime) {
+        self.focus = runtime.block_on(async {
+            self.file_manager.lock().await.with_selection(|selection| selection.focus().map(Path::to_path_buf)).await
+        });
+        if self.active {
+            self.handle_keys(ui, runtime);
+        }
+        self.drop_on_item = false;
+
         ui.vertical(|ui| {
             // View controls
             self.show_view_controls(ui, runtime);
             ui.separator();
+            let read = self.show_listing_state(ui, runtime);
 
             // File list
-            match self.view_mode {
+            let list = ui.scope(|ui| match self.view_mode {
                 ViewMode::List => self.show_list_view(ui, runtime),
                 ViewMode::Grid => self.show_grid_view(ui, runtime),
                 ViewMode::Details => self.show_details_view(ui, runtime),
+            }).response;
+            self.accept_drops(ui, list.rect, runtime);
+            if read {
+                self.restore_scroll = None;
             }
         });
+        self.show_name_prompt(ui.ctx(), runtime);
+    }
+
+    /// Asks for the name to rename an item to, or to give a new folder or
+    /// file in the current folder. The job that follows is journaled, so it
+    /// can be undone.
+    fn show_name_prompt(&mut self, ctx: &egui::Context, runtime: &tokio::runtime::Runtime) {
+        let Some((prompt, name)) = self.name_prompt.as_mut() else {
+            return;
+        };
+        let title = match prompt {
+            NamePrompt::Rename(_) => "Rename",
+            NamePrompt::NewFolder => "New Folder",
+            NamePrompt::NewFile => "New File",
+        };
+        let trimmed = name.trim();
+        let valid = !trimmed.is_empty() && !trimmed.contains(std::path::is_separator) && trimmed != "." && trimmed != "..";
+
+        let mut open = true;
+        let mut confirmed = false;
+        let mut cancelled = false;
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let response = ui.text_edit_singleline(name);
+                if !response.lost_focus() {
+                    response.request_focus();
+                }
+                confirmed = response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+                cancelled = ui.input(|input| input.key_pressed(egui::Key::Escape));
+
+                ui.horizontal(|ui| {
+                    confirmed |= ui.add_enabled(valid, egui::Button::new("OK")).clicked();
+                    cancelled |= ui.button("Cancel").clicked();
+                });
+            });
+
+        if confirmed && valid {
+            let Some((prompt, name)) = self.name_prompt.take() else {
+                return;
+            };
+            let name = name.trim().to_string();
+            let current_path = || runtime.block_on(async {
+                self.file_manager.lock().await.get_current_path().await
+            });
+            let job = match prompt {
+                NamePrompt::Rename(path) => {
+                    if path.file_name().is_some_and(|current| current == name.as_str()) {
+                        return;
+                    }
+                    JobKind::Rename { path, name }
+                }
+                NamePrompt::NewFolder => JobKind::CreateFolder { parent: current_path(), name },
+                NamePrompt::NewFile => JobKind::CreateFile { parent: current_path(), name },
+            };
+            self.jobs.enqueue(job);
+        } else if cancelled || !open {
+            self.name_prompt = None;
+        }
+    }
+
+    /// Opens the name window to rename `path`.
+    fn start_rename(&mut self, path: &Path) {
+        if let Some(name) = path.file_name() {
+            self.name_prompt = Some((NamePrompt::Rename(path.to_path_buf()), name.to_string_lossy().to_string()));
+        }
+    }
+
+    /// Files dropped on the list but not on a folder in it go to the current
+    /// folder. Files from other applications are always copied.
+    fn accept_drops(&self, ui: &egui::Ui, rect: egui::Rect, runtime: &tokio::runtime::Runtime) {
+        let current_path = runtime.block_on(async {
+            self.file_manager.lock().await.get_current_path().await
+        });
+        if !self.drop_on_item {
+            drag_drop::drop_target(ui, rect, &current_path, &self.jobs);
+        }
+
+        // The pointer isn't tracked during drags from other applications on
+        // every platform, and then the active pane takes the files
+        let over_list = match ui.ctx().pointer_hover_pos() {
+            Some(pos) => rect.contains(pos),
+            None => self.active,
+        };
+        if !over_list {
+            return;
+        }
+        if ui.input(|input| !input.raw.hovered_files.is_empty()) {
+            ui.painter().rect_stroke(rect, 2.0, ui.visuals().selection.stroke);
+        }
+        let dropped = drag_drop::dropped_files(ui.ctx());
+        if !dropped.is_empty() {
+            self.jobs.enqueue(JobKind::Copy { sources: dropped, destination: current_path });
+        }
     }
 
     fn show_view_controls(&mut self, ui: &mut egui::Ui, runtime: &tokio::runtime::Runtime) {
@@ -85,6 +306,37 @@ impl FileBrowser {
                 };
                 self.update_sort(runtime);
             }
+
+            // Other apps can't save into remote folders, so offer an upload
+            let current_path = runtime.block_on(async {
+                self.file_manager.lock().await.get_current_path().await
+            });
+
+            if sftp::is_sftp_path(&current_path) {
+                ui.separator();
+                if ui.button("📤 Upload…").on_hover_text("Upload files into this folder").clicked() {
+                    if let Some(files) = rfd::FileDialog::new().pick_files() {
+                        self.jobs.enqueue(JobKind::Copy { sources: files, destination: current_path });
+                    }
+                }
+            }
+
+            // Progress of the first running transfer; the transfers window has the rest
+            let progress = self.jobs.jobs().into_iter()
+                .find(|job| job.state == JobState::Running)
+                .and_then(|job| job.progress);
+            if let Some(progress) = progress {
+                ui.separator();
+                let mut hover = progress.current_file.display().to_string();
+                if let Some(rate) = progress.rate_text() {
+                    hover = format!("{}\n{}", hover, rate);
+                }
+                ui.add(
+                    egui::ProgressBar::new(progress.fraction())
+                        .desired_width(160.0)
+                        .text(format!("{} of {} files", progress.completed_files, progress.total_files))
+                ).on_hover_text(hover);
+            }
         });
     }
 
@@ -101,62 +353,100 @@ impl FileBrowser {
         });
     }
 
-    fn show_list_view(&mut self, ui: &mut egui::Ui, runtime: &tokio::runtime::Runtime) {
-        let items = runtime.block_on(async {
-            self.file_manager.lock().await.get_items().await
+    /// Shows how far reading the folder has got, with a way to stop it.
+    /// Returns whether reading is over.
+    fn show_listing_state(&self, ui: &mut egui::Ui, runtime: &tokio::runtime::Runtime) -> bool {
+        let listing = runtime.block_on(async {
+            self.file_manager.lock().await.listing().await
         });
 
-        let selected_items = runtime.block_on(async {
-            self.file_manager.lock().await.get_selected_items().await
-        });
+        if let Some(ref error) = listing.error {
+            ui.colored_label(ui.visuals().error_fg_color, format!("Could not read this folder: {}", error));
+        } else if listing.cancelled && !listing.done {
+            ui.weak(format!(
+                "Stopped after reading {} of {} items",
+                listing.loaded,
+                listing.total.unwrap_or(listing.loaded)
+            ));
+        } else if !listing.done {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                match listing.total {
+                    Some(total) => ui.label(format!("Loading {} of {} items…", listing.loaded, total)),
+                    None => ui.label("Loading items…"),
+                };
+                if ui.button("Cancel").clicked() {
+                    let file_manager = self.file_manager.clone();
+                    runtime.spawn(async move {
+                        file_manager.lock().await.cancel_loading().await;
+                    });
+                }
+            });
+        }
+        listing.done || listing.cancelled
+    }
+
+    fn item_count(&self, runtime: &tokio::runtime::Runtime) -> usize {
+        runtime.block_on(async {
+            self.file_manager.lock().await.with_items(|items| items.len()).await
+        })
+    }
 
-        egui::ScrollArea::vertical()
-            .auto_shrink([false, true])
-            .show(ui, |ui| {
-                for (index, item) in items.iter().enumerate() {
-                    let is_selected = selected_items.contains(&index);
-                    let icon = self.get_file_icon(&item);
+    /// Copies out the items in `range`, which may run past the end.
+    fn items_in(&self, range: std::ops::Range<usize>, runtime: &tokio::runtime::Runtime) -> Vec<FileItem> {
+        runtime.block_on(async {
+            self.file_manager.lock().await.items_in(range).await
+        })
+    }
+
+    fn show_list_view(&mut self, ui: &mut egui::Ui, runtime: &tokio::runtime::Runtime) {
+        let count = self.item_count(runtime);
+
+        // Only the rows in view are fetched and laid out
+        let output = self.scroll_area(ROW_HEIGHT + ui.spacing().item_spacing.y, 1)
+            .show_rows(ui, ROW_HEIGHT, count, |ui, range| {
+                for item in &self.items_in(range, runtime) {
+                    let is_selected = item.is_selected;
+                    let icon = self.get_file_icon(item);
                     let text = format!("{} {}", icon, item.name);
 
-                    let response = ui.selectable_label(is_selected, &text);
+                    let response = ui.horizontal(|ui| {
+                        ui.set_height(ROW_HEIGHT);
+                        Self::dim_if_cut(ui, item);
+                        ui.selectable_label(is_selected, &text)
+                    }).inner;
                     
-                    self.handle_item_interaction(response, index, item, runtime);
+                    self.draw_focus(ui, response.rect, item);
+                    self.handle_item_interaction(ui, response, item, runtime);
                 }
             });
+        self.remember_viewport(&output);
     }
 
     fn show_grid_view(&mut self, ui: &mut egui::Ui, runtime: &tokio::runtime::Runtime) {
-        let items = runtime.block_on(async {
-            self.file_manager.lock().await.get_items().await
-        });
-
-        let selected_items = runtime.block_on(async {
-            self.file_manager.lock().await.get_selected_items().await
-        });
-
-        egui::ScrollArea::vertical()
-            .auto_shrink([false, true])
-            .show(ui, |ui| {
-                let cols = (ui.available_width() / (self.item_size + 20.0)).floor() as usize;
-                if cols == 0 { return; }
-
-                egui::Grid::new("file_grid")
-                    .num_columns(cols)
-                    .spacing([10.0, 10.0])
-                    .show(ui, |ui| {
-                        for (index, item) in items.iter().enumerate() {
-                            if index % cols == 0 && index > 0 {
-                                ui.end_row();
-                            }
-
-                            let is_selected = selected_items.contains(&index);
+        let count = self.item_count(runtime);
+
+        let cols = (ui.available_width() / (self.item_size + 20.0)).floor() as usize;
+        if cols == 0 { return; }
+        let rows = count.div_ceil(cols);
+        self.grid_columns = cols;
+
+        ui.spacing_mut().item_spacing = egui::vec2(GRID_SPACING, GRID_SPACING);
+        let output = self.scroll_area(self.item_size + 30.0 + GRID_SPACING, cols)
+            .show_rows(ui, self.item_size + 30.0, rows, |ui, rows| {
+                let items = self.items_in(rows.start * cols..rows.end * cols, runtime);
+                for row_items in items.chunks(cols) {
+                    ui.horizontal(|ui| {
+                        for item in row_items {
+                            let is_selected = item.is_selected;
                             
                             ui.vertical(|ui| {
                                 ui.set_width(self.item_size);
                                 ui.set_height(self.item_size + 30.0);
+                                Self::dim_if_cut(ui, item);
 
                                 // File icon/thumbnail
-                                let icon = self.get_file_icon(&item);
+                                let icon = self.get_file_icon(item);
                                 let response = ui.button(
                                     egui::RichText::new(&icon)
                                         .size(self.item_size * 0.6)
@@ -173,46 +463,43 @@ impl FileBrowser {
                                         })
                                 );
 
-                                self.handle_item_interaction(response, index, item, runtime);
+                                self.draw_focus(ui, response.rect, item);
+                                self.handle_item_interaction(ui, response, item, runtime);
                             });
                         }
                     });
+                }
             });
+        self.remember_viewport(&output);
     }
 
     fn show_details_view(&mut self, ui: &mut egui::Ui, runtime: &tokio::runtime::Runtime) {
-        let items = runtime.block_on(async {
-            self.file_manager.lock().await.get_items().await
-        });
+        let count = self.item_count(runtime);
 
-        let selected_items = runtime.block_on(async {
-            self.file_manager.lock().await.get_selected_items().await
+        // Header
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            ui.separator();
+            ui.label("Size");
+            ui.separator();
+            ui.label("Type");
+            ui.separator();
+            ui.label("Modified");
         });
+        ui.separator();
 
-        egui::ScrollArea::vertical()
-            .auto_shrink([false, true])
-            .show(ui, |ui| {
-                // Header
-                ui.horizontal(|ui| {
-                    ui.label("Name");
-                    ui.separator();
-                    ui.label("Size");
-                    ui.separator();
-                    ui.label("Type");
-                    ui.separator();
-                    ui.label("Modified");
-                });
-                ui.separator();
-
+        let output = self.scroll_area(ROW_HEIGHT + ui.spacing().item_spacing.y, 1)
+            .show_rows(ui, ROW_HEIGHT, count, |ui, range| {
                 // Items
-                for (index, item) in items.iter().enumerate() {
-                    let is_selected = selected_items.contains(&index);
+                for item in &self.items_in(range, runtime) {
+                    let is_selected = item.is_selected;
                     
                     let response = ui.horizontal(|ui| {
-                        ui.set_height(20.0);
+                        ui.set_height(ROW_HEIGHT);
+                        Self::dim_if_cut(ui, item);
                         
                         // Icon and name
-                        let icon = self.get_file_icon(&item);
+                        let icon = self.get_file_icon(item);
                         ui.label(&icon);
                         ui.label(&item.name);
                         
@@ -248,26 +535,285 @@ impl FileBrowser {
                         );
                     }
 
-                    self.handle_item_interaction(response, index, item, runtime);
+                    self.draw_focus(ui, response.rect, item);
+                    self.handle_item_interaction(ui, response, item, runtime);
                 }
             });
+        self.remember_viewport(&output);
     }
 
-    fn handle_item_interaction(
+    /// A vertical scroll area that brings the item in `scroll_to` into view,
+    /// for rows `stride` apart holding `columns` items each.
+    fn scroll_area(&mut self, stride: f32, columns: usize) -> egui::ScrollArea {
+        let mut area = egui::ScrollArea::vertical().auto_shrink([false, true]);
+        if let Some(offset) = self.restore_scroll {
+            area = area.vertical_scroll_offset(offset);
+        }
+        if let Some(index) = self.scroll_to.take() {
+            let top = (index / columns.max(1)) as f32 * stride;
+            let offset = if top < self.scroll_offset {
+                top
+            } else if top + stride > self.scroll_offset + self.viewport_height {
+                top + stride - self.viewport_height
+            } else {
+                self.scroll_offset
+            };
+            area = area.vertical_scroll_offset(offset.max(0.0));
+        }
+        area
+    }
+
+    fn remember_viewport<R>(&mut self, output: &egui::scroll_area::ScrollAreaOutput<R>) {
+        self.scroll_offset = output.state.offset.y;
+        self.viewport_height = output.inner_rect.height();
+        if self.restore_scroll.is_some_and(|offset| (offset - self.scroll_offset).abs() < 1.0) {
+            self.restore_scroll = None;
+        }
+    }
+
+    /// Outlines the item with the keyboard focus.
+    fn draw_focus(&self, ui: &egui::Ui, rect: egui::Rect, item: &FileItem) {
+        if self.focus.as_ref() == Some(&item.path) {
+            ui.painter().rect_stroke(rect, 2.0, ui.visuals().selection.stroke);
+        }
+    }
+
+    /// Cut items are drawn faded until they are pasted.
+    fn dim_if_cut(ui: &mut egui::Ui, item: &FileItem) {
+        if item.is_cut {
+            ui.multiply_opacity(0.5);
+        }
+    }
+
+    /// Arrow keys, Home/End and PageUp/PageDown move the focus, Shift extends
+    /// the selection, Enter opens, Backspace goes up, F2 renames, typing finds
+    /// an item by name and Ctrl+C/Ctrl+X/Ctrl+V copy, cut and paste.
+    fn handle_keys(&mut self, ui: &egui::Ui, runtime: &tokio::runtime::Runtime) {
+        // Keys belong to the address bar or any other field being typed in,
+        // including the Enter that just left it
+        let typing = ui.ctx().memory(|memory| memory.focused().is_some());
+        if std::mem::replace(&mut self.was_typing, typing) || typing {
+            return;
+        }
+
+        let (columns, stride) = match self.view_mode {
+            ViewMode::Grid => (self.grid_columns.max(1), self.item_size + 30.0 + GRID_SPACING),
+            ViewMode::List | ViewMode::Details => (1, ROW_HEIGHT + ui.spacing().item_spacing.y),
+        };
+        let page = ((self.viewport_height / stride) as usize).max(1) * columns;
+
+        let events = ui.input(|input| input.events.clone());
+        for event in events {
+            match event {
+                egui::Event::Key { key, pressed: true, modifiers, .. } if !modifiers.ctrl && !modifiers.alt => {
+                    let step = match key {
+                        egui::Key::ArrowUp => Step::By(-(columns as isize)),
+                        egui::Key::ArrowDown => Step::By(columns as isize),
+                        egui::Key::ArrowLeft if columns > 1 => Step::By(-1),
+                        egui::Key::ArrowRight if columns > 1 => Step::By(1),
+                        egui::Key::PageUp => Step::By(-(page as isize)),
+                        egui::Key::PageDown => Step::By(page as isize),
+                        egui::Key::Home => Step::First,
+                        egui::Key::End => Step::Last,
+                        egui::Key::Enter => {
+                            self.open_focused(runtime);
+                            continue;
+                        }
+                        egui::Key::Backspace => {
+                            self.go_up(runtime);
+                            continue;
+                        }
+                        egui::Key::F2 => {
+                            if let Some(focus) = self.focus.clone() {
+                                self.start_rename(&focus);
+                            }
+                            continue;
+                        }
+                        _ => continue,
+                    };
+                    self.move_focus(step, modifiers.shift, runtime);
+                }
+                egui::Event::Text(text) => self.type_ahead(&text, runtime),
+                egui::Event::Copy => self.copy_to_clipboard(ui.ctx(), None, false, runtime),
+                egui::Event::Cut => self.copy_to_clipboard(ui.ctx(), None, true, runtime),
+                egui::Event::Paste(text) => self.paste(ui.ctx(), Some(&text), None, runtime),
+                _ => {}
+            }
+        }
+    }
+
+    fn move_focus(&mut self, step: Step, extend: bool, runtime: &tokio::runtime::Runtime) {
+        let index = runtime.block_on(async {
+            self.file_manager.lock().await.move_focus(|items, current| {
+                let last = items.len().checked_sub(1)?;
+                Some(match (step, current) {
+                    (Step::First, _) => 0,
+                    (Step::Last, _) => last,
+                    // Nothing focused yet: start at the top
+                    (Step::By(_), None) => 0,
+                    (Step::By(delta), Some(current)) => current.saturating_add_signed(delta).min(last),
+                })
+            }, extend).await
+        });
+        self.scroll_to = index;
+    }
+
+    /// Focuses the next item whose name starts with what was typed.
+    fn type_ahead(&mut self, text: &str, runtime: &tokio::runtime::Runtime) {
+        let now = Instant::now();
+        if self.type_ahead_at.is_none_or(|at| now - at > TYPE_AHEAD_TIMEOUT) {
+            self.type_ahead.clear();
+        }
+        self.type_ahead_at = Some(now);
+        self.type_ahead.push_str(&text.to_lowercase());
+
+        // A first character looks past the focused item, so pressing it again
+        // cycles through the matches
+        let prefix = self.type_ahead.as_str();
+        let skip = usize::from(prefix.chars().count() == 1);
+        let index = runtime.block_on(async {
+            self.file_manager.lock().await.move_focus(|items, current| {
+                let start = current.map_or(0, |current| current + skip);
+                (0..items.len())
+                    .map(|offset| (start + offset) % items.len())
+                    .find(|&index| items[index].name.to_lowercase().starts_with(prefix))
+            }, false).await
+        });
+        if index.is_some() {
+            self.scroll_to = index;
+        }
+    }
+
+    /// Puts `paths`, or the selection without them, on the clipboard, and
+    /// hands other applications the same files as `text/uri-list`.
+    fn copy_to_clipboard(
         &self,
+        ctx: &egui::Context,
+        paths: Option<Vec<PathBuf>>,
+        cut: bool,
+        runtime: &tokio::runtime::Runtime,
+    ) {
+        let uri_list = runtime.block_on(async {
+            let fm = self.file_manager.lock().await;
+            let paths = match paths {
+                Some(paths) => paths,
+                None => fm.selected_paths().await,
+            };
+            if paths.is_empty() {
+                return None;
+            }
+            fm.set_clipboard(paths, cut).await;
+            Some(fm.clipboard_uri_list().await)
+        });
+        // Always replaced, or Ctrl+V would paste what an earlier copy left there
+        if let Some(uri_list) = uri_list {
+            ctx.copy_text(uri_list);
+        }
+    }
+
+    /// Pastes into `destination`, or the current folder without one. Files
+    /// another application left on the system clipboard, seen as `pasted`
+    /// text, are copied; otherwise the clipboard's own files are.
+    fn paste(
+        &self,
+        ctx: &egui::Context,
+        pasted: Option<&str>,
+        destination: Option<PathBuf>,
+        runtime: &tokio::runtime::Runtime,
+    ) {
+        let (job, moved) = runtime.block_on(async {
+            let fm = self.file_manager.lock().await;
+            let external = pasted.map(clipboard::parse_uri_list).unwrap_or_default();
+            let (ours, cut) = fm.with_clipboard(|clipboard| {
+                (external.iter().all(|path| clipboard.contains(path)), clipboard.is_cut())
+            }).await;
+            if ours {
+                return (fm.paste_job(destination.as_deref()).await, cut);
+            }
+            let destination = match destination {
+                Some(destination) => destination,
+                None => fm.get_current_path().await,
+            };
+            (Some(JobKind::Copy { sources: external, destination }), false)
+        });
+        // Cut files move only once, so the system clipboard mustn't offer them again
+        if moved {
+            ctx.copy_text(clipboard::EMPTY_URI_LIST.to_string());
+        }
+        if let Some(job) = job {
+            self.jobs.enqueue(job);
+        }
+    }
+
+    fn open_focused(&self, runtime: &tokio::runtime::Runtime) {
+        let file_manager = self.file_manager.clone();
+        runtime.spawn(async move {
+            let focused = file_manager.lock().await.focused_item().await;
+            if let Some(item) = focused {
+                Self::open_path(&file_manager, &item.path).await;
+            }
+        });
+    }
+
+    fn go_up(&self, runtime: &tokio::runtime::Runtime) {
+        let file_manager = self.file_manager.clone();
+        runtime.spawn(async move {
+            if let Err(e) = FileManager::navigate_up(&file_manager).await {
+                log::error!("Failed to go up: {}", e);
+            }
+        });
+    }
+
+    /// Opens folders and supported archives in place, and files with their
+    /// default application. `file_manager` stays unlocked while `path` is
+    /// checked, as that may mean waiting on a server.
+    async fn open_path(file_manager: &Mutex<FileManager>, path: &Path) {
+        let vfs = file_manager.lock().await.vfs();
+        let can_browse = {
+            let (vfs, path) = (vfs.clone(), path.to_path_buf());
+            tokio::task::spawn_blocking(move || vfs.can_browse(&path)).await.unwrap_or(false)
+        };
+
+        if can_browse {
+            if let Err(e) = FileManager::navigate(file_manager, path).await {
+                log::error!("Failed to navigate to directory: {}", e);
+            }
+        } else {
+            Self::open_with_default_app(vfs.as_ref(), path);
+        }
+    }
+
+    fn handle_item_interaction(
+        &mut self,
+        ui: &egui::Ui,
         response: egui::Response,
-        index: usize,
         item: &FileItem,
         runtime: &tokio::runtime::Runtime,
     ) {
-        // Single click - select
+        // Dragging a selected item takes the whole selection along
+        let response = response.interact(egui::Sense::click_and_drag());
+        drag_drop::drag_source(&response, || {
+            if item.is_selected {
+                runtime.block_on(async { self.file_manager.lock().await.selected_paths().await })
+            } else {
+                vec![item.path.clone()]
+            }
+        });
+        if item.file_type == FileType::Directory
+            && drag_drop::drop_target(ui, response.rect, &item.path, &self.jobs)
+        {
+            self.drop_on_item = true;
+        }
+
+        // Single click - select, Ctrl toggles, Shift selects a range
         if response.clicked() {
             let file_manager = self.file_manager.clone();
-            let ctrl_held = response.ctx.input(|i| i.modifiers.ctrl);
+            let path = item.path.clone();
+            let mode = response.ctx.input(|i| SelectMode::from_modifiers(i.modifiers.ctrl, i.modifiers.shift));
             
             runtime.spawn(async move {
                 let fm = file_manager.lock().await;
-                fm.select_item(index, ctrl_held).await;
+                fm.select(&path, mode).await;
             });
         }
 
@@ -277,18 +823,7 @@ impl FileBrowser {
             let file_manager = self.file_manager.clone();
             
             runtime.spawn(async move {
-                if path.is_dir() {
-                    // Navigate to directory
-                    let mut fm = file_manager.lock().await;
-                    if let Err(e) = fm.navigate_to(&path).await {
-                        log::error!("Failed to navigate to directory: {}", e);
-                    }
-                } else {
-                    // Open file with default application
-                    if let Err(e) = open::that(&path) {
-                        log::error!("Failed to open file: {}", e);
-                    }
-                }
+                Self::open_path(&file_manager, &path).await;
             });
         }
 
@@ -296,47 +831,79 @@ impl FileBrowser {
         response.context_menu(|ui| {
             if ui.button("Open").clicked() {
                 let path = item.path.clone();
+                let file_manager = self.file_manager.clone();
                 runtime.spawn(async move {
-                    if let Err(e) = open::that(&path) {
-                        log::error!("Failed to open file: {}", e);
-                    }
+                    let vfs = file_manager.lock().await.vfs();
+                    Self::open_with_default_app(vfs.as_ref(), &path);
                 });
                 ui.close_menu();
             }
 
-            if item.file_type == FileType::Directory {
-                if ui.button("Open in New Tab").clicked() {
-                    // TODO: Implement tab functionality
-                    ui.close_menu();
-                }
+            if item.file_type == FileType::Directory && ui.button("Open in New Tab").clicked() {
+                self.new_tab = Some(item.path.clone());
+                ui.close_menu();
             }
 
             ui.separator();
 
+            if ui.button("Copy To Folder…").clicked() {
+                if let Some(destination) = rfd::FileDialog::new().pick_folder() {
+                    self.jobs.enqueue(JobKind::Copy { sources: vec![item.path.clone()], destination });
+                }
+                ui.close_menu();
+            }
+
+            // A selected item stands for the whole selection
+            let paths = (!item.is_selected).then(|| vec![item.path.clone()]);
             if ui.button("Copy").clicked() {
-                // TODO: Implement copy to clipboard
+                self.copy_to_clipboard(ui.ctx(), paths.clone(), false, runtime);
                 ui.close_menu();
             }
 
             if ui.button("Cut").clicked() {
-                // TODO: Implement cut to clipboard
+                self.copy_to_clipboard(ui.ctx(), paths, true, runtime);
+                ui.close_menu();
+            }
+
+            let can_paste = runtime.block_on(async {
+                !self.file_manager.lock().await.with_clipboard(|clipboard| clipboard.is_empty()).await
+            });
+            if ui.add_enabled(can_paste, egui::Button::new("Paste")).clicked() {
+                self.paste(ui.ctx(), None, None, runtime);
+                ui.close_menu();
+            }
+
+            if item.file_type == FileType::Directory
+                && ui.add_enabled(can_paste, egui::Button::new("Paste Into Folder")).clicked()
+            {
+                self.paste(ui.ctx(), None, Some(item.path.clone()), runtime);
                 ui.close_menu();
             }
 
             if ui.button("Delete").clicked() {
-                let path = item.path.clone();
-                runtime.spawn(async move {
-                    if let Err(e) = trash::delete(&path) {
-                        log::error!("Failed to delete file: {}", e);
-                    }
-                });
+                let paths = if item.is_selected {
+                    runtime.block_on(async { self.file_manager.lock().await.selected_paths().await })
+                } else {
+                    vec![item.path.clone()]
+                };
+                self.jobs.enqueue(JobKind::Delete { paths });
                 ui.close_menu();
             }
 
             ui.separator();
 
-            if ui.button("Rename").clicked() {
-                // TODO: Implement rename dialog
+            if ui.button("Rename…").clicked() {
+                self.start_rename(&item.path);
+                ui.close_menu();
+            }
+
+            if ui.button("New Folder…").clicked() {
+                self.name_prompt = Some((NamePrompt::NewFolder, String::from("New Folder")));
+                ui.close_menu();
+            }
+
+            if ui.button("New File…").clicked() {
+                self.name_prompt = Some((NamePrompt::NewFile, String::from("New File.txt")));
                 ui.close_menu();
             }
 
@@ -344,9 +911,38 @@ impl FileBrowser {
                 // TODO: Implement properties dialog
                 ui.close_menu();
             }
+
+            ui.separator();
+
+            if ui.button("Select All").clicked() {
+                let file_manager = self.file_manager.clone();
+                runtime.spawn(async move {
+                    file_manager.lock().await.select_all().await;
+                });
+                ui.close_menu();
+            }
+
+            if ui.button("Invert Selection").clicked() {
+                let file_manager = self.file_manager.clone();
+                runtime.spawn(async move {
+                    file_manager.lock().await.invert_selection().await;
+                });
+                ui.close_menu();
+            }
         });
     }
 
+    fn open_with_default_app(vfs: &dyn Vfs, path: &Path) {
+        match vfs.local_path(path) {
+            Some(local_path) => {
+                if let Err(e) = open::that(&local_path) {
+                    log::error!("Failed to open file: {}", e);
+                }
+            }
+            None => log::warn!("Cannot open {} outside the local filesystem", path.display()),
+        }
+    }
+
     fn get_file_icon(&self, item: &FileItem) -> String {
         match item.file_type {
             FileType::Directory => "📁".to_string(),
@@ -0,0 +1,138 @@
+use eframe::egui;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::core::file_manager::FileManager;
+use crate::operations::journal::OperationJournal;
+
+/// Window listing the operation journal, with undo and redo.
+pub struct HistoryPanel {
+    journal: Arc<Mutex<OperationJournal>>,
+    pub open: bool,
+}
+
+impl HistoryPanel {
+    pub fn new(journal: Arc<Mutex<OperationJournal>>) -> Self {
+        Self {
+            journal,
+            open: false,
+        }
+    }
+
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        runtime: &tokio::runtime::Runtime,
+        file_manager: &Arc<Mutex<FileManager>>,
+    ) {
+        if !self.open {
+            return;
+        }
+
+        let (undo_entries, redo_entries) = runtime.block_on(async {
+            let journal = self.journal.lock().await;
+            (journal.undo_entries().to_vec(), journal.redo_entries().to_vec())
+        });
+
+        let mut open = self.open;
+        egui::Window::new("History")
+            .open(&mut open)
+            .default_width(320.0)
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!undo_entries.is_empty(), egui::Button::new("↶ Undo"))
+                        .on_hover_text("Undo (Ctrl+Z)")
+                        .clicked()
+                    {
+                        Self::undo(runtime, &self.journal, file_manager);
+                    }
+
+                    if ui.add_enabled(!redo_entries.is_empty(), egui::Button::new("↷ Redo"))
+                        .on_hover_text("Redo (Ctrl+Y)")
+                        .clicked()
+                    {
+                        Self::redo(runtime, &self.journal, file_manager);
+                    }
+                });
+
+                ui.separator();
+
+                if undo_entries.is_empty() && redo_entries.is_empty() {
+                    ui.label("No operations yet");
+                    return;
+                }
+
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        // Undone operations above the line, newest redo nearest to it
+                        for entry in &redo_entries {
+                            ui.label(
+                                egui::RichText::new(entry.action.description())
+                                    .color(ui.style().visuals.weak_text_color())
+                            ).on_hover_text("Undone");
+                        }
+
+                        if !redo_entries.is_empty() {
+                            ui.separator();
+                        }
+
+                        for entry in undo_entries.iter().rev() {
+                            ui.horizontal(|ui| {
+                                ui.label(entry.action.description());
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    let local_time = entry.completed_at.with_timezone(&chrono::Local);
+                                    ui.weak(local_time.format("%H:%M:%S").to_string());
+                                });
+                            });
+                        }
+                    });
+            });
+        self.open = open;
+    }
+
+    /// Undoes the latest operation in the background and refreshes the listing.
+    pub fn undo(
+        runtime: &tokio::runtime::Runtime,
+        journal: &Arc<Mutex<OperationJournal>>,
+        file_manager: &Arc<Mutex<FileManager>>,
+    ) {
+        let journal = journal.clone();
+        let file_manager = file_manager.clone();
+        runtime.spawn(async move {
+            let vfs = file_manager.lock().await.vfs();
+            match journal.lock().await.undo(vfs).await {
+                Ok(Some(description)) => log::info!("Undid: {}", description),
+                Ok(None) => return,
+                Err(e) => log::error!("Failed to undo: {}", e),
+            }
+
+            if let Err(e) = file_manager.lock().await.refresh_items().await {
+                log::error!("Failed to refresh after undo: {}", e);
+            }
+        });
+    }
+
+    /// Redoes the latest undone operation in the background and refreshes the listing.
+    pub fn redo(
+        runtime: &tokio::runtime::Runtime,
+        journal: &Arc<Mutex<OperationJournal>>,
+        file_manager: &Arc<Mutex<FileManager>>,
+    ) {
+        let journal = journal.clone();
+        let file_manager = file_manager.clone();
+        runtime.spawn(async move {
+            let vfs = file_manager.lock().await.vfs();
+            match journal.lock().await.redo(vfs).await {
+                Ok(Some(description)) => log::info!("Redid: {}", description),
+                Ok(None) => return,
+                Err(e) => log::error!("Failed to redo: {}", e),
+            }
+
+            if let Err(e) = file_manager.lock().await.refresh_items().await {
+                log::error!("Failed to refresh after redo: {}", e);
+            }
+        });
+    }
+}
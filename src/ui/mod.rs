@@ -6,3 +6,9 @@ pub mod status_bar;
 pub mod context_menu;
 pub mod dialogs;
 pub mod tabs;
+pub mod history;
+pub mod transfers;
+pub mod duplicates;
+pub mod disk_usage;
+pub mod drag_drop;
+pub mod session;
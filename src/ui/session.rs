@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ui::app::PaneLayout;
+use crate::ui::tabs::TabState;
+
+/// The tabs of one pane, and which of them is showing.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PaneSession {
+    pub tabs: Vec<TabState>,
+    pub active_tab: usize,
+}
+
+/// The open panes and tabs, saved on exit and put back on the next start.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub panes: Vec<PaneSession>,
+    pub active_pane: usize,
+    pub layout: PaneLayout,
+}
+
+impl Session {
+    pub fn load() -> Self {
+        if let Some(config_dir) = dirs::config_dir() {
+            let session_path = config_dir.join("chex-explorer").join("session.json");
+
+            if let Ok(content) = std::fs::read_to_string(&session_path) {
+                match serde_json::from_str(&content) {
+                    Ok(session) => return session,
+                    Err(e) => log::warn!("Ignoring unreadable session {}: {}", session_path.display(), e),
+                }
+            }
+        }
+
+        Self::default()
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(config_dir) = dirs::config_dir() {
+            let config_path = config_dir.join("chex-explorer");
+            std::fs::create_dir_all(&config_path)?;
+
+            let session_path = config_path.join("session.json");
+            let content = serde_json::to_string_pretty(self)?;
+            std::fs::write(&session_path, content)?;
+        }
+
+        Ok(())
+    }
+}
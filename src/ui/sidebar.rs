@@ -1,65 +1,91 @@
 use eframe::egui;
+use egui::collapsing_header::CollapsingState;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use crate::core::bookmark::BookmarkManager;
 use crate::core::file_manager::FileManager;
+use crate::core::folder_tree::{FolderTree, Subfolders};
+use crate::core::vfs::sftp;
+use crate::operations::jobs::JobManager;
+use crate::ui::drag_drop;
+use crate::utils::file_utils;
 
 pub struct Sidebar {
     bookmark_manager: Arc<Mutex<BookmarkManager>>,
+    jobs: JobManager,
+    tree: FolderTree,
+    /// Mount points, listed once as they rarely change.
+    drives: Vec<PathBuf>,
+    /// The active pane's folder as of last frame, revealed in the tree when it changes.
+    current_path: PathBuf,
+    show_hidden: bool,
+    scroll_to_current: bool,
+    /// Folder clicked this frame.
+    navigate_to: Option<PathBuf>,
+    /// Bookmark removed this frame.
+    remove_bookmark: Option<usize>,
 }
 
 impl Sidebar {
-    pub fn new(bookmark_manager: Arc<Mutex<BookmarkManager>>) -> Self {
+    pub fn new(bookmark_manager: Arc<Mutex<BookmarkManager>>, jobs: JobManager, tree: FolderTree) -> Self {
         Self {
             bookmark_manager,
+            jobs,
+            tree,
+            drives: file_utils::get_available_drives(),
+            current_path: PathBuf::new(),
+            show_hidden: false,
+            scroll_to_current: false,
+            navigate_to: None,
+            remove_bookmark: None,
         }
     }
 
     pub fn show(
-        &mut self, 
-        ui: &mut egui::Ui, 
+        &mut self,
+        ui: &mut egui::Ui,
         runtime: &tokio::runtime::Runtime,
         file_manager: &Arc<Mutex<FileManager>>
     ) {
+        self.tree.poll();
+
+        let bookmarks = runtime.block_on(async {
+            self.bookmark_manager.lock().await.get_bookmarks().clone()
+        });
+        let (current_path, show_hidden) = runtime.block_on(async {
+            let fm = file_manager.lock().await;
+            (fm.get_current_path().await, fm.get_show_hidden().await)
+        });
+        self.show_hidden = show_hidden;
+        if current_path != self.current_path {
+            // Reveal the folder under the closest bookmark or mount point holding it
+            let root = bookmarks.iter()
+                .map(|bookmark| bookmark.path.as_path())
+                .chain(self.drives.iter().map(PathBuf::as_path))
+                .filter(|root| current_path.starts_with(root))
+                .max_by_key(|root| root.components().count())
+                .map(Path::to_path_buf);
+            if let Some(root) = root {
+                self.tree.reveal(&root, &current_path);
+            }
+            self.scroll_to_current = true;
+            self.current_path = current_path;
+        }
+
         ui.vertical(|ui| {
             ui.heading("Quick Access");
             ui.separator();
 
-            let bookmarks = runtime.block_on(async {
-                self.bookmark_manager.lock().await.get_bookmarks().clone()
-            });
-
             egui::ScrollArea::vertical()
                 .auto_shrink([false, true])
                 .show(ui, |ui| {
                     for (index, bookmark) in bookmarks.iter().enumerate() {
-                        let button_text = format!("📁 {}", bookmark.name);
-                        
-                        let button_response = ui.selectable_label(false, &button_text)
-                            .on_hover_text(bookmark.path.display().to_string());
-                        
-                        if button_response.clicked() {
-                            let path = bookmark.path.clone();
-                            let file_manager = file_manager.clone();
-                            runtime.spawn(async move {
-                                let mut fm = file_manager.lock().await;
-                                if let Err(e) = fm.navigate_to(&path).await {
-                                    log::error!("Failed to navigate to bookmark: {}", e);
-                                }
-                            });
-                        }
-
-                        // Context menu for bookmarks
-                        button_response.context_menu(|ui| {
-                            if ui.button("Remove Bookmark").clicked() {
-                                let bookmark_manager = self.bookmark_manager.clone();
-                                runtime.spawn(async move {
-                                    let mut bm = bookmark_manager.lock().await;
-                                    bm.remove_bookmark(index);
-                                });
-                                ui.close_menu();
-                            }
+                        let icon = if sftp::is_sftp_path(&bookmark.path) { "🌐" } else { "📁" };
+                        let label = format!("{} {}", icon, bookmark.name);
+                        ui.push_id(("bookmark", index), |ui| {
+                            self.show_folder(ui, &bookmark.path, &label, Some(index));
                         });
                     }
 
@@ -74,115 +100,149 @@ impl Sidebar {
                                 let fm = file_manager.lock().await;
                                 fm.get_current_path().await
                             };
-                            
+
                             let folder_name = current_path
                                 .file_name()
                                 .unwrap_or_default()
                                 .to_string_lossy()
                                 .to_string();
-                            
+
                             let bookmark = crate::core::bookmark::Bookmark::new(
                                 folder_name.clone(),
                                 current_path
                             );
-                            
+
                             let mut bm = bookmark_manager.lock().await;
                             bm.add_bookmark(bookmark);
                         });
                     }
-                });
 
-            ui.separator();
+                    ui.separator();
 
-            // Quick system locations
-            ui.heading("System");
-            
-            if let Some(home) = dirs::home_dir() {
-                if ui.selectable_label(false, "🏠 Home").clicked() {
-                    let file_manager = file_manager.clone();
-                    runtime.spawn(async move {
-                        let mut fm = file_manager.lock().await;
-                        if let Err(e) = fm.navigate_to(&home).await {
-                            log::error!("Failed to navigate to home: {}", e);
+                    // Quick system locations
+                    ui.heading("System");
+
+                    let locations = [
+                        ("🏠 Home", dirs::home_dir()),
+                        ("🖥 Desktop", dirs::desktop_dir()),
+                        ("📄 Documents", dirs::document_dir()),
+                        ("⬇ Downloads", dirs::download_dir()),
+                    ];
+                    for (label, path) in locations {
+                        if let Some(path) = path {
+                            if ui.selectable_label(false, label).clicked() {
+                                self.navigate_to = Some(path);
+                            }
                         }
-                    });
-                }
-            }
+                    }
 
-            if let Some(desktop) = dirs::desktop_dir() {
-                if ui.selectable_label(false, "🖥 Desktop").clicked() {
-                    let file_manager = file_manager.clone();
-                    runtime.spawn(async move {
-                        let mut fm = file_manager.lock().await;
-                        if let Err(e) = fm.navigate_to(&desktop).await {
-                            log::error!("Failed to navigate to desktop: {}", e);
-                        }
-                    });
-                }
-            }
+                    // Drives on Windows, the root and mount points elsewhere
+                    ui.separator();
+                    ui.heading("Drives");
+
+                    for (index, drive) in self.drives.clone().iter().enumerate() {
+                        let label = if drive == Path::new("/") {
+                            "💻 Root".to_string()
+                        } else {
+                            format!("💾 {}", folder_name(drive))
+                        };
+                        ui.push_id(("drive", index), |ui| {
+                            self.show_folder(ui, drive, &label, None);
+                        });
+                    }
+                });
+        });
 
-            if let Some(documents) = dirs::document_dir() {
-                if ui.selectable_label(false, "📄 Documents").clicked() {
-                    let file_manager = file_manager.clone();
-                    runtime.spawn(async move {
-                        let mut fm = file_manager.lock().await;
-                        if let Err(e) = fm.navigate_to(&documents).await {
-                            log::error!("Failed to navigate to documents: {}", e);
-                        }
-                    });
+        if let Some(path) = self.navigate_to.take() {
+            let file_manager = file_manager.clone();
+            runtime.spawn(async move {
+                if let Err(e) = FileManager::navigate(&file_manager, &path).await {
+                    log::error!("Failed to navigate to {}: {}", path.display(), e);
                 }
-            }
+            });
+        }
 
-            if let Some(downloads) = dirs::download_dir() {
-                if ui.selectable_label(false, "⬇ Downloads").clicked() {
-                    let file_manager = file_manager.clone();
-                    runtime.spawn(async move {
-                        let mut fm = file_manager.lock().await;
-                        if let Err(e) = fm.navigate_to(&downloads).await {
-                            log::error!("Failed to navigate to downloads: {}", e);
-                        }
-                    });
-                }
-            }
+        if let Some(index) = self.remove_bookmark.take() {
+            let bookmark_manager = self.bookmark_manager.clone();
+            runtime.spawn(async move {
+                let mut bm = bookmark_manager.lock().await;
+                bm.remove_bookmark(index);
+            });
+        }
+    }
 
-            // Show drives on Windows
-            #[cfg(windows)]
-            {
-                ui.separator();
-                ui.heading("Drives");
-                
-                for drive in 'A'..='Z' {
-                    let drive_path = std::path::PathBuf::from(format!("{}:\\", drive));
-                    if drive_path.exists() {
-                        let drive_text = format!("💾 Drive {}", drive);
-                        if ui.selectable_label(false, &drive_text).clicked() {
-                            let file_manager = file_manager.clone();
-                            runtime.spawn(async move {
-                                let mut fm = file_manager.lock().await;
-                                if let Err(e) = fm.navigate_to(&drive_path).await {
-                                    log::error!("Failed to navigate to drive: {}", e);
-                                }
-                            });
-                        }
+    /// Draws `path` as a row of the folder tree, with its subfolders below
+    /// it while it is expanded. `bookmark` is the index of the bookmark the
+    /// row stands for, if any.
+    fn show_folder(&mut self, ui: &mut egui::Ui, path: &Path, label: &str, bookmark: Option<usize>) {
+        // Folders found to have no subfolders have nothing to expand
+        if matches!(self.tree.subfolders(path), Some(Subfolders::Loaded(folders)) if folders.is_empty()) {
+            ui.horizontal(|ui| {
+                ui.add_space(ui.spacing().icon_width + ui.spacing().item_spacing.x);
+                self.folder_row(ui, path, label, bookmark);
+            });
+            return;
+        }
+
+        let expanded = self.tree.is_expanded(path);
+        let mut state = CollapsingState::load_with_default_open(ui.ctx(), ui.make_persistent_id(path), false);
+        state.set_open(expanded);
+        let (toggle, _, _) = state
+            .show_header(ui, |ui| self.folder_row(ui, path, label, bookmark))
+            .body(|ui| self.show_subfolders(ui, path));
+        if toggle.clicked() {
+            self.tree.set_expanded(path, !expanded);
+        }
+    }
+
+    fn show_subfolders(&mut self, ui: &mut egui::Ui, path: &Path) {
+        match self.tree.subfolders(path).cloned() {
+            Some(Subfolders::Loaded(folders)) => {
+                for folder in folders {
+                    // Hidden folders show only when hidden files do, or to reach the current folder
+                    if folder.is_hidden && !self.show_hidden && !self.current_path.starts_with(&folder.path) {
+                        continue;
                     }
+                    self.show_folder(ui, &folder.path, &format!("📁 {}", folder.name), None);
                 }
             }
+            Some(Subfolders::Failed(error)) => {
+                ui.weak("Can't read folder").on_hover_text(error);
+            }
+            Some(Subfolders::Loading) | None => {
+                ui.spinner();
+            }
+        }
+    }
 
-            // Show root on Unix-like systems
-            #[cfg(not(windows))]
-            {
-                ui.separator();
-                if ui.selectable_label(false, "💻 Root").clicked() {
-                    let root_path = std::path::PathBuf::from("/");
-                    let file_manager = file_manager.clone();
-                    runtime.spawn(async move {
-                        let mut fm = file_manager.lock().await;
-                        if let Err(e) = fm.navigate_to(&root_path).await {
-                            log::error!("Failed to navigate to root: {}", e);
-                        }
-                    });
+    fn folder_row(&mut self, ui: &mut egui::Ui, path: &Path, label: &str, bookmark: Option<usize>) {
+        let is_current = self.current_path == path;
+        let response = ui.selectable_label(is_current, label)
+            .on_hover_text(path.display().to_string());
+        drag_drop::drop_target(ui, response.rect, path, &self.jobs);
+
+        if response.clicked() {
+            self.navigate_to = Some(path.to_path_buf());
+        }
+        if is_current && std::mem::take(&mut self.scroll_to_current) {
+            response.scroll_to_me(Some(egui::Align::Center));
+        }
+
+        // Context menu for bookmarks
+        if let Some(index) = bookmark {
+            response.context_menu(|ui| {
+                if ui.button("Remove Bookmark").clicked() {
+                    self.remove_bookmark = Some(index);
+                    ui.close_menu();
                 }
-            }
-        });
+            });
+        }
     }
 }
+
+/// A folder's name, or the whole path for a drive.
+fn folder_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
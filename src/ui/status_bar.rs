@@ -3,18 +3,26 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use crate::core::file_manager::FileManager;
+use crate::operations::jobs::{JobManager, JobState};
 
 pub struct StatusBar {
     file_manager: Arc<Mutex<FileManager>>,
+    jobs: JobManager,
 }
 
 impl StatusBar {
-    pub fn new(file_manager: Arc<Mutex<FileManager>>) -> Self {
+    pub fn new(file_manager: Arc<Mutex<FileManager>>, jobs: JobManager) -> Self {
         Self {
             file_manager,
+            jobs,
         }
     }
 
+    /// Points the status bar at `file_manager`, the pane it works on.
+    pub fn set_file_manager(&mut self, file_manager: Arc<Mutex<FileManager>>) {
+        self.file_manager = file_manager;
+    }
+
     pub fn show(&mut self, ui: &mut egui::Ui, runtime: &tokio::runtime::Runtime) {
         ui.horizontal(|ui| {
             // Current path info
@@ -22,17 +30,18 @@ impl StatusBar {
                 self.file_manager.lock().await.get_current_path().await
             });
 
-            let items = runtime.block_on(async {
-                self.file_manager.lock().await.get_items().await
+            let (selected_count, selected_size) = runtime.block_on(async {
+                self.file_manager.lock().await.with_selection(|selection| (selection.len(), selection.bytes())).await
             });
 
-            let selected_items = runtime.block_on(async {
-                self.file_manager.lock().await.get_selected_items().await
+            // Summed in place, as large folders are too big to copy every frame
+            let (total_items, dir_info) = runtime.block_on(async {
+                self.file_manager.lock().await.with_items(|items| {
+                    (items.len(), self.calculate_directory_info(items))
+                }).await
             });
 
             // Item count
-            let total_items = items.len();
-            let selected_count = selected_items.len();
             
             if selected_count > 0 {
                 ui.label(format!("{} of {} items selected", selected_count, total_items));
@@ -44,27 +53,54 @@ impl StatusBar {
 
             // Selected items size
             if selected_count > 0 {
-                let total_size: u64 = selected_items.iter()
-                    .filter_map(|&index| items.get(index))
-                    .map(|item| item.size)
-                    .sum();
-                
-                let formatted_size = crate::utils::format::format_file_size(total_size);
+                let formatted_size = crate::utils::format::format_file_size(selected_size);
                 ui.label(format!("Selected: {}", formatted_size));
                 ui.separator();
             }
 
             // Current directory size (async calculation)
-            let dir_info = self.calculate_directory_info(&items);
             ui.label(format!("Total: {}", dir_info));
 
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                 // Show current path
                 ui.label(format!("📁 {}", current_path.display()));
+
+                if let Some(transfers) = self.transfer_info() {
+                    ui.separator();
+                    ui.label(transfers);
+                }
             });
         });
     }
 
+    /// Combined speed and time left of the running transfers.
+    fn transfer_info(&self) -> Option<String> {
+        let running: Vec<_> = self.jobs.jobs().into_iter()
+            .filter(|job| job.state == JobState::Running)
+            .filter_map(|job| job.progress)
+            .collect();
+        if running.is_empty() {
+            return None;
+        }
+
+        let label = match running.len() {
+            1 => "1 transfer".to_string(),
+            count => format!("{} transfers", count),
+        };
+        let speed: f64 = running.iter().filter_map(|progress| progress.bytes_per_second).sum();
+        if speed <= 0.0 {
+            return Some(format!("⇅ {}", label));
+        }
+
+        // Jobs run side by side, so the slowest one decides when all are done
+        let eta = running.iter().filter_map(|progress| progress.eta).max();
+        let mut info = format!("⇅ {}: {}/s", label, crate::utils::format::format_file_size(speed as u64));
+        if let Some(eta) = eta {
+            info.push_str(&format!(", {} left", crate::utils::format::format_duration(eta)));
+        }
+        Some(info)
+    }
+
     fn calculate_directory_info(&self, items: &[crate::core::file_item::FileItem]) -> String {
         let total_size: u64 = items.iter()
             .filter(|item| item.file_type == crate::core::file_item::FileType::RegularFile)
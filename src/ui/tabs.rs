@@ -1,5 +1,18 @@
 use eframe::egui;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::core::file_manager::NavigationState;
+use crate::operations::jobs::JobManager;
+use crate::ui::drag_drop;
+use crate::ui::file_browser::ViewState;
+
+/// Everything a tab keeps while another one is showing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabState {
+    pub navigation: NavigationState,
+    pub view: ViewState,
+}
 
 #[derive(Debug, Clone)]
 pub struct Tab {
@@ -7,6 +20,9 @@ pub struct Tab {
     pub title: String,
     pub path: PathBuf,
     pub is_active: bool,
+    /// What to go back to when the tab is picked again. The active tab's
+    /// state lives in its pane instead.
+    pub state: Option<TabState>,
 }
 
 impl Tab {
@@ -16,10 +32,30 @@ impl Tab {
             title,
             path,
             is_active: false,
+            state: None,
         }
     }
+
+    /// A tab showing where `state` is.
+    pub fn with_state(state: TabState) -> Self {
+        let path = state.navigation.path().map(Path::to_path_buf).unwrap_or_default();
+        let mut tab = Self::new(tab_title(&path), path);
+        tab.state = Some(state);
+        tab
+    }
+}
+
+/// What clicking in the tab bar asks the pane to do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TabRequest {
+    Activate(uuid::Uuid),
+    Close(uuid::Uuid),
+    New,
 }
 
+/// A tab being dragged to a new place in the tab bar.
+struct DraggedTab(uuid::Uuid);
+
 pub struct TabManager {
     tabs: Vec<Tab>,
     active_tab_id: Option<uuid::Uuid>,
@@ -33,80 +69,134 @@ impl TabManager {
         }
     }
 
-    pub fn show_tabs(&mut self, ui: &mut egui::Ui) {
-        if self.tabs.is_empty() {
-            return;
-        }
+    /// Draws the tab bar and returns what was clicked, for the pane to carry
+    /// out. Tabs are reordered by dragging them; files dragged onto a tab go
+    /// to its folder through `jobs`.
+    pub fn show_tabs(&mut self, ui: &mut egui::Ui, jobs: &JobManager) -> Option<TabRequest> {
+        let mut request = None;
 
         ui.horizontal(|ui| {
             ui.spacing_mut().item_spacing.x = 2.0;
 
-            let mut tab_to_close = None;
-            let mut tab_to_activate = None;
+            let mut tab_to_move = None;
+            let can_close = self.tabs.len() > 1;
 
-            for tab in &self.tabs {
+            for (index, tab) in self.tabs.iter().enumerate() {
                 let is_active = Some(tab.id) == self.active_tab_id;
-                
+
                 ui.group(|ui| {
                     ui.horizontal(|ui| {
                         ui.spacing_mut().item_spacing.x = 4.0;
-                        
-                        let tab_text = if tab.title.len() > 20 {
-                            format!("{}...", &tab.title[..17])
+
+                        let tab_text = if tab.title.chars().count() > 20 {
+                            format!("{}...", tab.title.chars().take(17).collect::<String>())
                         } else {
                             tab.title.clone()
                         };
 
-                        if ui.selectable_label(is_active, &tab_text)
+                        let response = ui.selectable_label(is_active, &tab_text)
                             .on_hover_text(tab.path.display().to_string())
-                            .clicked() 
-                        {
-                            tab_to_activate = Some(tab.id);
+                            .interact(egui::Sense::click_and_drag());
+                        if response.clicked() {
+                            request = Some(TabRequest::Activate(tab.id));
                         }
+                        drag_drop::drop_target(ui, response.rect, &tab.path, jobs);
 
-                        if ui.small_button("✕")
-                            .on_hover_text("Close tab")
-                            .clicked() 
+                        // Dropping a tab on another puts it in that one's place
+                        response.dnd_set_drag_payload(DraggedTab(tab.id));
+                        if response.dnd_hover_payload::<DraggedTab>().is_some_and(|dragged| dragged.0 != tab.id) {
+                            let x = response.rect.left() - 3.0;
+                            ui.painter().vline(x, response.rect.y_range(), ui.visuals().selection.stroke);
+                        }
+                        if let Some(dragged) = response.dnd_release_payload::<DraggedTab>() {
+                            tab_to_move = Some((dragged.0, index));
+                        }
+
+                        if can_close && ui.small_button("✕")
+                            .on_hover_text("Close tab (Ctrl+W)")
+                            .clicked()
                         {
-                            tab_to_close = Some(tab.id);
+                            request = Some(TabRequest::Close(tab.id));
                         }
                     });
                 });
             }
 
-            // Handle tab activation
-            if let Some(tab_id) = tab_to_activate {
-                self.activate_tab(tab_id);
-            }
-
-            // Handle tab closing
-            if let Some(tab_id) = tab_to_close {
-                self.close_tab(tab_id);
+            if let Some((tab_id, index)) = tab_to_move {
+                self.move_tab(tab_id, index);
             }
 
             ui.separator();
 
             // New tab button
-            if ui.button("+ New Tab").clicked() {
-                // TODO: Open new tab with current directory or home
-                if let Some(home) = dirs::home_dir() {
-                    self.add_tab("Home".to_string(), home);
-                }
+            if ui.button("+ New Tab").on_hover_text("Open this folder in a new tab (Ctrl+T)").clicked() {
+                request = Some(TabRequest::New);
             }
         });
+
+        request
     }
 
     pub fn add_tab(&mut self, title: String, path: PathBuf) {
         let tab = Tab::new(title, path);
         let tab_id = tab.id;
-        
+
+        self.tabs.push(tab);
+        self.activate_tab(tab_id);
+    }
+
+    /// Adds a tab for `state` after the others and makes it the active one.
+    /// The pane still has to show it.
+    pub fn open_tab(&mut self, state: TabState) {
+        let tab = Tab::with_state(state);
+        let tab_id = tab.id;
+
         self.tabs.push(tab);
         self.activate_tab(tab_id);
     }
 
+    /// Replaces the tabs with ones for `states`, the one at `active` first showing.
+    pub fn restore(&mut self, states: Vec<TabState>, active: usize) {
+        self.tabs = states.into_iter().map(Tab::with_state).collect();
+        self.active_tab_id = None;
+        if let Some(tab_id) = self.tabs.get(active).or(self.tabs.first()).map(|tab| tab.id) {
+            self.activate_tab(tab_id);
+        }
+    }
+
+    /// The state of every tab, with `active_state` for the active one, and
+    /// where the active one is.
+    pub fn states(&self, active_state: TabState) -> (Vec<TabState>, usize) {
+        let mut active = 0;
+        let mut active_state = Some(active_state);
+        let states = self.tabs.iter()
+            .enumerate()
+            .filter_map(|(index, tab)| {
+                if Some(tab.id) == self.active_tab_id {
+                    active = index;
+                    active_state.take()
+                } else {
+                    tab.state.clone()
+                }
+            })
+            .collect();
+        (states, active)
+    }
+
+    /// Moves the tab `tab_id` in front of the tab currently at `index`,
+    /// which is where the drop marker is drawn.
+    pub fn move_tab(&mut self, tab_id: uuid::Uuid, index: usize) {
+        if let Some(from) = self.tabs.iter().position(|t| t.id == tab_id) {
+            let tab = self.tabs.remove(from);
+            // Taking the tab out shifts everything after it one place left
+            let index = if from < index { index - 1 } else { index };
+            self.tabs.insert(index.min(self.tabs.len()), tab);
+        }
+    }
+
     pub fn activate_tab(&mut self, tab_id: uuid::Uuid) {
         self.active_tab_id = Some(tab_id);
-        
+
         // Update active status
         for tab in &mut self.tabs {
             tab.is_active = tab.id == tab_id;
@@ -116,7 +206,7 @@ impl TabManager {
     pub fn close_tab(&mut self, tab_id: uuid::Uuid) {
         if let Some(index) = self.tabs.iter().position(|t| t.id == tab_id) {
             self.tabs.remove(index);
-            
+
             // If this was the active tab, activate another one
             if Some(tab_id) == self.active_tab_id {
                 if !self.tabs.is_empty() {
@@ -130,23 +220,45 @@ impl TabManager {
         }
     }
 
+    pub fn len(&self) -> usize {
+        self.tabs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tabs.is_empty()
+    }
+
     pub fn get_active_tab(&self) -> Option<&Tab> {
         self.active_tab_id.and_then(|id| {
             self.tabs.iter().find(|t| t.id == id)
         })
     }
 
+    /// Keeps `state` in the active tab, as another is about to show.
+    pub fn store_active_state(&mut self, state: TabState) {
+        if let Some(tab) = self.active_tab_mut() {
+            tab.state = Some(state);
+        }
+    }
+
+    /// Takes the state the active tab kept, to show it.
+    pub fn take_active_state(&mut self) -> Option<TabState> {
+        self.active_tab_mut().and_then(|tab| tab.state.take())
+    }
+
     pub fn update_active_tab_path(&mut self, path: PathBuf) {
-        if let Some(active_id) = self.active_tab_id {
-            if let Some(tab) = self.tabs.iter_mut().find(|t| t.id == active_id) {
-                tab.path = path.clone();
-                tab.title = path.file_name()
-                    .unwrap_or_default()
-                    .to_string_lossy()
-                    .to_string();
+        if let Some(tab) = self.active_tab_mut() {
+            if tab.path != path {
+                tab.title = tab_title(&path);
+                tab.path = path;
             }
         }
     }
+
+    fn active_tab_mut(&mut self) -> Option<&mut Tab> {
+        let active_id = self.active_tab_id?;
+        self.tabs.iter_mut().find(|t| t.id == active_id)
+    }
 }
 
 impl Default for TabManager {
@@ -154,3 +266,51 @@ impl Default for TabManager {
         Self::new()
     }
 }
+
+/// A folder's name, or the whole path for a root.
+fn tab_title(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager(names: &[&str]) -> TabManager {
+        let mut tabs = TabManager::new();
+        for name in names {
+            tabs.add_tab(name.to_string(), PathBuf::from(format!("/{}", name)));
+        }
+        tabs
+    }
+
+    fn titles(tabs: &TabManager) -> Vec<&str> {
+        tabs.tabs.iter().map(|t| t.title.as_str()).collect()
+    }
+
+    #[test]
+    fn moving_right_lands_in_front_of_the_target() {
+        let mut tabs = manager(&["a", "b", "c", "d"]);
+        let a = tabs.tabs[0].id;
+        tabs.move_tab(a, 2);
+        assert_eq!(titles(&tabs), ["b", "a", "c", "d"]);
+    }
+
+    #[test]
+    fn moving_left_lands_in_front_of_the_target() {
+        let mut tabs = manager(&["a", "b", "c", "d"]);
+        let d = tabs.tabs[3].id;
+        tabs.move_tab(d, 1);
+        assert_eq!(titles(&tabs), ["a", "d", "b", "c"]);
+    }
+
+    #[test]
+    fn dropping_on_itself_keeps_the_order() {
+        let mut tabs = manager(&["a", "b", "c"]);
+        let b = tabs.tabs[1].id;
+        tabs.move_tab(b, 1);
+        assert_eq!(titles(&tabs), ["a", "b", "c"]);
+    }
+}
@@ -3,6 +3,7 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use crate::core::file_manager::FileManager;
+use crate::ui::app::PaneLayout;
 
 pub struct Toolbar {
     file_manager: Arc<Mutex<FileManager>>,
@@ -17,7 +18,20 @@ impl Toolbar {
         }
     }
 
-    pub fn show(&mut self, ui: &mut egui::Ui, runtime: &tokio::runtime::Runtime) {
+    /// Points the toolbar at `file_manager`, the pane it works on.
+    pub fn set_file_manager(&mut self, file_manager: Arc<Mutex<FileManager>>) {
+        self.file_manager = file_manager;
+    }
+
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        runtime: &tokio::runtime::Runtime,
+        history_open: &mut bool,
+        transfers_open: &mut bool,
+        duplicates_open: &mut bool,
+        disk_usage_open: &mut bool,
+    ) {
         ui.horizontal(|ui| {
             ui.spacing_mut().item_spacing.x = 8.0;
 
@@ -60,8 +74,7 @@ impl Toolbar {
             if ui.button("⬆").on_hover_text("Go Up (Alt+Up)").clicked() {
                 let file_manager = self.file_manager.clone();
                 runtime.spawn(async move {
-                    let mut fm = file_manager.lock().await;
-                    if let Err(e) = fm.go_up().await {
+                    if let Err(e) = FileManager::navigate_up(&file_manager).await {
                         log::error!("Failed to go up: {}", e);
                     }
                 });
@@ -73,9 +86,8 @@ impl Toolbar {
             if ui.button("🏠").on_hover_text("Go Home").clicked() {
                 let file_manager = self.file_manager.clone();
                 runtime.spawn(async move {
-                    let mut fm = file_manager.lock().await;
                     if let Some(home) = dirs::home_dir() {
-                        if let Err(e) = fm.navigate_to(&home).await {
+                        if let Err(e) = FileManager::navigate(&file_manager, &home).await {
                             log::error!("Failed to navigate to home: {}", e);
                         }
                     }
@@ -104,8 +116,7 @@ impl Toolbar {
                 let path = std::path::PathBuf::from(&self.address_bar_text);
                 let file_manager = self.file_manager.clone();
                 runtime.spawn(async move {
-                    let mut fm = file_manager.lock().await;
-                    if let Err(e) = fm.navigate_to(&path).await {
+                    if let Err(e) = FileManager::navigate(&file_manager, &path).await {
                         log::error!("Failed to navigate to path: {}", e);
                     }
                 });
@@ -114,7 +125,7 @@ impl Toolbar {
             ui.separator();
 
             // View options
-            if ui.button("🔄").on_hover_text("Refresh (F5)").clicked() {
+            if ui.button("🔄").on_hover_text("Refresh (F5, or Ctrl+R in dual-pane mode)").clicked() {
                 let file_manager = self.file_manager.clone();
                 runtime.spawn(async move {
                     let mut fm = file_manager.lock().await;
@@ -144,6 +155,20 @@ impl Toolbar {
             if ui.button("🔍").on_hover_text("Search").clicked() {
                 // TODO: Implement search dialog
             }
+
+            ui.toggle_value(history_open, "🕘").on_hover_text("Operation History");
+            ui.toggle_value(transfers_open, "⇅").on_hover_text("Transfers");
+            ui.toggle_value(duplicates_open, "⧉").on_hover_text("Find Duplicate Files");
+            ui.toggle_value(disk_usage_open, "📊").on_hover_text("Disk Usage");
         });
     }
+
+    /// Toggles for dual-pane mode and, in it, synchronized navigation.
+    pub fn show_layout(ui: &mut egui::Ui, layout: &mut PaneLayout) {
+        ui.separator();
+        ui.toggle_value(&mut layout.dual_pane, "◫").on_hover_text("Dual-Pane Mode (Tab switches panes)");
+        if layout.dual_pane {
+            ui.toggle_value(&mut layout.sync_navigation, "⇄").on_hover_text("Synchronized Navigation");
+        }
+    }
 }
@@ -0,0 +1,211 @@
+use eframe::egui;
+
+use crate::operations::checksum::HashAlgorithm;
+use crate::operations::conflict::ConflictPolicy;
+use crate::operations::copy::CopyErrorMode;
+use crate::operations::jobs::{Job, JobManager, JobState};
+use crate::utils::format::format_file_size;
+
+/// Window listing queued and running jobs, with pause, resume, cancel and reorder.
+pub struct TransfersPanel {
+    jobs: JobManager,
+    pub open: bool,
+}
+
+impl TransfersPanel {
+    pub fn new(jobs: JobManager) -> Self {
+        Self {
+            jobs,
+            open: false,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        if !self.open {
+            return;
+        }
+
+        let jobs = self.jobs.jobs();
+
+        let mut open = self.open;
+        egui::Window::new("Transfers")
+            .open(&mut open)
+            .default_width(420.0)
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let mut max_parallel = self.jobs.max_parallel();
+                    ui.label("Run at once:");
+                    if ui.add(egui::DragValue::new(&mut max_parallel).range(1..=8)).changed() {
+                        self.jobs.set_max_parallel(max_parallel);
+                    }
+
+                    let mut policy = self.jobs.conflict_policy();
+                    ui.label("When files exist:");
+                    egui::ComboBox::from_id_source("conflict_policy")
+                        .selected_text(policy.label())
+                        .show_ui(ui, |ui| {
+                            for option in ConflictPolicy::ALL {
+                                ui.selectable_value(&mut policy, option, option.label());
+                            }
+                        });
+                    if policy != self.jobs.conflict_policy() {
+                        self.jobs.set_conflict_policy(policy);
+                    }
+
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.button("Clear Finished").clicked() {
+                            self.jobs.clear_finished();
+                        }
+                    });
+                });
+
+                ui.horizontal(|ui| {
+                    let mut workers = self.jobs.copy_workers();
+                    ui.label("Files at once:");
+                    if ui.add(egui::DragValue::new(&mut workers).range(1..=32)).changed() {
+                        self.jobs.set_copy_workers(workers);
+                    }
+
+                    let mut error_mode = self.jobs.copy_error_mode();
+                    ui.label("On errors:");
+                    egui::ComboBox::from_id_source("copy_error_mode")
+                        .selected_text(error_mode.label())
+                        .show_ui(ui, |ui| {
+                            for option in CopyErrorMode::ALL {
+                                ui.selectable_value(&mut error_mode, option, option.label());
+                            }
+                        });
+                    if error_mode != self.jobs.copy_error_mode() {
+                        self.jobs.set_copy_error_mode(error_mode);
+                    }
+
+                    let mut verify = self.jobs.verify();
+                    ui.label("Verify:");
+                    egui::ComboBox::from_id_source("verify_copies")
+                        .selected_text(verify.map_or("Off", HashAlgorithm::label))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut verify, None, "Off");
+                            for option in HashAlgorithm::ALL {
+                                ui.selectable_value(&mut verify, Some(option), option.label());
+                            }
+                        });
+                    if verify != self.jobs.verify() {
+                        self.jobs.set_verify(verify);
+                    }
+                });
+
+                ui.separator();
+
+                if jobs.is_empty() {
+                    ui.label("No transfers");
+                    return;
+                }
+
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for job in &jobs {
+                            self.show_job(ui, job);
+                            ui.separator();
+                        }
+                    });
+            });
+        self.open = open;
+    }
+
+    fn show_job(&self, ui: &mut egui::Ui, job: &Job) {
+        ui.horizontal(|ui| {
+            ui.label(job.kind.description());
+
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                match job.state {
+                    JobState::Queued => {
+                        if ui.small_button("✖").on_hover_text("Cancel").clicked() {
+                            self.jobs.cancel(job.id);
+                        }
+                        if ui.small_button("▶").on_hover_text("Start now").clicked() {
+                            self.jobs.start_now(job.id);
+                        }
+                        if ui.small_button("⏷").on_hover_text("Move down").clicked() {
+                            self.jobs.move_down(job.id);
+                        }
+                        if ui.small_button("⏶").on_hover_text("Move up").clicked() {
+                            self.jobs.move_up(job.id);
+                        }
+                    }
+                    JobState::Running => {
+                        if ui.small_button("✖").on_hover_text("Cancel").clicked() {
+                            self.jobs.cancel(job.id);
+                        }
+                        if ui.small_button("⏸").on_hover_text("Pause").clicked() {
+                            self.jobs.pause(job.id);
+                        }
+                    }
+                    JobState::Paused => {
+                        if ui.small_button("✖").on_hover_text("Cancel").clicked() {
+                            self.jobs.cancel(job.id);
+                        }
+                        if ui.small_button("▶").on_hover_text("Resume").clicked() {
+                            self.jobs.resume(job.id);
+                        }
+                    }
+                    _ => {}
+                }
+            });
+        });
+
+        match (&job.state, &job.progress) {
+            (JobState::Queued, _) => {
+                ui.weak("Queued");
+            }
+            (JobState::Running | JobState::Paused, Some(progress)) => {
+                let mut text = format!("{} of {} files", progress.completed_files, progress.total_files);
+                if job.state == JobState::Paused {
+                    text.push_str(" (paused)");
+                }
+                ui.add(egui::ProgressBar::new(progress.fraction()).text(text))
+                    .on_hover_text(progress.current_file.display().to_string());
+
+                ui.horizontal(|ui| {
+                    let name = progress.current_file.file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    if progress.current_file_size > 0 {
+                        ui.weak(format!(
+                            "{} ({} of {})",
+                            name,
+                            format_file_size(progress.current_file_bytes),
+                            format_file_size(progress.current_file_size)
+                        ));
+                    } else {
+                        ui.weak(name);
+                    }
+
+                    if job.state == JobState::Running {
+                        if let Some(rate) = progress.rate_text() {
+                            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                ui.weak(rate);
+                            });
+                        }
+                    }
+                });
+            }
+            (JobState::Running, None) => {
+                ui.add(egui::ProgressBar::new(0.0).animate(true));
+            }
+            (JobState::Paused, None) => {
+                ui.weak("Paused");
+            }
+            (JobState::Completed, _) => {
+                ui.weak("Done");
+            }
+            (JobState::Cancelled, _) => {
+                ui.weak("Cancelled");
+            }
+            (JobState::Failed(error), _) => {
+                ui.colored_label(ui.style().visuals.error_fg_color, error);
+            }
+        }
+    }
+}
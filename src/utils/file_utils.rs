@@ -1,8 +1,15 @@
 use std::path::{Path, PathBuf};
 use anyhow::Result;
 
+use crate::core::vfs::{LocalFs, Vfs};
+
 pub fn ensure_unique_filename(path: &Path) -> PathBuf {
-    if !path.exists() {
+    ensure_unique_filename_in(&LocalFs, path)
+}
+
+/// Like [`ensure_unique_filename`], checking for existing names on `vfs`.
+pub fn ensure_unique_filename_in(vfs: &dyn Vfs, path: &Path) -> PathBuf {
+    if !vfs.exists(path) {
         return path.to_path_buf();
     }
 
@@ -19,7 +26,7 @@ pub fn ensure_unique_filename(path: &Path) -> PathBuf {
         };
 
         let new_path = parent.join(new_name);
-        if !new_path.exists() {
+        if !vfs.exists(&new_path) {
             return new_path;
         }
         counter += 1;